@@ -1,5 +1,84 @@
 type Precision = f64;
 use std::f64::consts as C;
+use std::ops::{Add, Div, Mul, Neg};
+
+/// Minimal complex number, used for evaluating the frequency response of filters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Complex {
+    pub(crate) re: Precision,
+    pub(crate) im: Precision,
+}
+
+impl Complex {
+    pub(crate) const ONE: Self = Self::new(1.0, 0.0);
+
+    pub(crate) const fn new(re: Precision, im: Precision) -> Self {
+        Self { re, im }
+    }
+
+    /// The point on the unit circle at the given angle, in radians
+    pub(crate) fn from_angle(angle: Precision) -> Self {
+        let (im, re) = angle.sin_cos();
+        Self { re, im }
+    }
+
+    /// Magnitude
+    pub(crate) fn norm(&self) -> Precision {
+        self.re.hypot(self.im)
+    }
+
+    /// Phase in radians, -pi -- pi
+    pub(crate) fn arg(&self) -> Precision {
+        self.im.atan2(self.re)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Mul<Precision> for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Precision) -> Self {
+        Self::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let denominator = rhs.re * rhs.re + rhs.im * rhs.im;
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) / denominator,
+            (self.im * rhs.re - self.re * rhs.im) / denominator,
+        )
+    }
+}
+
+impl Neg for Complex {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
 
 pub(crate) struct Biquad {
     b0: Precision,
@@ -52,6 +131,16 @@ impl Biquad {
         self.a2 = a2;
     }
 
+    /// The complex gain of the filter at frequency `f`, evaluated from the coefficients
+    pub(crate) fn frequency_response(&self, f: Precision, sr: Precision) -> Complex {
+        // z^-1 on the unit circle
+        let z1 = Complex::from_angle(-C::TAU * f / sr);
+        let z2 = z1 * z1;
+        let numerator = Complex::new(self.b0, 0.0) + z1 * self.b1 + z2 * self.b2;
+        let denominator = Complex::ONE + z1 * self.a1 + z2 * self.a2;
+        numerator / denominator
+    }
+
     pub(crate) fn is_stable(&self) -> bool {
         // |a1| < 2  &&  |a1| − 1 < a2 < 1
         (self.a1.abs() < 2.0) && ((self.a1.abs() - 1.0) < self.a2 && self.a2 < 1.0)
@@ -92,6 +181,11 @@ impl<T: FixedQFilterKind> FixedQFilter<T> {
         self.biquad.process_sample(x0)
     }
 
+    /// The complex gain of the filter at frequency `f`
+    pub(crate) fn frequency_response(&self, f: Precision) -> Complex {
+        self.biquad.frequency_response(f, self.sr)
+    }
+
     pub(crate) fn new(frequency: Precision, sample_rate: Precision) -> Self {
         let coeffs = T::coefficients(frequency, sample_rate);
         Self {
@@ -144,6 +238,11 @@ impl<T: GainlessFilterKind> GainlessFilter<T> {
         self.biquad.process_sample(x0)
     }
 
+    /// The complex gain of the filter at frequency `f`
+    pub(crate) fn frequency_response(&self, f: Precision) -> Complex {
+        self.biquad.frequency_response(f, self.sr)
+    }
+
     pub(crate) fn new(frequency: Precision, q: Precision, sample_rate: Precision) -> Self {
         let coeffs = T::coefficients(frequency, q, sample_rate);
         Self {
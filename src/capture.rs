@@ -0,0 +1,194 @@
+//! Lock-free capture of the audio signal, used by the spectrum analyzer and scope in the editor.
+//!
+//! The audio thread writes into a ring of atomics, and the editor copies out the most recent
+//! samples. The editor may read while the audio thread is writing, so a read can contain samples
+//! from two different buffers. That is fine for display purposes.
+//!
+//! The full-rate buffers feed the spectrum analyzer, the scope gets decimated frames.
+
+use atomic_float::AtomicF32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of samples kept by each capture buffer, must be a power of 2
+pub(crate) const CAPTURE_SIZE: usize = 4096;
+
+pub(crate) struct CaptureBuffer {
+    samples: [AtomicF32; CAPTURE_SIZE],
+    /// Index where the next sample will be written
+    write_pos: AtomicUsize,
+}
+
+impl Default for CaptureBuffer {
+    fn default() -> Self {
+        Self {
+            samples: std::array::from_fn(|_| AtomicF32::new(0.0)),
+            write_pos: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl CaptureBuffer {
+    /// Called by the audio thread
+    pub(crate) fn push(&self, sample: f32) {
+        let pos = self.write_pos.load(Ordering::Relaxed);
+        self.samples[pos].store(sample, Ordering::Relaxed);
+        self.write_pos
+            .store((pos + 1) % CAPTURE_SIZE, Ordering::Release);
+    }
+
+    /// Copy the most recent samples into `out`, oldest first. `out` can't be longer than
+    /// [`CAPTURE_SIZE`].
+    pub(crate) fn read_latest(&self, out: &mut [f32]) {
+        debug_assert!(out.len() <= CAPTURE_SIZE);
+
+        let end = self.write_pos.load(Ordering::Acquire);
+        let start = end + CAPTURE_SIZE - out.len();
+        for (i, sample) in out.iter_mut().enumerate() {
+            *sample = self.samples[(start + i) % CAPTURE_SIZE].load(Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        for sample in self.samples.iter() {
+            sample.store(0.0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Captures {
+    /// The signal before processing after the lookahead delay, summed to mono
+    pub(crate) input: CaptureBuffer,
+    /// The signal after processing, summed to mono
+    pub(crate) output: CaptureBuffer,
+    pub(crate) scope: ScopeCapture,
+    /// Sample rate of the captured signal
+    pub(crate) sample_rate: AtomicF32,
+}
+
+/// Number of frames kept by the scope
+pub(crate) const SCOPE_FRAMES: usize = 4096;
+/// Number of samples summarized by a single scope frame
+pub(crate) const SCOPE_DECIMATION: usize = 128;
+/// Number of trigger markers kept by the scope
+const SCOPE_TRIGGERS: usize = 64;
+
+/// Summary of [`SCOPE_DECIMATION`] samples
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScopeFrame {
+    /// Absolute peak of the input, after the lookahead delay
+    pub(crate) input: f32,
+    /// Absolute peak of the output
+    pub(crate) output: f32,
+    /// Gain reduction of the low, mid and high bands in dB, negative values are boosts
+    pub(crate) gain_reduction: [f32; 3],
+    /// Position of the frame in the song in beats, compensated for the lookahead. NaN if the
+    /// transport isn't playing.
+    pub(crate) beat: f32,
+}
+
+impl Default for ScopeFrame {
+    fn default() -> Self {
+        Self {
+            input: 0.0,
+            output: 0.0,
+            gain_reduction: [0.0; 3],
+            beat: f32::NAN,
+        }
+    }
+}
+
+/// Decimated history of the signal and gain reduction, for the scope in the editor.
+///
+/// Frames are numbered from the start of the session. The frame number of a trigger marker is
+/// where the note lands in the delayed audio, so it may be ahead of the latest frame.
+pub(crate) struct ScopeCapture {
+    input: Box<[AtomicF32]>,
+    output: Box<[AtomicF32]>,
+    gain_reduction: Box<[[AtomicF32; 3]]>,
+    beat: Box<[AtomicF32]>,
+    /// Number of the next frame to be written
+    frame_count: AtomicUsize,
+    triggers: [AtomicUsize; SCOPE_TRIGGERS],
+    /// Number of the next trigger to be written
+    trigger_count: AtomicUsize,
+}
+
+impl Default for ScopeCapture {
+    fn default() -> Self {
+        // these are large, so keep them on the heap
+        let atomics = || (0..SCOPE_FRAMES).map(|_| AtomicF32::new(0.0)).collect();
+        Self {
+            input: atomics(),
+            output: atomics(),
+            gain_reduction: (0..SCOPE_FRAMES)
+                .map(|_| std::array::from_fn(|_| AtomicF32::new(0.0)))
+                .collect(),
+            beat: (0..SCOPE_FRAMES)
+                .map(|_| AtomicF32::new(f32::NAN))
+                .collect(),
+            frame_count: AtomicUsize::new(0),
+            triggers: std::array::from_fn(|_| AtomicUsize::new(usize::MAX)),
+            trigger_count: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ScopeCapture {
+    /// Number of the next frame to be written, called by the audio thread
+    pub(crate) fn frame_count(&self) -> usize {
+        self.frame_count.load(Ordering::Relaxed)
+    }
+
+    /// Called by the audio thread
+    pub(crate) fn push_frame(&self, frame: &ScopeFrame) {
+        let count = self.frame_count.load(Ordering::Relaxed);
+        let i = count % SCOPE_FRAMES;
+        self.input[i].store(frame.input, Ordering::Relaxed);
+        self.output[i].store(frame.output, Ordering::Relaxed);
+        for (atomic, value) in self.gain_reduction[i].iter().zip(frame.gain_reduction) {
+            atomic.store(value, Ordering::Relaxed);
+        }
+        self.beat[i].store(frame.beat, Ordering::Relaxed);
+        self.frame_count.store(count + 1, Ordering::Release);
+    }
+
+    /// Mark a note landing at the given frame, called by the audio thread
+    pub(crate) fn push_trigger(&self, frame: usize) {
+        let count = self.trigger_count.load(Ordering::Relaxed);
+        self.triggers[count % SCOPE_TRIGGERS].store(frame, Ordering::Relaxed);
+        self.trigger_count.store(count + 1, Ordering::Release);
+    }
+
+    /// Return the number of the first frame, and up to `len` of the most recent frames, oldest
+    /// first
+    pub(crate) fn read_frames(&self, len: usize) -> (usize, Vec<ScopeFrame>) {
+        let end = self.frame_count.load(Ordering::Acquire);
+        let len = len.min(SCOPE_FRAMES).min(end);
+        let start = end - len;
+        let frames = (start..end)
+            .map(|n| {
+                let i = n % SCOPE_FRAMES;
+                ScopeFrame {
+                    input: self.input[i].load(Ordering::Relaxed),
+                    output: self.output[i].load(Ordering::Relaxed),
+                    gain_reduction: self.gain_reduction[i]
+                        .each_ref()
+                        .map(|x| x.load(Ordering::Relaxed)),
+                    beat: self.beat[i].load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        (start, frames)
+    }
+
+    /// The frame numbers of the most recent triggers, in no particular order
+    pub(crate) fn read_triggers(&self) -> Vec<usize> {
+        let count = self.trigger_count.load(Ordering::Acquire);
+        self.triggers
+            .iter()
+            .take(count.min(SCOPE_TRIGGERS))
+            .map(|x| x.load(Ordering::Relaxed))
+            .collect()
+    }
+}
@@ -1,24 +1,61 @@
 use std::f32::consts::PI;
 
+use nih_plug::prelude::Enum;
+
 use crate::pattern::Pattern;
 
-#[derive(Debug)]
+/// Level at which an analog release is considered silent (-60 dB)
+const SILENCE_THRESHOLD: f32 = 0.001;
+
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum ReleaseModel {
+    /// The release follows the release curve, and reaches zero after the release duration
+    #[id = "time"]
+    #[name = "Time-bounded"]
+    TimeBounded,
+    /// The release decays exponentially like a compressor's RC circuit, the release duration is
+    /// the time constant
+    #[id = "analog"]
+    #[name = "Analog"]
+    Analog,
+}
+
+#[derive(Debug, Clone)]
 pub(crate) struct Envelope {
     sr: f32,
 
     // I'm storing samples, because the samplerate shouldn't change in the middle of the song
     delay_samples: f32,             // samples
     attack_samples: f32,            // samples
+    hold_samples: f32,              // samples
     release_samples: f32,           // samples
     delay_samples_remaining: f32,   // samples
     attack_samples_remaining: f32,  // samples
+    hold_samples_remaining: f32,    // samples
     release_samples_remaining: f32, // samples
 
     // Also store original seconds for faster comparisons
     delay_seconds: f32,   // seconds
     attack_seconds: f32,  // seconds
+    hold_seconds: f32,    // seconds
     release_seconds: f32, // seconds
 
+    /// While sustained, the envelope stays in the hold stage indefinitely (e.g. while a note is
+    /// held down). The hold duration only starts counting down after the sustain is released.
+    sustain: bool,
+
+    /// The value the delay stage starts from, this is above 0.0 for legato retriggers
+    start_level: f32,
+    /// The value the delay stage ends at and the attack stage starts from. For legato retriggers,
+    /// this is where the replaced envelope would be by then.
+    delay_end_level: f32,
+    /// The value the release stage starts from, this is below 1.0 if the envelope was choked
+    release_level: f32,
+    /// The last value returned by `tick()`
+    last_value: f32,
+
+    release_model: ReleaseModel,
+
     // curves that define this envelope
     attack_curve: Curve,
     release_curve: Curve,
@@ -30,6 +67,7 @@ impl Envelope {
         sample_rate: f32,
         delay_seconds: f32,
         attack_seconds: f32,
+        hold_seconds: f32,
         release_seconds: f32,
         attack_curve: Curve,
         release_curve: Curve,
@@ -37,87 +75,185 @@ impl Envelope {
         // convert seconds to samples
         let delay_samples = sample_rate * delay_seconds;
         let attack_samples = sample_rate * attack_seconds;
+        let hold_samples = sample_rate * hold_seconds;
         let release_samples = sample_rate * release_seconds;
 
         Self {
             sr: sample_rate,
             delay_samples,
             attack_samples,
+            hold_samples,
             release_samples,
             delay_samples_remaining: delay_samples,
             attack_samples_remaining: attack_samples,
+            hold_samples_remaining: hold_samples,
             release_samples_remaining: release_samples,
             delay_seconds,
             attack_seconds,
+            hold_seconds,
             release_seconds,
+            sustain: false,
+            start_level: 0.0,
+            delay_end_level: 0.0,
+            release_level: 1.0,
+            last_value: 0.0,
+            release_model: ReleaseModel::TimeBounded,
             attack_curve,
             release_curve,
         }
     }
 
+    /// Create an envelope that peaks at the given latency plus offset (in seconds).
+    ///
+    /// The offset may be negative, but the peak can't be earlier than the start of the
+    /// envelope. If there isn't enough time left for the full precomp, the attack is shortened.
     pub(crate) fn from_latency(
         sr: f32,
         latency_seconds: f32,
+        offset_seconds: f32,
         precomp: f32,
+        hold: f32,
         decay: f32,
         attack_curve: Curve,
         release_curve: Curve,
     ) -> Self {
+        let peak_seconds = (latency_seconds + offset_seconds).max(0.0);
+        let attack_seconds = precomp.min(peak_seconds);
+
         Self::new(
             sr,
-            latency_seconds - precomp,
-            precomp,
+            peak_seconds - attack_seconds,
+            attack_seconds,
+            hold,
             decay,
             attack_curve,
             release_curve,
         )
     }
 
-    // This method is not recommended!
-    // If you have a multiband setup, where each band has a different attack speed,
-    // this may only update some bands' attack and not other bands.
-    //
-    // /// Update the attack duration of the envelope (in seconds).
-    // /// If the envelope is still in delay, this will attempt to decrease the delay and increase the attack, keeping the total sum (delay + attack) the same.
-    // /// If the envelope is already in attack, this does nothing.
-    // pub(crate) fn set_attack(&mut self, attack: f32) {
-    //     // convert seconds to samples
-    //     let attack = self.sr * attack;
-
-    //     // do nothing if attack is unchanged
-    //     if attack == self.attack {
-    //         return;
-    //     }
-
-    //     if self.delay_remaining > 0.0 || self.attack == self.attack_remaining {
-    //         // still in delay stage || beginning of attack stage, but not done anything yet
-
-    //         if attack >= self.attack {
-    //             // new attack is longer
-    //             // only allowed if remaining delay is lage enough
-    //             let diff = attack - self.attack;
-    //             if diff <= self.delay_remaining {
-    //                 self.delay_remaining -= diff;
-    //                 self.delay -= diff;
-    //                 self.attack_remaining += diff;
-    //                 self.attack += diff;
-    //             }
-    //         } else {
-    //             // new attack is shorter
-    //             // this is always allowed
-    //             let diff = self.attack - attack;
-    //             self.delay_remaining += diff;
-    //             self.delay += diff;
-    //             self.attack_remaining -= diff;
-    //             self.attack -= diff;
-    //         }
-    //     } else {
-    //         // in attack stage or past it, nothing can be done
-    //     }
-    // }
+    /// Whether [`Self::set_attack()`] can change the attack to the given duration (in seconds).
+    ///
+    /// The attack can only be changed before it has started, and only if the new attack start
+    /// hasn't already passed.
+    pub(crate) fn can_set_attack(&self, attack_seconds: f32) -> bool {
+        let attack_started = !(self.delay_samples_remaining > 0.0
+            || (self.attack_samples > 0.0 && self.attack_samples_remaining == self.attack_samples));
+        if attack_started {
+            return false;
+        }
+
+        let peak_samples = self.delay_samples + self.attack_samples;
+        let attack_samples = (self.sr * attack_seconds).min(peak_samples);
+        let elapsed_samples = self.delay_samples - self.delay_samples_remaining;
+
+        peak_samples - attack_samples >= elapsed_samples
+    }
+
+    /// Update the attack duration of the envelope (in seconds), moving the boundary between the
+    /// delay and attack so that the peak stays at the same time.
+    /// Does nothing if [`Self::can_set_attack()`] returns false.
+    ///
+    /// Changing only some bands of a multiband setup would make them drift apart, so callers
+    /// should check [`Self::can_set_attack()`] on all bands before updating any of them.
+    pub(crate) fn set_attack(&mut self, attack_seconds: f32) {
+        if !self.can_set_attack(attack_seconds) {
+            return;
+        }
+
+        let peak_samples = self.delay_samples + self.attack_samples;
+        let attack_samples = (self.sr * attack_seconds).min(peak_samples);
+        let elapsed_samples = self.delay_samples - self.delay_samples_remaining;
+
+        self.delay_samples = peak_samples - attack_samples;
+        self.delay_samples_remaining = self.delay_samples - elapsed_samples;
+        self.delay_seconds = self.delay_samples / self.sr;
+        self.attack_samples = attack_samples;
+        self.attack_samples_remaining = attack_samples;
+        self.attack_seconds = attack_samples / self.sr;
+    }
+
+    /// Keep the envelope at its maximum value after the attack, until the sustain is turned off.
+    pub(crate) fn set_sustain(&mut self, sustain: bool) {
+        self.sustain = sustain;
+    }
+
+    /// Switch to the given release model. The release duration is recalculated, unless the release
+    /// has already started.
+    pub(crate) fn set_release_model(&mut self, release_model: ReleaseModel) {
+        self.release_model = release_model;
+
+        if self.release_samples == self.release_samples_remaining {
+            let release_samples = self.release_duration_samples(self.release_seconds);
+            self.release_samples = release_samples;
+            self.release_samples_remaining = release_samples;
+        }
+    }
+
+    /// Number of samples that the release stage lasts for, for the given release parameter
+    fn release_duration_samples(&self, release_seconds: f32) -> f32 {
+        match self.release_model {
+            ReleaseModel::TimeBounded => self.sr * release_seconds,
+            // the release ends once it decays below the silence threshold
+            ReleaseModel::Analog => self.sr * release_seconds * (1.0 / SILENCE_THRESHOLD).ln(),
+        }
+    }
+
+    /// Continue from the envelope that this one replaces, instead of starting from zero. The
+    /// delay stage keeps decaying from the next value of `previous` to the value it would have
+    /// reached by the end of the delay, then the attack stage rises from there.
+    pub(crate) fn continue_from(&mut self, previous: &Envelope) {
+        let mut previous = previous.clone();
+        self.start_level = previous.clone().tick().unwrap_or(0.0);
+        previous.skip(self.delay_samples);
+        self.delay_end_level = previous.tick().unwrap_or(0.0);
+    }
+
+    /// Advance the envelope by the given number of samples, without computing its values
+    fn skip(&mut self, mut samples: f32) {
+        let mut consume = |remaining: &mut f32| {
+            let consumed = remaining.max(0.0).min(samples);
+            *remaining -= consumed;
+            samples -= consumed;
+        };
+
+        consume(&mut self.delay_samples_remaining);
+        consume(&mut self.attack_samples_remaining);
+        // the hold stage doesn't elapse while the envelope is sustained
+        if self.sustain {
+            return;
+        }
+        consume(&mut self.hold_samples_remaining);
+        consume(&mut self.release_samples_remaining);
+    }
+
+    /// Update the morph amount of the attack and release curves (if they are morph curves)
+    pub(crate) fn set_morph(&mut self, amount: f32) {
+        self.attack_curve.set_morph(amount);
+        self.release_curve.set_morph(amount);
+    }
+
+    /// The last value returned by `tick()`, or 0.0 if it hasn't been ticked yet
+    pub(crate) fn value(&self) -> f32 {
+        self.last_value
+    }
+
+    /// Skip to the release stage, fading out from the current value over the given duration
+    /// (in seconds). Used when the envelope gets cut off by another envelope.
+    pub(crate) fn choke(&mut self, release_seconds: f32) {
+        let release_samples = self.sr * release_seconds;
+
+        self.sustain = false;
+        self.delay_samples_remaining = 0.0;
+        self.attack_samples_remaining = 0.0;
+        self.hold_samples_remaining = 0.0;
+        self.release_samples = release_samples;
+        self.release_samples_remaining = release_samples;
+        self.release_seconds = release_seconds;
+        self.release_level = self.last_value;
+    }
 
     /// Update the release duration of the envelope (in seconds).
-    /// If the envelope is still in attack/delay, this will reset the duration
+    /// If the envelope is still in delay/attack/hold, this will reset the duration
     /// If the envelope is already releasing, only the remaining duration will be affected.
     pub(crate) fn set_release(&mut self, release_seconds: f32) {
         if self.release_seconds == release_seconds {
@@ -125,7 +261,7 @@ impl Envelope {
         }
 
         // convert seconds to samples
-        let release_samples = self.sr * release_seconds;
+        let release_samples = self.release_duration_samples(release_seconds);
 
         // do nothing if release is unchanged
         if release_samples == self.release_samples {
@@ -133,8 +269,11 @@ impl Envelope {
         }
 
         if (
-            // still in attack/delay stage
-            self.delay_samples_remaining > 0.0 || self.attack_samples_remaining > 0.0
+            // still in delay/attack/hold stage
+            self.sustain
+                || self.delay_samples_remaining > 0.0
+                || self.attack_samples_remaining > 0.0
+                || self.hold_samples_remaining > 0.0
         ) || (
             // beginning of release stage, but not done anything yet
             self.release_samples == self.release_samples_remaining
@@ -155,21 +294,24 @@ impl Envelope {
     }
 
     pub(crate) fn is_complete(&self) -> bool {
-        self.delay_samples_remaining <= 0.0
+        !self.sustain
+            && self.delay_samples_remaining <= 0.0
             && self.attack_samples_remaining <= 0.0
+            && self.hold_samples_remaining <= 0.0
             && self.release_samples_remaining <= 0.0
     }
 
     pub(crate) fn duration_samples(&self) -> f32 {
-        self.delay_samples + self.attack_samples + self.release_samples
+        self.delay_samples + self.attack_samples + self.hold_samples + self.release_samples
     }
 
     /// Return the progress of this envelope in percentage (0.0 to 1.0)
     pub(crate) fn progress(&self) -> f32 {
         1.0 - ((self.delay_samples_remaining
             + self.attack_samples_remaining
+            + self.hold_samples_remaining
             + self.release_samples_remaining)
-            / (self.delay_samples + self.attack_samples + self.release_samples))
+            / self.duration_samples())
     }
 
     /// Get the current value (from 0.0 -- 1.0), then increment the state.
@@ -178,23 +320,45 @@ impl Envelope {
     /// Note: This should be called once per sample.
     pub(crate) fn tick(&mut self) -> Option<f32> {
         if self.delay_samples_remaining > 0.0 {
-            // in delay phase
+            // in delay phase, legato retriggers keep decaying towards the start of the attack
+            let x = 1.0 - self.delay_samples_remaining / self.delay_samples;
+            let y = self.start_level + (self.delay_end_level - self.start_level) * x;
+
             self.delay_samples_remaining -= 1.0;
-            Some(0.0)
+            self.last_value = y;
+
+            Some(y)
         } else if self.attack_samples_remaining > 0.0 {
             // in attack phase
             let x = 1.0 - self.attack_samples_remaining / self.attack_samples;
-            let y = self.attack_curve.get_y(x);
+            let y =
+                self.delay_end_level + (1.0 - self.delay_end_level) * self.attack_curve.get_y(x);
 
             self.attack_samples_remaining -= 1.0;
+            self.last_value = y;
 
             Some(y)
+        } else if self.sustain || self.hold_samples_remaining > 0.0 {
+            // in hold phase, stay at the maximum value
+            // the hold duration doesn't elapse while the envelope is sustained
+            if !self.sustain {
+                self.hold_samples_remaining -= 1.0;
+            }
+            self.last_value = 1.0;
+
+            Some(1.0)
         } else if self.release_samples_remaining > 0.0 {
             // in release phase
             let x = 1.0 - self.release_samples_remaining / self.release_samples;
-            let y = 1.0 - self.release_curve.get_y(x);
+            let y = match self.release_model {
+                ReleaseModel::TimeBounded => 1.0 - self.release_curve.get_y(x),
+                // decays from 1.0 to the silence threshold
+                ReleaseModel::Analog => SILENCE_THRESHOLD.powf(x),
+            };
+            let y = self.release_level * y;
 
             self.release_samples_remaining -= 1.0;
+            self.last_value = y;
 
             Some(y)
         } else {
@@ -210,42 +374,582 @@ impl Default for Envelope {
             sr: Default::default(),
             delay_samples: Default::default(),
             attack_samples: Default::default(),
+            hold_samples: Default::default(),
             release_samples: Default::default(),
             delay_samples_remaining: Default::default(),
             attack_samples_remaining: Default::default(),
+            hold_samples_remaining: Default::default(),
             release_samples_remaining: Default::default(),
             delay_seconds: Default::default(),
             attack_seconds: Default::default(),
+            hold_seconds: Default::default(),
             release_seconds: Default::default(),
-            attack_curve: Curve::EaseInSine,
-            release_curve: Curve::EaseInOutSine,
+            sustain: false,
+            start_level: 0.0,
+            delay_end_level: 0.0,
+            release_level: 1.0,
+            last_value: 0.0,
+            release_model: ReleaseModel::TimeBounded,
+            attack_curve: Curve::new(CurveShape::EaseInSine, 0.0),
+            release_curve: Curve::new(CurveShape::EaseInOutSine, 0.0),
         }
     }
 }
 
+/// Steepness of the exponential (RC) curve, in time constants
+const RC_TIME_CONSTANTS: f32 = 5.0;
+/// Steepness of the logarithmic curve
+const LOG_STEEPNESS: f32 = 50.0;
+
+/// The user-selectable curve shapes, see [`Curve::get_y()`]
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum CurveShape {
+    #[id = "linear"]
+    #[name = "Linear"]
+    Linear,
+    #[id = "ease_in_sine"]
+    #[name = "Sine in"]
+    EaseInSine,
+    #[id = "ease_out_sine"]
+    #[name = "Sine out"]
+    EaseOutSine,
+    #[id = "ease_in_out_sine"]
+    #[name = "Sine in-out"]
+    EaseInOutSine,
+    #[id = "ease_in_quad"]
+    #[name = "Quad in"]
+    EaseInQuad,
+    #[id = "ease_out_quad"]
+    #[name = "Quad out"]
+    EaseOutQuad,
+    #[id = "ease_in_out_quad"]
+    #[name = "Quad in-out"]
+    EaseInOutQuad,
+    #[id = "ease_in_cubic"]
+    #[name = "Cubic in"]
+    EaseInCubic,
+    #[id = "ease_out_cubic"]
+    #[name = "Cubic out"]
+    EaseOutCubic,
+    #[id = "ease_in_out_cubic"]
+    #[name = "Cubic in-out"]
+    EaseInOutCubic,
+    #[id = "ease_in_expo"]
+    #[name = "Expo in"]
+    EaseInExpo,
+    #[id = "ease_out_expo"]
+    #[name = "Expo out"]
+    EaseOutExpo,
+    #[id = "ease_in_out_expo"]
+    #[name = "Expo in-out"]
+    EaseInOutExpo,
+    /// Charging capacitor, as a release this is an analog compressor's RC release
+    #[id = "exponential"]
+    #[name = "Exponential"]
+    Exponential,
+    #[id = "logarithmic"]
+    #[name = "Logarithmic"]
+    Logarithmic,
+    /// Blend between the band's A and B patterns, see [`Curve::Morph`]. On its own this is linear.
+    #[id = "pattern"]
+    #[name = "Pattern morph"]
+    Pattern,
+}
+
 /// This should define a graph that starts from 0.0 to 1.0.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum Curve {
-    EaseInOutSine,
-    EaseInSine,
+    /// `curvature` (-1.0 -- 1.0) warps the x axis, positive values make the curve start slower
+    Shape {
+        shape: CurveShape,
+        curvature: f32,
+    },
     Pattern(Pattern),
+    /// Output-wise blend between two patterns, `amount` is 0.0 for `a` and 1.0 for `b`
+    Morph {
+        a: Pattern,
+        b: Pattern,
+        amount: f32,
+    },
 }
 
 impl Curve {
+    pub(crate) fn new(shape: CurveShape, curvature: f32) -> Self {
+        Curve::Shape { shape, curvature }
+    }
+
+    /// Same as [`Self::new()`], but [`CurveShape::Pattern`] blends between the given A and B
+    /// patterns. Copying the patterns doesn't allocate.
+    pub(crate) fn with_patterns(
+        shape: CurveShape,
+        curvature: f32,
+        [a, b]: &[Pattern; 2],
+        morph: f32,
+    ) -> Self {
+        match shape {
+            CurveShape::Pattern => Curve::Morph {
+                a: a.clone(),
+                b: b.clone(),
+                amount: morph,
+            },
+            shape => Self::new(shape, curvature),
+        }
+    }
+
     /// Range of `x` is 0.0 to 1.0
     ///
     /// Output should be in range 0.0 to 1.0
     fn get_y(&self, x: f32) -> f32 {
         match self {
-            Curve::EaseInOutSine => {
-                // https://easings.net/#easeInOutSine
-                -((PI * x).cos() - 1.0) / 2.0
-            }
-            Curve::EaseInSine => {
-                // https://easings.net/#easeInOutSine
-                1.0 - ((x * PI) / 2.0).cos()
+            Curve::Shape { shape, curvature } => {
+                let x = if *curvature == 0.0 {
+                    x
+                } else {
+                    x.clamp(0.0, 1.0).powf(4.0_f32.powf(*curvature))
+                };
+                shape.get_y(x)
             }
             Curve::Pattern(pattern) => pattern.get_y_at(x as f64) as f32,
+            Curve::Morph { a, b, amount } => {
+                let y_a = a.get_y_at(x as f64) as f32;
+                let y_b = b.get_y_at(x as f64) as f32;
+                y_a + (y_b - y_a) * amount
+            }
+        }
+    }
+
+    /// Update the morph amount, does nothing if this isn't a morph curve
+    fn set_morph(&mut self, morph: f32) {
+        if let Curve::Morph { amount, .. } = self {
+            *amount = morph;
+        }
+    }
+}
+
+impl CurveShape {
+    /// Range of `x` is 0.0 to 1.0
+    ///
+    /// Most formulas are from https://easings.net/
+    fn get_y(&self, x: f32) -> f32 {
+        match self {
+            CurveShape::Linear => x,
+            CurveShape::EaseInSine => 1.0 - ((x * PI) / 2.0).cos(),
+            CurveShape::EaseOutSine => ((x * PI) / 2.0).sin(),
+            CurveShape::EaseInOutSine => -((PI * x).cos() - 1.0) / 2.0,
+            CurveShape::EaseInQuad => x * x,
+            CurveShape::EaseOutQuad => 1.0 - (1.0 - x) * (1.0 - x),
+            CurveShape::EaseInOutQuad => {
+                if x < 0.5 {
+                    2.0 * x * x
+                } else {
+                    1.0 - (-2.0 * x + 2.0).powi(2) / 2.0
+                }
+            }
+            CurveShape::EaseInCubic => x * x * x,
+            CurveShape::EaseOutCubic => 1.0 - (1.0 - x).powi(3),
+            CurveShape::EaseInOutCubic => {
+                if x < 0.5 {
+                    4.0 * x * x * x
+                } else {
+                    1.0 - (-2.0 * x + 2.0).powi(3) / 2.0
+                }
+            }
+            CurveShape::EaseInExpo => {
+                if x <= 0.0 {
+                    0.0
+                } else {
+                    2.0_f32.powf(10.0 * x - 10.0)
+                }
+            }
+            CurveShape::EaseOutExpo => {
+                if x >= 1.0 {
+                    1.0
+                } else {
+                    1.0 - 2.0_f32.powf(-10.0 * x)
+                }
+            }
+            CurveShape::EaseInOutExpo => {
+                if x <= 0.0 {
+                    0.0
+                } else if x >= 1.0 {
+                    1.0
+                } else if x < 0.5 {
+                    2.0_f32.powf(20.0 * x - 10.0) / 2.0
+                } else {
+                    (2.0 - 2.0_f32.powf(-20.0 * x + 10.0)) / 2.0
+                }
+            }
+            CurveShape::Exponential => {
+                // normalized so that the curve reaches 1.0 at the end
+                (1.0 - (-RC_TIME_CONSTANTS * x).exp()) / (1.0 - (-RC_TIME_CONSTANTS).exp())
+            }
+            CurveShape::Logarithmic => (1.0 + LOG_STEEPNESS * x).ln() / (1.0 + LOG_STEEPNESS).ln(),
+            CurveShape::Pattern => x,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 10 samples of delay, 5 of attack, 3 of hold and 10 of release
+    fn envelope() -> Envelope {
+        Envelope::new(
+            100.0,
+            0.1,
+            0.05,
+            0.03,
+            0.1,
+            Curve::new(CurveShape::EaseInSine, 0.0),
+            Curve::new(CurveShape::EaseInOutSine, 0.0),
+        )
+    }
+
+    fn assert_endpoints(curve: &Curve) {
+        let start = curve.get_y(0.0);
+        let end = curve.get_y(1.0);
+        assert!(start.abs() < 1e-6, "{curve:?} starts at {start}");
+        assert!((end - 1.0).abs() < 1e-6, "{curve:?} ends at {end}");
+    }
+
+    /// Tick the envelope until it reaches its peak, returning the number of ticks before the peak
+    fn ticks_to_peak(env: &mut Envelope) -> usize {
+        let mut ticks = 0;
+        while env.tick() != Some(1.0) {
+            ticks += 1;
+            assert!(ticks < 1000, "envelope never reached its peak");
+        }
+        ticks
+    }
+
+    mod tick {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // delay stage
+            let mut env = envelope();
+            for _ in 0..10 {
+                assert_eq!(env.tick(), Some(0.0));
+            }
+        }
+
+        #[test]
+        fn test_02() {
+            // attack stage
+            let mut env = envelope();
+            for _ in 0..10 {
+                env.tick();
+            }
+
+            let mut previous = -1.0;
+            for _ in 0..5 {
+                let value = env.tick().unwrap();
+                assert!(value > previous && value < 1.0);
+                previous = value;
+            }
+        }
+
+        #[test]
+        fn test_03() {
+            // hold stage
+            let mut env = envelope();
+            for _ in 0..15 {
+                env.tick();
+            }
+            for _ in 0..3 {
+                assert_eq!(env.tick(), Some(1.0));
+            }
+        }
+
+        #[test]
+        fn test_04() {
+            // release stage
+            let mut env = envelope();
+            for _ in 0..18 {
+                env.tick();
+            }
+
+            let mut previous = 2.0;
+            for _ in 0..10 {
+                let value = env.tick().unwrap();
+                assert!(value < previous && value >= 0.0);
+                previous = value;
+            }
+            assert_eq!(env.tick(), None);
+            assert!(env.is_complete());
+        }
+
+        #[test]
+        fn test_05() {
+            // sustain holds the envelope at its peak until it is turned off
+            let mut env = envelope();
+            env.set_sustain(true);
+            for _ in 0..15 {
+                env.tick();
+            }
+            for _ in 0..100 {
+                assert_eq!(env.tick(), Some(1.0));
+            }
+            assert!(!env.is_complete());
+
+            env.set_sustain(false);
+            for _ in 0..3 {
+                assert_eq!(env.tick(), Some(1.0));
+            }
+            assert!(env.tick().unwrap() <= 1.0);
+        }
+
+        #[test]
+        fn test_06() {
+            // legato retriggers keep decaying through the delay, then attack from there
+            let mut previous = Envelope::new(
+                100.0,
+                0.1,
+                0.05,
+                0.03,
+                0.5,
+                Curve::new(CurveShape::Linear, 0.0),
+                Curve::new(CurveShape::Linear, 0.0),
+            );
+            for _ in 0..20 {
+                previous.tick();
+            }
+            let mut env = envelope();
+            env.continue_from(&previous);
+
+            let mut last = previous.value();
+            for i in 0..10 {
+                let value = env.tick().unwrap();
+                let expected = previous.tick().unwrap();
+                assert!(value < last, "delay stalls at sample {i}");
+                last = value;
+                // the linear release is followed exactly
+                assert!((value - expected).abs() < 1e-6);
+            }
+
+            let attack_start = env.tick().unwrap();
+            assert!((attack_start - previous.tick().unwrap()).abs() < 1e-6);
+            assert_eq!(ticks_to_peak(&mut env), 4);
+        }
+
+        #[test]
+        fn test_07() {
+            // a legato retrigger of a sustained envelope stays at its peak through the delay
+            let mut previous = envelope();
+            previous.set_sustain(true);
+            for _ in 0..20 {
+                previous.tick();
+            }
+            let mut env = envelope();
+            env.continue_from(&previous);
+            for _ in 0..15 {
+                assert_eq!(env.tick(), Some(1.0));
+            }
+        }
+    }
+
+    mod from_latency {
+        use super::*;
+
+        fn envelope(offset_seconds: f32) -> Envelope {
+            // 16 samples of latency and 4 of precomp
+            Envelope::from_latency(
+                128.0,
+                0.125,
+                offset_seconds,
+                0.03125,
+                0.0,
+                0.125,
+                Curve::new(CurveShape::Linear, 0.0),
+                Curve::new(CurveShape::Linear, 0.0),
+            )
+        }
+
+        #[test]
+        fn test_01() {
+            // negative offsets move the peak earlier, positive offsets later
+            let peak = ticks_to_peak(&mut envelope(0.0));
+            assert_eq!(ticks_to_peak(&mut envelope(-0.0625)), peak - 8);
+            assert_eq!(ticks_to_peak(&mut envelope(0.0625)), peak + 8);
+        }
+
+        #[test]
+        fn test_02() {
+            // the peak can't be earlier than the start of the envelope, the attack gets shorter
+            let earliest = ticks_to_peak(&mut envelope(-0.125));
+            assert_eq!(ticks_to_peak(&mut envelope(-0.5)), earliest);
+            assert_eq!(ticks_to_peak(&mut envelope(-10.0)), earliest);
+        }
+    }
+
+    mod set_attack {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // longer attack during delay, the peak stays in place
+            let mut env = envelope();
+            env.tick();
+            env.tick();
+            assert!(env.can_set_attack(0.08));
+            env.set_attack(0.08);
+            assert_eq!(env.attack_samples, 8.0);
+            assert_eq!(ticks_to_peak(&mut env), 13);
+        }
+
+        #[test]
+        fn test_02() {
+            // shorter attack during delay, the peak stays in place
+            let mut env = envelope();
+            env.tick();
+            env.tick();
+            env.set_attack(0.01);
+            assert_eq!(env.attack_samples, 1.0);
+            assert_eq!(ticks_to_peak(&mut env), 13);
+        }
+
+        #[test]
+        fn test_03() {
+            // longer attack that would have started in the past
+            let mut env = envelope();
+            for _ in 0..8 {
+                env.tick();
+            }
+            assert!(!env.can_set_attack(0.1));
+            env.set_attack(0.1);
+            assert_eq!(env.attack_samples, 5.0);
+            assert_eq!(ticks_to_peak(&mut env), 7);
+        }
+
+        #[test]
+        fn test_04() {
+            // attack longer than delay + attack is clamped
+            let mut env = envelope();
+            env.set_attack(1.0);
+            assert_eq!(env.attack_samples, 15.0);
+            assert_eq!(env.delay_samples, 0.0);
+            assert_eq!(ticks_to_peak(&mut env), 15);
+        }
+
+        #[test]
+        fn test_05() {
+            // attack has started
+            let mut env = envelope();
+            for _ in 0..12 {
+                env.tick();
+            }
+            assert!(!env.can_set_attack(0.01));
+            env.set_attack(0.01);
+            assert_eq!(env.attack_samples, 5.0);
+        }
+
+        #[test]
+        fn test_06() {
+            // hold and release stages
+            let mut env = envelope();
+            for _ in 0..16 {
+                env.tick();
+            }
+            assert!(!env.can_set_attack(0.01));
+            for _ in 0..5 {
+                env.tick();
+            }
+            assert!(!env.can_set_attack(0.01));
+        }
+    }
+
+    mod curve {
+        use super::*;
+
+        /// Every curve shape with the given curvature
+        fn curves(curvature: f32) -> impl Iterator<Item = Curve> {
+            (0..CurveShape::variants().len())
+                .map(move |i| Curve::new(CurveShape::from_index(i), curvature))
+        }
+
+        #[test]
+        fn test_01() {
+            for curve in curves(0.0) {
+                assert_endpoints(&curve);
+            }
+        }
+
+        #[test]
+        fn test_02() {
+            for curve in curves(1.0).chain(curves(-1.0)).chain(curves(0.37)) {
+                assert_endpoints(&curve);
+            }
+        }
+    }
+
+    mod release_model {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // analog release decays by the same ratio every sample, down to the silence threshold
+            let mut env = envelope();
+            env.set_release_model(ReleaseModel::Analog);
+            for _ in 0..18 {
+                env.tick();
+            }
+
+            let first = env.tick().unwrap();
+            let second = env.tick().unwrap();
+            let third = env.tick().unwrap();
+            assert_eq!(first, 1.0);
+            assert!((second / first - third / second).abs() < 1e-4);
+
+            let mut last = third;
+            while let Some(value) = env.tick() {
+                last = value;
+            }
+            assert!(last < SILENCE_THRESHOLD * 1.1);
+            assert!(env.is_complete());
+        }
+
+        #[test]
+        fn test_02() {
+            // the release lasts until the time constant has decayed to the silence threshold
+            let mut env = envelope();
+            env.set_release_model(ReleaseModel::Analog);
+            let expected = 10.0 * (1.0 / SILENCE_THRESHOLD).ln();
+            assert!((env.release_samples - expected).abs() < 1e-3);
+        }
+    }
+
+    mod morph {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // endpoints are kept for any amount
+            for amount in [0.0, 0.3, 1.0] {
+                let curve = Curve::Morph {
+                    a: Pattern::pluck(),
+                    b: Pattern::swell(),
+                    amount,
+                };
+                assert_endpoints(&curve);
+            }
+        }
+
+        #[test]
+        fn test_02() {
+            // the output is blended between the two patterns
+            let mut curve = Curve::Morph {
+                a: Pattern::pluck(),
+                b: Pattern::swell(),
+                amount: 0.0,
+            };
+            let y_a = curve.get_y(0.5);
+            curve.set_morph(1.0);
+            let y_b = curve.get_y(0.5);
+            curve.set_morph(0.5);
+            let y_mid = curve.get_y(0.5);
+            assert!((y_mid - (y_a + y_b) / 2.0).abs() < 1e-6);
         }
     }
 }
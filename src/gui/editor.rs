@@ -1,33 +1,48 @@
+use super::envpreview::EnvelopePreview;
+use super::fft::spectrum_db;
 use super::knob::{Knob, KnobStyle};
+use super::layout::{self, Size};
+use super::meters::{GainReductionHistory, GainReductionMeter, PeakMeter};
+use super::overview::ChannelOverview;
+use super::paramlabel::ParamLabel;
+use super::patterneditor::PatternEditor;
+use super::response::FrequencyResponse;
+use super::scope::Scope;
+use super::spectrum::{smooth_spectrum, SpectrumAnalyzer};
+use super::stepgrid::StepGrid;
+use super::undo::UndoEditor;
+use super::window::ZoomableWindow;
 use crate::{
+    capture::{Captures, SCOPE_DECIMATION, SCOPE_FRAMES},
+    clamp_crossovers,
     gui::{
         button::{custom_block_button, BlockButton, ButtonContent},
         knob::KnobDonutText,
         knobtext::KnobText,
-        palette::{self as C},
+        palette::{self as C, load_custom_theme, Theme, ThemeName},
     },
-    MIDIProcessingMode, Malt,
+    max_magnitude,
+    meters::MeterReadings,
+    modulation::ModSource,
+    notemap::{note_name, LastNoteOn, NoteMap},
+    MIDIProcessingMode, Malt, MaltParams, OffsetMode, Slope, ThreeBandSplitter, MAX_BOOST_DB,
+    PREVIEW_SAMPLE_RATE,
 };
 use nih_plug::prelude::*;
 use nih_plug_egui::{
     create_egui_editor,
     egui::{
-        self,
+        self, pos2,
         style::ScrollStyle,
         text::{LayoutJob, TextWrapping},
-        vec2, Align, CentralPanel, Color32, Context, FontFamily, FontId, Id, Label, Layout,
-        Painter, Pos2, Rect, Response, RichText, ScrollArea, Spacing, Style, TextStyle, Ui,
-        UiBuilder, Vec2,
+        vec2, Align, Align2, CentralPanel, Color32, Context, FontFamily, FontId, Id, Key,
+        KeyboardShortcut, Label, Layout, Modifiers, Painter, Pos2, Rect, Response, RichText,
+        ScrollArea, Sense, Spacing, Stroke, Style, TextStyle, Ui, UiBuilder, Vec2,
     },
-    resizable_window::ResizableWindow,
     widgets::{self, ParamSlider},
 };
-
-// the DPI-independent size of the window
-// pub(crate) const GUI_DEFAULT_WIDTH: u32 = 651;
-// pub(crate) const GUI_DEFAULT_HEIGHT: u32 = 391;
-// pub(crate) const GUI_MINIMUM_WIDTH: u32 = 128;
-// pub(crate) const GUI_MINIMUM_HEIGHT: u32 = 128;
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
 
 /// Rich text
 fn rt(ui: &mut egui::Ui, text: impl Into<String>, family: &FontFamily, size: f32, color: Color32) {
@@ -80,15 +95,146 @@ fn draw_texts(
     painter.galley(position, galley, Color32::RED);
 }
 
+/// Number of frames shown in the gain reduction history
+const GR_HISTORY_LEN: usize = 240;
+/// Largest gain reduction shown by the meters, in dB
+const METER_MAX_REDUCTION_DB: f32 = 24.0;
+/// How much the meters fall back every frame
+const METER_FALLOFF: f32 = 0.85;
+/// Number of samples analyzed by the spectrum analyzer
+const SPECTRUM_SIZE: usize = 2048;
+/// How much the spectrum analyzer falls back every frame, in dB
+const SPECTRUM_FALLOFF_DB: f32 = 1.5;
+/// The editor doesn't know the host's tempo, so note length offsets are previewed at this tempo.
+/// Also used by the scope while the host isn't playing.
+const PREVIEW_TEMPO: f64 = 120.0;
+/// Height of each channel in the overview
+const OVERVIEW_ROW_HEIGHT: f32 = 18.0;
+/// How much the channel activity in the overview falls back every frame
+const ACTIVITY_FALLOFF: f32 = 0.9;
+/// Height of the header and the footer
+const HEADER_HEIGHT: f32 = 25.0;
+/// Width of the band controls on the right of the main view
+const BAND_WIDGET_WIDTH: f32 = 248.0;
+/// Height of the crossover controls between two bands
+const CROSSOVER_HEIGHT: f32 = 22.0;
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+/// The zoom levels that can be picked in the header, in percent
+const ZOOM_LEVELS: [u8; 6] = [75, 100, 125, 150, 175, 200];
+
+/// The page shown by the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    /// The mockup layout, with the main controls of the selected channel
+    Main,
+    /// Every setting, including the modulation, sequencer and note map
+    Advanced,
+}
+
 struct UIState {
+    view: View,
     help_enabled: bool,
+    /// The channel that is waiting for a key to be pressed (MIDI learn)
+    learning_channel: Option<usize>,
+    /// The note-on counter when MIDI learn was started, see [`LastNoteOn`]
+    learn_counter: u16,
+    /// Whether MIDI learn should only accept notes from the same MIDI channel
+    learn_midi_channel: bool,
+    /// The meter values that are currently shown
+    meters: MeterReadings,
+    /// Gain reduction of the low, mid and high bands of the previous frames, oldest first
+    gr_history: VecDeque<[f32; 3]>,
+    /// The spectra that are currently shown, in dB
+    spectrum_input: Vec<f32>,
+    spectrum_output: Vec<f32>,
+    /// Whether the crossover plot shows the phase response too
+    show_phase: bool,
+    /// Length of the scope in beats, 1 -- 4
+    scope_beats: usize,
+    /// How recently each channel was triggered, 0.0 -- 1.0
+    channel_activity: [f32; 16],
+    /// The theme from the user's theme file, loaded whenever the editor is opened
+    custom_theme: Option<Theme>,
+    /// The zoom factor the window's size was last scaled for, see [`ZoomableWindow`]
+    window_zoom: Option<f32>,
 }
 
 impl UIState {
     fn new() -> Self {
         Self {
+            view: View::Main,
             help_enabled: false,
+            learning_channel: None,
+            learn_counter: 0,
+            learn_midi_channel: false,
+            meters: Default::default(),
+            gr_history: VecDeque::with_capacity(GR_HISTORY_LEN),
+            spectrum_input: Vec::new(),
+            spectrum_output: Vec::new(),
+            show_phase: false,
+            scope_beats: 2,
+            channel_activity: [0.0; 16],
+            custom_theme: None,
+            window_zoom: None,
+        }
+    }
+
+    /// The theme to draw the editor with
+    fn theme(&self, name: ThemeName) -> Theme {
+        match (name, self.custom_theme) {
+            (ThemeName::Custom, Some(theme)) => theme,
+            (name, _) => Theme::builtin(name),
+        }
+    }
+
+    fn update_spectrum(&mut self, captures: &Captures) {
+        let mut samples = vec![0.0; SPECTRUM_SIZE];
+        captures.input.read_latest(&mut samples);
+        smooth_spectrum(
+            &mut self.spectrum_input,
+            &spectrum_db(&samples),
+            SPECTRUM_FALLOFF_DB,
+        );
+        captures.output.read_latest(&mut samples);
+        smooth_spectrum(
+            &mut self.spectrum_output,
+            &spectrum_db(&samples),
+            SPECTRUM_FALLOFF_DB,
+        );
+    }
+
+    fn update_meters(&mut self, readings: MeterReadings) {
+        // the meters fall back gradually, so they don't flicker on frames where the audio thread
+        // hasn't processed any buffers
+        for (shown, new) in self
+            .meters
+            .gain_reduction
+            .iter_mut()
+            .zip(readings.gain_reduction)
+        {
+            *shown = max_magnitude(new, *shown * METER_FALLOFF);
+        }
+        self.meters.input_peak = readings
+            .input_peak
+            .max(self.meters.input_peak * METER_FALLOFF);
+        self.meters.output_peak = readings
+            .output_peak
+            .max(self.meters.output_peak * METER_FALLOFF);
+        self.meters.active_voices = readings.active_voices;
+        for (i, activity) in self.channel_activity.iter_mut().enumerate() {
+            if readings.channel_triggers & (1 << i) != 0 {
+                *activity = 1.0;
+            } else {
+                *activity *= ACTIVITY_FALLOFF;
+            }
+        }
+
+        if self.gr_history.len() == GR_HISTORY_LEN {
+            self.gr_history.pop_front();
         }
+        self.gr_history.push_back(self.meters.gain_reduction);
     }
 }
 
@@ -100,6 +246,7 @@ fn simple_block_button(
     active_color: Color32,
     fg_color: Color32,
     bg_color: Color32,
+    theme: &Theme,
 ) -> Response {
     if active {
         ui.add(BlockButton::new(
@@ -109,8 +256,8 @@ fn simple_block_button(
             bg_color,
             bg_color,
             active_color,
-            active_color.lerp_to_gamma(C::FG_WHITE, 0.2),
-            active_color.lerp_to_gamma(C::BG_DARK, 0.2),
+            active_color.lerp_to_gamma(theme.fg_bright, 0.2),
+            active_color.lerp_to_gamma(theme.bg_deep, 0.2),
         ))
     } else {
         ui.add(BlockButton::new(
@@ -120,92 +267,672 @@ fn simple_block_button(
             fg_color,
             fg_color.gamma_multiply(0.5),
             Color32::TRANSPARENT,
-            C::FG_WHITE.gamma_multiply(0.1),
+            theme.fg_bright.gamma_multiply(0.1),
             Color32::TRANSPARENT,
         ))
     }
 }
 
-fn panel_band<'a, P: Param>(
-    ui: &mut Ui,
-    name: &'static str,
-    precomp: &'a P,
-    decay: &'a P,
-    reduction: &'a P,
-) {
-    const BUTTON_SIZE: Vec2 = Vec2::splat(22.0);
+/// Toggle a parameter as a single gesture
+fn toggle_param(setter: &ParamSetter, param: &BoolParam) {
+    setter.begin_set_parameter(param);
+    setter.set_parameter(param, !param.value());
+    setter.end_set_parameter(param);
+}
 
-    ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
-        let res = simple_block_button(
-            ui,
-            true, // TODO
-            ButtonContent::Image(egui::include_image!("res/power.svg")),
-            BUTTON_SIZE,
-            C::FG_ORANGE,
-            C::FG_GREY,
-            C::BG_NORMAL,
-        );
-        if res.clicked() {
-            nih_log!("Power!");
-        }
+/// Number of channels that can be edited, only the first channel is used in omni mode
+fn channel_count(params: &MaltParams) -> usize {
+    if matches!(params.midi_mode.value(), MIDIProcessingMode::Omni) {
+        1
+    } else {
+        16
+    }
+}
 
-        ui.with_layout(Layout::bottom_up(Align::LEFT), |ui| {
-            let res = simple_block_button(
-                ui,
-                true, // TODO
-                ButtonContent::Text("S", FontId::new(C::TEXT_BASE, C::FONT_BOLD.clone())),
-                BUTTON_SIZE,
-                C::FG_BLUE,
-                C::FG_GREY,
-                C::BG_NORMAL,
-            );
+/// The channel being edited, see [`MaltParams::editor_state_active_channel`]
+fn active_channel(params: &MaltParams) -> usize {
+    let channel = params.editor_state_active_channel.load(Ordering::Relaxed) as usize;
+    channel.min(channel_count(params) - 1)
+}
+
+fn set_active_channel(params: &MaltParams, channel: usize) {
+    params
+        .editor_state_active_channel
+        .store(channel as u8, Ordering::Relaxed);
+}
+
+/// Index of the editor's zoom in [`ZOOM_LEVELS`], see [`MaltParams::editor_zoom`]
+fn zoom_level(params: &MaltParams) -> usize {
+    let percent = params.editor_zoom.load(Ordering::Relaxed);
+    // snap to the nearest level, in case the saved zoom isn't one of them
+    (0..ZOOM_LEVELS.len())
+        .min_by_key(|&i| ZOOM_LEVELS[i].abs_diff(percent))
+        .unwrap()
+}
+
+fn set_zoom_level(params: &MaltParams, level: usize) {
+    params
+        .editor_zoom
+        .store(ZOOM_LEVELS[level], Ordering::Relaxed);
+}
+
+/// Number of scope frames in a beat. Without the host's tempo, the scrolling scope assumes
+/// [`PREVIEW_TEMPO`].
+fn scope_frames_per_beat(sample_rate: f32) -> f32 {
+    sample_rate * 60.0 / PREVIEW_TEMPO as f32 / SCOPE_DECIMATION as f32
+}
+
+/// The title bar, with the channel selector, the zoom, and the theme, view and help toggles
+fn header(ui: &mut Ui, rect: Rect, params: &MaltParams, state: &mut UIState, theme: &Theme) {
+    const BUTTON_SIZE: Vec2 = vec2(22.0, HEADER_HEIGHT);
+
+    ui.painter().rect_filled(rect, 0.0, theme.bg_deep);
+    let [title, channel, zoom, theme_button, view, help] = layout::columns(
+        rect,
+        [
+            Size::flex(1.0),
+            Size::Fixed(96.0),
+            Size::Fixed(84.0),
+            Size::Fixed(88.0),
+            Size::Fixed(72.0),
+            Size::Fixed(BUTTON_SIZE.x),
+        ],
+        0.0,
+    );
+
+    let painter = ui.painter_at(title);
+    let company = painter.text(
+        pos2(title.left() + 12.0, title.center().y),
+        Align2::LEFT_CENTER,
+        "sai audio",
+        FontId::new(theme.text_large, C::FONT_NORMAL),
+        theme.fg_dim,
+    );
+    painter.text(
+        pos2(company.right() + 10.0, title.center().y),
+        Align2::LEFT_CENTER,
+        "Malt",
+        FontId::new(theme.text_large, C::FONT_BOLD.clone()),
+        theme.fg_bright,
+    );
+
+    // channel selector, there is only one channel in omni mode
+    let channel_count = channel_count(params);
+    if channel_count > 1 {
+        let current = active_channel(params);
+        let [previous, label, next] = layout::columns(
+            channel,
+            [
+                Size::Fixed(BUTTON_SIZE.x),
+                Size::flex(1.0),
+                Size::Fixed(BUTTON_SIZE.x),
+            ],
+            0.0,
+        );
+        for (rect, text, target) in [
+            (previous, "<", (current + channel_count - 1) % channel_count),
+            (next, ">", (current + 1) % channel_count),
+        ] {
+            let res = ui.put(rect, |ui: &mut Ui| {
+                simple_block_button(
+                    ui,
+                    false,
+                    ButtonContent::Text(text, FontId::new(theme.text_base, C::FONT_BOLD.clone())),
+                    BUTTON_SIZE,
+                    theme.fg_bright,
+                    theme.fg_dim,
+                    theme.bg_deep,
+                    theme,
+                )
+            });
             if res.clicked() {
-                nih_log!("Solo!");
+                set_active_channel(params, target);
             }
-            let res = simple_block_button(
+        }
+        ui.painter().text(
+            label.center(),
+            Align2::CENTER_CENTER,
+            format!("Channel {}", current),
+            FontId::new(theme.text_sm, C::FONT_NORMAL),
+            theme.fg_bright,
+        );
+    }
+
+    // zoom selector, clicking the percentage resets the zoom
+    let level = zoom_level(params);
+    let [zoom_out, percent, zoom_in] = layout::columns(
+        zoom,
+        [
+            Size::Fixed(BUTTON_SIZE.x),
+            Size::flex(1.0),
+            Size::Fixed(BUTTON_SIZE.x),
+        ],
+        0.0,
+    );
+    for (rect, text, target) in [
+        (zoom_out, "-", level.saturating_sub(1)),
+        (zoom_in, "+", (level + 1).min(ZOOM_LEVELS.len() - 1)),
+    ] {
+        let res = ui.put(rect, |ui: &mut Ui| {
+            simple_block_button(
                 ui,
-                true, // TODO
-                ButtonContent::Text("M", FontId::new(C::TEXT_BASE, C::FONT_BOLD.clone())),
+                false,
+                ButtonContent::Text(text, FontId::new(theme.text_base, C::FONT_BOLD.clone())),
                 BUTTON_SIZE,
-                C::FG_RED,
-                C::FG_GREY,
-                C::BG_NORMAL,
-            );
-            if res.clicked() {
-                nih_log!("Mute!");
-            }
+                theme.fg_bright,
+                theme.fg_dim,
+                theme.bg_deep,
+                theme,
+            )
         });
+        if res.clicked() {
+            set_zoom_level(params, target);
+        }
+    }
+    let res = ui.interact(percent, ui.id().with("zoom"), Sense::click());
+    ui.painter().text(
+        percent.center(),
+        Align2::CENTER_CENTER,
+        format!("{}%", ZOOM_LEVELS[level]),
+        FontId::new(theme.text_sm, C::FONT_NORMAL),
+        if res.hovered() {
+            theme.fg_bright
+        } else {
+            theme.fg_dim
+        },
+    );
+    if res.clicked() {
+        set_zoom_level(params, ZOOM_LEVELS.iter().position(|x| *x == 100).unwrap());
+    }
+
+    let theme_name = *params.editor_theme.read().unwrap();
+    let res = ui.put(theme_button, |ui: &mut Ui| {
+        simple_block_button(
+            ui,
+            false,
+            ButtonContent::Text(
+                theme_name.label(),
+                FontId::new(theme.text_sm, C::FONT_NORMAL),
+            ),
+            vec2(theme_button.width(), HEADER_HEIGHT),
+            theme.fg_bright,
+            theme.fg_dim,
+            theme.bg_deep,
+            theme,
+        )
     });
-    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-        ui.add(BlockButton::new(
-            ButtonContent::Text("?", FontId::new(C::TEXT_BASE, C::FONT_BOLD.clone())),
+    if res.clicked() {
+        let mut next = theme_name.next();
+        // the custom theme can only be picked if there is a theme file
+        if next == ThemeName::Custom && state.custom_theme.is_none() {
+            next = next.next();
+        }
+        *params.editor_theme.write().unwrap() = next;
+    }
+
+    let res = ui.put(view, |ui: &mut Ui| {
+        simple_block_button(
+            ui,
+            state.view == View::Advanced,
+            ButtonContent::Text("Advanced", FontId::new(theme.text_sm, C::FONT_NORMAL)),
+            vec2(view.width(), HEADER_HEIGHT),
+            theme.fg_orange,
+            theme.fg_dim,
+            theme.bg_deep,
+            theme,
+        )
+    });
+    if res.clicked() {
+        state.view = match state.view {
+            View::Main => View::Advanced,
+            View::Advanced => View::Main,
+        };
+    }
+
+    let res = ui.put(help, |ui: &mut Ui| {
+        simple_block_button(
+            ui,
+            state.help_enabled,
+            ButtonContent::Text("?", FontId::new(theme.text_base, C::FONT_BOLD.clone())),
             BUTTON_SIZE,
-            C::BG_DARK,
-            C::BG_DARK,
-            C::BG_DARK,
-            C::FG_GREEN,
-            C::FG_GREEN.lerp_to_gamma(C::FG_WHITE, 0.1),
-            C::FG_GREEN.lerp_to_gamma(C::BG_DARK, 0.2),
-        ));
+            theme.fg_green,
+            theme.fg_dim,
+            theme.bg_deep,
+            theme,
+        )
     });
+    if res.clicked() {
+        state.help_enabled = !state.help_enabled;
+    }
+}
+
+/// The global settings along the bottom of the main view
+fn footer(ui: &mut Ui, rect: Rect, params: &MaltParams, setter: &ParamSetter, theme: &Theme) {
+    ui.painter().rect_filled(rect, 0.0, theme.bg_deep);
+    let [trigger, mode, lookahead, smooth, bypass, mix] =
+        layout::columns(rect, [Size::flex(1.0); 6], 0.0);
+    let font_id = FontId::new(theme.text_sm, C::FONT_NORMAL);
+
+    ui.put(
+        trigger,
+        ParamLabel::for_param(
+            &params.midi_mode,
+            setter,
+            "Trigger",
+            true,
+            trigger.size(),
+            font_id.clone(),
+            theme,
+        ),
+    );
+    ui.put(
+        mode,
+        ParamLabel::for_param(
+            &params.trigger_mode,
+            setter,
+            "Mode",
+            true,
+            mode.size(),
+            font_id.clone(),
+            theme,
+        ),
+    );
+    ui.put(
+        lookahead,
+        ParamLabel::for_param(
+            &params.lookahead,
+            setter,
+            "Lookahead",
+            true,
+            lookahead.size(),
+            font_id.clone(),
+            theme,
+        ),
+    );
+    ui.put(
+        smooth,
+        ParamLabel::for_param(
+            &params.smoothing,
+            setter,
+            "Smooth",
+            true,
+            smooth.size(),
+            font_id.clone(),
+            theme,
+        ),
+    );
+    ui.put(
+        bypass,
+        ParamLabel::for_param(
+            &params.bypass,
+            setter,
+            "Bypass",
+            false,
+            bypass.size(),
+            font_id.clone(),
+            theme,
+        ),
+    );
+    ui.put(
+        mix,
+        ParamLabel::for_param(&params.mix, setter, "Mix", true, mix.size(), font_id, theme),
+    );
+}
+
+/// The controls of a single band in the main view
+struct BandControls<'a> {
+    color: Color32,
+    precomp: &'a FloatParam,
+    decay: &'a FloatParam,
+    reduction: &'a FloatParam,
+    bypass: &'a BoolParam,
+    mute: &'a BoolParam,
+    solo: &'a BoolParam,
+}
+
+/// The band widget from the mockup: power, mute and solo buttons, then the precomp knob, a plot of
+/// the band's envelope, and the decay and reduction knobs
+fn panel_band(
+    ui: &mut Ui,
+    rect: Rect,
+    band: &BandControls,
+    curves: &[Vec<f32>; 3],
+    note_sample: usize,
+    setter: &ParamSetter,
+    theme: &Theme,
+) {
+    const BUTTON_SIZE: Vec2 = Vec2::splat(22.0);
+    const LABEL_HEIGHT: f32 = 14.0;
+
+    let rect = rect.shrink(4.0);
+    let [buttons, precomp, graph, decay, reduction] = layout::columns(
+        rect,
+        [
+            Size::Fixed(BUTTON_SIZE.x),
+            Size::flex(1.0),
+            Size::clamped(1.6, 60.0, 110.0),
+            Size::flex(1.0),
+            Size::flex(1.0),
+        ],
+        4.0,
+    );
+
+    // power, mute and solo
+    let [power, _, mute, solo] = layout::rows(
+        buttons,
+        [
+            Size::Fixed(BUTTON_SIZE.y),
+            Size::flex(1.0),
+            Size::Fixed(BUTTON_SIZE.y),
+            Size::Fixed(BUTTON_SIZE.y),
+        ],
+        0.0,
+    );
+    for (rect, param, active, content, active_color) in [
+        (
+            power,
+            band.bypass,
+            !band.bypass.value(),
+            ButtonContent::Image(egui::include_image!("res/power.svg")),
+            theme.fg_orange,
+        ),
+        (
+            mute,
+            band.mute,
+            band.mute.value(),
+            ButtonContent::Text("M", FontId::new(theme.text_base, C::FONT_BOLD.clone())),
+            theme.fg_red,
+        ),
+        (
+            solo,
+            band.solo,
+            band.solo.value(),
+            ButtonContent::Text("S", FontId::new(theme.text_base, C::FONT_BOLD.clone())),
+            theme.fg_blue,
+        ),
+    ] {
+        let res = ui.put(rect, |ui: &mut Ui| {
+            simple_block_button(
+                ui,
+                active,
+                content,
+                BUTTON_SIZE,
+                active_color,
+                theme.fg_dim,
+                theme.bg_raised,
+                theme,
+            )
+        });
+        if res.clicked() {
+            toggle_param(setter, param);
+        }
+    }
+
+    // knobs with their names underneath
+    for (rect, param, name, highlight_color) in [
+        (precomp, band.precomp, "Precomp", band.color),
+        (decay, band.decay, "Decay", band.color),
+        (reduction, band.reduction, "Reduction", theme.fg_bright),
+    ] {
+        let [knob, label] = layout::rows(rect, [Size::flex(1.0), Size::Fixed(LABEL_HEIGHT)], 0.0);
+        let size = knob.width().min(knob.height()).clamp(16.0, 44.0);
+        ui.put(
+            Rect::from_center_size(knob.center(), Vec2::splat(size)),
+            Knob::for_param(
+                param,
+                setter,
+                size,
+                KnobStyle::Analog {
+                    highlight_color,
+                    line_width: 2.0,
+                },
+                theme,
+            ),
+        );
+        ui.painter().text(
+            label.center(),
+            Align2::CENTER_CENTER,
+            name,
+            FontId::new(theme.text_xs, C::FONT_NORMAL),
+            theme.fg_dim,
+        );
+    }
+
+    let [graph, _] = layout::rows(graph, [Size::flex(1.0), Size::Fixed(LABEL_HEIGHT)], 0.0);
+    ui.put(
+        graph,
+        EnvelopePreview::new(curves, note_sample, graph.size(), [band.color; 3], theme),
+    );
+}
+
+/// Crossover frequency and slope, shown between two bands
+fn panel_crossover(
+    ui: &mut Ui,
+    rect: Rect,
+    crossover: &FloatParam,
+    slope: &EnumParam<Slope>,
+    setter: &ParamSetter,
+    theme: &Theme,
+) {
+    let [_, knob, frequency, slope_rect, _] = layout::columns(
+        rect,
+        [
+            Size::flex(1.0),
+            Size::Fixed(16.0),
+            Size::Fixed(64.0),
+            Size::Fixed(96.0),
+            Size::flex(1.0),
+        ],
+        4.0,
+    );
+    ui.put(
+        Rect::from_center_size(knob.center(), Vec2::splat(14.0)),
+        Knob::for_param(
+            crossover,
+            setter,
+            14.0,
+            KnobStyle::Donut { line_width: 4.0 },
+            theme,
+        ),
+    );
+    ui.put(
+        frequency,
+        KnobText::for_param(
+            crossover,
+            setter,
+            frequency.size(),
+            FontId::new(theme.text_sm, C::FONT_NORMAL),
+            theme.fg_dim,
+            true,
+            true,
+            false,
+            theme,
+        ),
+    );
+    ui.put(
+        slope_rect,
+        ParamLabel::for_param(
+            slope,
+            setter,
+            "",
+            true,
+            slope_rect.size(),
+            FontId::new(theme.text_sm, C::FONT_NORMAL),
+            theme,
+        ),
+    );
+}
+
+/// The mockup layout. The selected channel's bands are shown from high to low, each with a scope
+/// on the left and its controls on the right. Every rect is computed from the window size up
+/// front, see [`layout`].
+fn main_view(
+    ui: &mut Ui,
+    params: &MaltParams,
+    setter: &ParamSetter,
+    state: &mut UIState,
+    captures: &Captures,
+    sample_rate: f32,
+    theme: &Theme,
+) {
+    let [header_rect, body, footer_rect] = layout::rows(
+        ui.max_rect(),
+        [
+            Size::Fixed(HEADER_HEIGHT),
+            Size::flex(1.0),
+            Size::Fixed(HEADER_HEIGHT),
+        ],
+        0.0,
+    );
+    header(ui, header_rect, params, state, theme);
+    footer(ui, footer_rect, params, setter, theme);
+
+    let columns = [Size::flex(1.0), Size::Fixed(BAND_WIDGET_WIDTH)];
+    let [analyzer, controls] = layout::columns(body, columns, 0.0);
+    ui.painter().rect_filled(analyzer, 0.0, theme.bg_normal);
+    ui.painter().rect_filled(controls, 0.0, theme.bg_raised);
+
+    let [high, high_crossover, mid, low_crossover, low] = layout::rows(
+        body,
+        [
+            Size::flex(1.0),
+            Size::Fixed(CROSSOVER_HEIGHT),
+            Size::flex(1.0),
+            Size::Fixed(CROSSOVER_HEIGHT),
+            Size::flex(1.0),
+        ],
+        0.0,
+    );
+
+    let ch = &params.channels[active_channel(params)];
+    let lookahead = params.lookahead.value() / 1000.0;
+    let note_sample = (lookahead * PREVIEW_SAMPLE_RATE).round() as usize;
+    let [low_curve, mid_curve, high_curve] = ch.value(lookahead).preview(
+        lookahead,
+        PREVIEW_TEMPO,
+        &params.morph_patterns.read().unwrap(),
+    );
+    let (first_frame, frames) = captures.scope.read_frames(SCOPE_FRAMES);
+    let triggers = captures.scope.read_triggers();
+
+    let bands = [
+        (
+            high,
+            2,
+            [Vec::new(), Vec::new(), high_curve],
+            BandControls {
+                color: theme.fg_yellow,
+                precomp: &ch.high_precomp,
+                decay: &ch.high_decay,
+                reduction: &ch.high_db,
+                bypass: &params.bypass_high,
+                mute: &params.mute_high,
+                solo: &params.solo_high,
+            },
+        ),
+        (
+            mid,
+            1,
+            [Vec::new(), mid_curve, Vec::new()],
+            BandControls {
+                color: theme.fg_purple,
+                precomp: &ch.mid_precomp,
+                decay: &ch.mid_decay,
+                reduction: &ch.mid_db,
+                bypass: &params.bypass_mid,
+                mute: &params.mute_mid,
+                solo: &params.solo_mid,
+            },
+        ),
+        (
+            low,
+            0,
+            [low_curve, Vec::new(), Vec::new()],
+            BandControls {
+                color: theme.fg_blue,
+                precomp: &ch.low_precomp,
+                decay: &ch.low_decay,
+                reduction: &ch.low_db,
+                bypass: &params.bypass_low,
+                mute: &params.mute_low,
+                solo: &params.solo_low,
+            },
+        ),
+    ];
+    for (rect, index, curves, band) in bands.iter() {
+        let [scope, controls] = layout::columns(*rect, columns, 0.0);
+
+        // each band gets its own lane of the scope, with only that band's gain reduction
+        let mut colors = [None; 3];
+        colors[*index] = Some(band.color);
+        ui.put(
+            scope,
+            Scope::new(
+                first_frame,
+                &frames,
+                &triggers,
+                state.scope_beats,
+                scope_frames_per_beat(sample_rate),
+                scope.size(),
+                colors,
+                theme,
+            ),
+        );
+
+        panel_band(ui, controls, band, curves, note_sample, setter, theme);
+    }
+
+    for (rect, crossover) in [
+        (high_crossover, &params.high_crossover),
+        (low_crossover, &params.low_crossover),
+    ] {
+        let [scope, controls] = layout::columns(rect, columns, 0.0);
+        ui.painter().rect_filled(scope, 0.0, theme.bg_normal);
+        ui.painter().hline(
+            scope.left()..=scope.right(),
+            scope.center().y,
+            Stroke::new(1.0, theme.bg_deep),
+        );
+        panel_crossover(
+            ui,
+            controls,
+            crossover,
+            &params.crossover_slope,
+            setter,
+            theme,
+        );
+    }
 }
 
-// TEMP SIZES
-pub(crate) const GUI_DEFAULT_WIDTH: u32 = 560;
-pub(crate) const GUI_DEFAULT_HEIGHT: u32 = 350;
-pub(crate) const GUI_MINIMUM_WIDTH: u32 = 560;
+// the DPI-independent size of the window, the default size matches the mockup
+pub(crate) const GUI_DEFAULT_WIDTH: u32 = 651;
+pub(crate) const GUI_DEFAULT_HEIGHT: u32 = 391;
+pub(crate) const GUI_MINIMUM_WIDTH: u32 = 650;
 pub(crate) const GUI_MINIMUM_HEIGHT: u32 = 350;
-/// TEMP GUI
+
 pub(crate) fn create_gui(
     plugin: &mut Malt,
     _async_executor: AsyncExecutor<Malt>,
 ) -> Option<Box<dyn Editor>> {
     let params = plugin.params.clone();
     let egui_state = plugin.params.editor_state.clone();
-    create_egui_editor(
+    let last_note_on = plugin.last_note_on.clone();
+    let meters = plugin.meters.clone();
+    let captures = plugin.captures.clone();
+    let undo_history = plugin.undo_history.clone();
+    let editor = create_egui_editor(
         plugin.params.editor_state.clone(),
-        (),
+        UIState::new(),
         |ctx, state| {
+            // read the theme file again, so changes show up after reopening the editor
+            state.custom_theme = match load_custom_theme() {
+                Ok(theme) => theme,
+                Err(err) => {
+                    nih_error!("{err}");
+                    None
+                }
+            };
+
             // Load new fonts
             {
                 use egui::{FontData, FontDefinitions, FontFamily};
@@ -262,17 +989,103 @@ pub(crate) fn create_gui(
                 ctx.set_style(style);
             }
 
+            // the zoom is set in the header, and saved with the plugin state
+            ctx.options_mut(|x| x.zoom_with_keyboard = false);
+
             // Enable loading image resources
             egui_extras::install_image_loaders(ctx);
         },
         move |ctx, setter, state| {
-            ResizableWindow::new("resizable-window")
+            // the meters are updated every frame, so keep repainting at the display rate
+            state.update_meters(meters.take());
+            state.update_spectrum(&captures);
+            ctx.request_repaint();
+
+            // the sample rate is only known once the plugin has been initialized
+            let sample_rate = match captures.sample_rate.load(Ordering::Relaxed) {
+                x if x > 0.0 => x,
+                _ => 44100.0,
+            };
+
+            let theme = &state.theme(*params.editor_theme.read().unwrap());
+            if ctx.style().visuals.dark_mode != theme.dark {
+                ctx.set_visuals(if theme.dark {
+                    egui::Visuals::dark()
+                } else {
+                    egui::Visuals::light()
+                });
+            }
+
+            // scale the window with the zoom, so the layout still fits. the window's size was
+            // already scaled when it was last opened
+            let zoom = ZOOM_LEVELS[zoom_level(&params)] as f32 / 100.0;
+            if let Some(window_zoom) = state.window_zoom.filter(|x| *x != zoom) {
+                let (width, height) = egui_state.size();
+                let ratio = zoom / window_zoom;
+                egui_state.set_requested_size((
+                    (width as f32 * ratio).round() as u32,
+                    (height as f32 * ratio).round() as u32,
+                ));
+            }
+            state.window_zoom = Some(zoom);
+            if ctx.zoom_factor() != zoom {
+                ctx.set_zoom_factor(zoom);
+            }
+
+            // ctrl+z and ctrl+shift+z, unless a text field is being edited
+            if !ctx.wants_keyboard_input() {
+                let (redo, undo) = ctx.input_mut(|x| {
+                    // shift is ignored when matching shortcuts, so check for redo first
+                    (
+                        x.consume_shortcut(&REDO_SHORTCUT),
+                        x.consume_shortcut(&UNDO_SHORTCUT),
+                    )
+                });
+                if redo {
+                    undo_history.redo(setter.raw_context, &params);
+                } else if undo {
+                    undo_history.undo(setter.raw_context, &params);
+                }
+            }
+
+            // MIDI learn, assign the next pressed key to the channel that is waiting for it
+            if let Some(channel) = state.learning_channel {
+                let (counter, note, midi_channel) = last_note_on.load();
+                if counter != state.learn_counter {
+                    let midi_channel = state.learn_midi_channel.then_some(midi_channel);
+                    params
+                        .note_map
+                        .write()
+                        .unwrap()
+                        .assign(note, channel, midi_channel);
+                    state.learning_channel = None;
+                } else {
+                    // keep polling until a key gets pressed
+                    ctx.request_repaint();
+                }
+            }
+
+            ZoomableWindow::new("resizable-window")
                 .min_size(vec2(GUI_MINIMUM_WIDTH as f32, GUI_MINIMUM_HEIGHT as f32))
-                .show(ctx, &egui_state, |ui| {
+                .show(ctx, &egui_state, zoom, |ui| {
+                    if state.view == View::Main {
+                        main_view(ui, &params, setter, state, &captures, sample_rate, theme);
+                        return;
+                    }
+
+                    egui::TopBottomPanel::top("header panel")
+                        .exact_height(HEADER_HEIGHT)
+                        .show_separator_line(false)
+                        .frame(egui::Frame::none().fill(theme.bg_deep))
+                        .show(ctx, |ui| {
+                            let rect = ui.max_rect();
+                            header(ui, rect, &params, state, theme);
+                        });
+
                     egui::SidePanel::left("left panel")
                         .exact_width(250.0)
                         .resizable(false)
-                        .frame(egui::Frame::none().fill(C::BG_NORMAL))
+                        .frame(egui::Frame::none().fill(theme.bg_normal))
                         .show(ctx, |ui| {
                             fn blockbutton_param<'a>(
                                 ui: &mut Ui,
@@ -283,6 +1096,7 @@ pub(crate) fn create_gui(
                                 active_color: Color32,
                                 fg_color: Color32,
                                 bg_color: Color32,
+                                theme: &Theme,
                             ) -> Response {
                                 let old_active = param.value();
 
@@ -294,6 +1108,7 @@ pub(crate) fn create_gui(
                                     active_color,
                                     fg_color,
                                     bg_color,
+                                    theme,
                                 );
 
                                 if res.clicked() {
@@ -304,334 +1119,1036 @@ pub(crate) fn create_gui(
                                 res
                             }
 
-                            // top bypass button
-                            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                            ui.style_mut().spacing.scroll = ScrollStyle::solid();
+                            ScrollArea::vertical().show(ui, |ui| {
+                                // top bypass button
+                                ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                                    blockbutton_param(
+                                        ui,
+                                        &params.bypass,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "Bypass",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(52.0, 22.0),
+                                        theme.fg_orange,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                });
+
+                                // meters section
+                                rt(ui, "Meters", &C::FONT_NORMAL, theme.text_base, theme.fg_dim);
+                                ui.horizontal(|ui| {
+                                    rt(ui, "In", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
+                                    ui.add(PeakMeter::new(
+                                        state.meters.input_peak,
+                                        vec2(180.0, 8.0),
+                                        theme.fg_green,
+                                        theme,
+                                    ));
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(ui, "Out", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
+                                    ui.add(PeakMeter::new(
+                                        state.meters.output_peak,
+                                        vec2(180.0, 8.0),
+                                        theme.fg_green,
+                                        theme,
+                                    ));
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(ui, "High", &C::FONT_NORMAL, theme.text_sm, theme.fg_yellow);
+                                    ui.add(GainReductionMeter::new(
+                                        state.meters.gain_reduction[2],
+                                        MAX_BOOST_DB,
+                                        METER_MAX_REDUCTION_DB,
+                                        vec2(180.0, 8.0),
+                                        theme.fg_yellow,
+                                        theme,
+                                    ));
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(ui, "Mid", &C::FONT_NORMAL, theme.text_sm, theme.fg_purple);
+                                    ui.add(GainReductionMeter::new(
+                                        state.meters.gain_reduction[1],
+                                        MAX_BOOST_DB,
+                                        METER_MAX_REDUCTION_DB,
+                                        vec2(180.0, 8.0),
+                                        theme.fg_purple,
+                                        theme,
+                                    ));
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(ui, "Low", &C::FONT_NORMAL, theme.text_sm, theme.fg_blue);
+                                    ui.add(GainReductionMeter::new(
+                                        state.meters.gain_reduction[0],
+                                        MAX_BOOST_DB,
+                                        METER_MAX_REDUCTION_DB,
+                                        vec2(180.0, 8.0),
+                                        theme.fg_blue,
+                                        theme,
+                                    ));
+                                });
+                                ui.add(GainReductionHistory::new(
+                                    &state.gr_history,
+                                    GR_HISTORY_LEN,
+                                    METER_MAX_REDUCTION_DB,
+                                    vec2(230.0, 60.0),
+                                    theme.bands(),
+                                    theme,
+                                ));
+                                rt(
+                                    ui,
+                                    format!("Active voices: {}", state.meters.active_voices),
+                                    &C::FONT_NORMAL,
+                                    theme.text_sm,
+                                    theme.fg_dim,
+                                );
+                                ui.separator();
+
+                                // options section
+                                rt(
+                                    ui,
+                                    "Options",
+                                    &C::FONT_NORMAL,
+                                    theme.text_base,
+                                    theme.fg_dim,
+                                );
                                 blockbutton_param(
                                     ui,
-                                    &params.bypass,
+                                    &params.smoothing,
                                     setter,
                                     ButtonContent::Text(
-                                        "Bypass",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
+                                        "Smooth",
+                                        FontId::new(theme.text_base, C::FONT_NORMAL),
                                     ),
                                     vec2(52.0, 22.0),
-                                    C::FG_ORANGE,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
+                                    theme.fg_blue,
+                                    theme.fg_bright,
+                                    theme.bg_normal,
+                                    theme,
                                 );
-                            });
 
-                            // options section
-                            rt(ui, "Options", &C::FONT_NORMAL, C::TEXT_BASE, C::FG_GREY);
-                            blockbutton_param(
-                                ui,
-                                &params.smoothing,
-                                setter,
-                                ButtonContent::Text(
-                                    "Smooth",
-                                    FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                ),
-                                vec2(52.0, 22.0),
-                                C::FG_BLUE,
-                                C::FG_WHITE,
-                                C::BG_NORMAL,
-                            );
-
-                            ui.horizontal(|ui| {
-                                rt(ui, "Lookahead", &C::FONT_NORMAL, C::TEXT_SM, C::FG_GREY);
-                                ui.add(Knob::for_param(
-                                    &params.lookahead,
-                                    setter,
-                                    24.0,
-                                    KnobStyle::Analog {
-                                        highlight_color: C::FG_YELLOW,
-                                        line_width: 2.0,
-                                    },
-                                ));
-                                ui.add(KnobText::for_param(
-                                    &params.lookahead,
-                                    setter,
-                                    vec2(60.0, 24.0),
-                                    FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                    C::FG_GREY,
-                                    true,
-                                    true,
-                                    false,
-                                ));
-                            });
+                                ui.horizontal(|ui| {
+                                    rt(
+                                        ui,
+                                        "Lookahead",
+                                        &C::FONT_NORMAL,
+                                        theme.text_sm,
+                                        theme.fg_dim,
+                                    );
+                                    ui.add(Knob::for_param(
+                                        &params.lookahead,
+                                        setter,
+                                        24.0,
+                                        KnobStyle::Analog {
+                                            highlight_color: theme.fg_yellow,
+                                            line_width: 2.0,
+                                        },
+                                        theme,
+                                    ));
+                                    ui.add(KnobText::for_param(
+                                        &params.lookahead,
+                                        setter,
+                                        vec2(60.0, 24.0),
+                                        FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                        theme.fg_dim,
+                                        true,
+                                        true,
+                                        false,
+                                        theme,
+                                    ));
+                                });
 
-                            ui.horizontal(|ui| {
-                                rt(ui, "Mix", &C::FONT_NORMAL, C::TEXT_SM, C::FG_GREY);
-                                ui.add(Knob::for_param(
-                                    &params.mix,
-                                    setter,
-                                    24.0,
-                                    KnobStyle::Analog {
-                                        highlight_color: C::FG_WHITE,
-                                        line_width: 2.0,
-                                    },
-                                ));
-                                ui.add(KnobText::for_param(
-                                    &params.mix,
-                                    setter,
-                                    vec2(60.0, 24.0),
-                                    FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                    C::FG_GREY,
-                                    true,
-                                    true,
-                                    false,
-                                ));
-                            });
-                            ui.horizontal(|ui| {
-                                rt(ui, "MIDI Mode", &C::FONT_NORMAL, C::TEXT_SM, C::FG_GREY);
-                                ui.add(ParamSlider::for_param(&params.midi_mode, setter));
-                            });
-                            if matches!(params.midi_mode.value(), MIDIProcessingMode::Pitch) {
                                 ui.horizontal(|ui| {
-                                    rt(ui, "Root note", &C::FONT_NORMAL, C::TEXT_SM, C::FG_GREY);
+                                    rt(ui, "Mix", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
                                     ui.add(Knob::for_param(
-                                        &params.midi_root_note,
+                                        &params.mix,
                                         setter,
                                         24.0,
                                         KnobStyle::Analog {
-                                            highlight_color: C::FG_WHITE,
+                                            highlight_color: theme.fg_bright,
                                             line_width: 2.0,
                                         },
+                                        theme,
                                     ));
                                     ui.add(KnobText::for_param(
-                                        &params.midi_root_note,
+                                        &params.mix,
                                         setter,
                                         vec2(60.0, 24.0),
-                                        FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                        C::FG_GREY,
+                                        FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                        theme.fg_dim,
                                         true,
                                         true,
                                         false,
+                                        theme,
                                     ));
                                 });
-                            }
+                                ui.horizontal(|ui| {
+                                    rt(
+                                        ui,
+                                        "MIDI Mode",
+                                        &C::FONT_NORMAL,
+                                        theme.text_sm,
+                                        theme.fg_dim,
+                                    );
+                                    ui.add(ParamSlider::for_param(&params.midi_mode, setter));
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(ui, "Trigger", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
+                                    ui.add(ParamSlider::for_param(&params.trigger_mode, setter));
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(
+                                        ui,
+                                        "Voice stealing",
+                                        &C::FONT_NORMAL,
+                                        theme.text_sm,
+                                        theme.fg_dim,
+                                    );
+                                    ui.add(ParamSlider::for_param(&params.voice_steal, setter));
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(ui, "Seed", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
+                                    ui.add(ParamSlider::for_param(&params.seed, setter));
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(ui, "Invert", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
+                                    blockbutton_param(
+                                        ui,
+                                        &params.invert,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "On",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_red,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                });
+                                if params.invert.value() {
+                                    for (name, param) in [
+                                        ("Low floor", &params.low_floor),
+                                        ("Mid floor", &params.mid_floor),
+                                        ("High floor", &params.high_floor),
+                                    ] {
+                                        ui.horizontal(|ui| {
+                                            rt(
+                                                ui,
+                                                name,
+                                                &C::FONT_NORMAL,
+                                                theme.text_sm,
+                                                theme.fg_dim,
+                                            );
+                                            ui.add(ParamSlider::for_param(param, setter));
+                                        });
+                                    }
+                                }
+                                if matches!(params.midi_mode.value(), MIDIProcessingMode::Pitch) {
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Root note",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(Knob::for_param(
+                                            &params.midi_root_note,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_bright,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &params.midi_root_note,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                    });
+                                }
+                                if matches!(params.midi_mode.value(), MIDIProcessingMode::Map) {
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Presets",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+
+                                        let presets = [
+                                            ("GM drums", NoteMap::gm_drums()),
+                                            (
+                                                "Pitch",
+                                                NoteMap::from_root_note(
+                                                    params.midi_root_note.value() as u8,
+                                                ),
+                                            ),
+                                            ("Clear", NoteMap::empty()),
+                                        ];
+                                        for (name, preset) in presets {
+                                            let res = simple_block_button(
+                                                ui,
+                                                false,
+                                                ButtonContent::Text(
+                                                    name,
+                                                    FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                                ),
+                                                vec2(52.0, 22.0),
+                                                theme.fg_green,
+                                                theme.fg_bright,
+                                                theme.bg_normal,
+                                                theme,
+                                            );
+                                            if res.clicked() {
+                                                *params.note_map.write().unwrap() = preset;
+                                            }
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Learn MIDI channel",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        let res = simple_block_button(
+                                            ui,
+                                            state.learn_midi_channel,
+                                            ButtonContent::Text(
+                                                "Ch",
+                                                FontId::new(theme.text_base, C::FONT_NORMAL),
+                                            ),
+                                            vec2(22.0, 22.0),
+                                            theme.fg_green,
+                                            theme.fg_bright,
+                                            theme.bg_normal,
+                                            theme,
+                                        );
+                                        if res.clicked() {
+                                            state.learn_midi_channel = !state.learn_midi_channel;
+                                        }
+                                    });
+                                }
 
-                            ui.separator();
+                                ui.separator();
 
-                            // band splits section
-                            rt(ui, "Band splits", &C::FONT_NORMAL, C::TEXT_BASE, C::FG_GREY);
-                            ui.horizontal(|ui| {
-                                rt(ui, "Slope", &C::FONT_NORMAL, C::TEXT_SM, C::FG_GREY);
-                                ui.add(ParamSlider::for_param(&params.crossover_slope, setter));
-                            });
+                                // band splits section
+                                rt(
+                                    ui,
+                                    "Band splits",
+                                    &C::FONT_NORMAL,
+                                    theme.text_base,
+                                    theme.fg_dim,
+                                );
+                                ui.horizontal(|ui| {
+                                    rt(ui, "Slope", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
+                                    ui.add(ParamSlider::for_param(&params.crossover_slope, setter));
+                                });
 
-                            ui.horizontal(|ui| {
-                                ui.add(Knob::for_param(
-                                    &params.high_crossover,
-                                    setter,
-                                    15.0,
-                                    KnobStyle::Donut { line_width: 4.0 },
-                                ));
-                                ui.add(KnobText::for_param(
-                                    &params.high_crossover,
-                                    setter,
-                                    vec2(70.0, 15.0),
-                                    FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                    C::FG_WHITE,
-                                    true,
-                                    true,
-                                    false,
-                                ));
-                            });
+                                ui.horizontal(|ui| {
+                                    ui.add(Knob::for_param(
+                                        &params.high_crossover,
+                                        setter,
+                                        15.0,
+                                        KnobStyle::Donut { line_width: 4.0 },
+                                        theme,
+                                    ));
+                                    ui.add(KnobText::for_param(
+                                        &params.high_crossover,
+                                        setter,
+                                        vec2(70.0, 15.0),
+                                        FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                        theme.fg_bright,
+                                        true,
+                                        true,
+                                        false,
+                                        theme,
+                                    ));
+                                });
 
-                            ui.horizontal(|ui| {
-                                ui.add(Knob::for_param(
-                                    &params.low_crossover,
-                                    setter,
-                                    15.0,
-                                    KnobStyle::Donut { line_width: 4.0 },
-                                ));
-                                ui.add(KnobText::for_param(
-                                    &params.low_crossover,
-                                    setter,
-                                    vec2(70.0, 15.0),
-                                    FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                    C::FG_WHITE,
-                                    true,
-                                    true,
-                                    false,
-                                ));
-                            });
+                                ui.horizontal(|ui| {
+                                    ui.add(Knob::for_param(
+                                        &params.low_crossover,
+                                        setter,
+                                        15.0,
+                                        KnobStyle::Donut { line_width: 4.0 },
+                                        theme,
+                                    ));
+                                    ui.add(KnobText::for_param(
+                                        &params.low_crossover,
+                                        setter,
+                                        vec2(70.0, 15.0),
+                                        FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                        theme.fg_bright,
+                                        true,
+                                        true,
+                                        false,
+                                        theme,
+                                    ));
+                                });
 
-                            ui.horizontal(|ui| {
-                                rt(ui, "HIGH", &C::FONT_NORMAL, C::TEXT_SM, C::FG_GREY);
+                                ui.horizontal(|ui| {
+                                    rt(ui, "HIGH", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
 
-                                blockbutton_param(
-                                    ui,
-                                    &params.solo_high,
-                                    setter,
-                                    ButtonContent::Text(
-                                        "S",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                    ),
-                                    vec2(22.0, 22.0),
-                                    C::FG_BLUE,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
-                                );
-                                blockbutton_param(
-                                    ui,
-                                    &params.mute_high,
-                                    setter,
-                                    ButtonContent::Text(
-                                        "M",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                    ),
-                                    vec2(22.0, 22.0),
-                                    C::FG_RED,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
-                                );
-                                blockbutton_param(
-                                    ui,
-                                    &params.bypass_high,
-                                    setter,
-                                    ButtonContent::Text(
-                                        "X",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                    ),
-                                    vec2(22.0, 22.0),
-                                    C::FG_ORANGE,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
-                                );
-                            });
-                            ui.horizontal(|ui| {
-                                rt(ui, "MID", &C::FONT_NORMAL, C::TEXT_SM, C::FG_GREY);
+                                    blockbutton_param(
+                                        ui,
+                                        &params.solo_high,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "S",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_blue,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                    blockbutton_param(
+                                        ui,
+                                        &params.mute_high,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "M",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_red,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                    blockbutton_param(
+                                        ui,
+                                        &params.bypass_high,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "X",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_orange,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(ui, "MID", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
 
-                                blockbutton_param(
-                                    ui,
-                                    &params.solo_mid,
-                                    setter,
-                                    ButtonContent::Text(
-                                        "S",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                    ),
-                                    vec2(22.0, 22.0),
-                                    C::FG_BLUE,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
-                                );
-                                blockbutton_param(
-                                    ui,
-                                    &params.mute_mid,
-                                    setter,
-                                    ButtonContent::Text(
-                                        "M",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                    ),
-                                    vec2(22.0, 22.0),
-                                    C::FG_RED,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
-                                );
-                                blockbutton_param(
-                                    ui,
-                                    &params.bypass_mid,
-                                    setter,
-                                    ButtonContent::Text(
-                                        "X",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                    ),
-                                    vec2(22.0, 22.0),
-                                    C::FG_ORANGE,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
-                                );
-                            });
-                            ui.horizontal(|ui| {
-                                rt(ui, "LOW", &C::FONT_NORMAL, C::TEXT_SM, C::FG_GREY);
+                                    blockbutton_param(
+                                        ui,
+                                        &params.solo_mid,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "S",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_blue,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                    blockbutton_param(
+                                        ui,
+                                        &params.mute_mid,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "M",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_red,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                    blockbutton_param(
+                                        ui,
+                                        &params.bypass_mid,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "X",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_orange,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    rt(ui, "LOW", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
 
-                                blockbutton_param(
+                                    blockbutton_param(
+                                        ui,
+                                        &params.solo_low,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "S",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_blue,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                    blockbutton_param(
+                                        ui,
+                                        &params.mute_low,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "M",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_red,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                    blockbutton_param(
+                                        ui,
+                                        &params.bypass_low,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "X",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_orange,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                });
+                                ui.separator();
+
+                                // sequencer section
+                                rt(
                                     ui,
-                                    &params.solo_low,
-                                    setter,
-                                    ButtonContent::Text(
-                                        "S",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                    ),
-                                    vec2(22.0, 22.0),
-                                    C::FG_BLUE,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
+                                    "Sequencer",
+                                    &C::FONT_NORMAL,
+                                    theme.text_base,
+                                    theme.fg_dim,
                                 );
-                                blockbutton_param(
+                                ui.horizontal(|ui| {
+                                    rt(ui, "Enabled", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
+                                    blockbutton_param(
+                                        ui,
+                                        &params.seq_enabled,
+                                        setter,
+                                        ButtonContent::Text(
+                                            "On",
+                                            FontId::new(theme.text_base, C::FONT_NORMAL),
+                                        ),
+                                        vec2(22.0, 22.0),
+                                        theme.fg_orange,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                });
+                                if params.seq_enabled.value() {
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Steps",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(ParamSlider::for_param(&params.seq_steps, setter));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Rate",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(ParamSlider::for_param(&params.seq_rate, setter));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Swing",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(ParamSlider::for_param(&params.seq_swing, setter));
+                                    });
+                                }
+                                ui.separator();
+
+                                // morph patterns section, used by the "Pattern morph" curves
+                                rt(
                                     ui,
-                                    &params.mute_low,
-                                    setter,
-                                    ButtonContent::Text(
-                                        "M",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                    ),
-                                    vec2(22.0, 22.0),
-                                    C::FG_RED,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
+                                    "Morph patterns",
+                                    &C::FONT_NORMAL,
+                                    theme.text_base,
+                                    theme.fg_dim,
                                 );
-                                blockbutton_param(
+                                for (band, name, color) in [
+                                    (2, "High", theme.fg_yellow),
+                                    (1, "Mid", theme.fg_purple),
+                                    (0, "Low", theme.fg_blue),
+                                ] {
+                                    rt(ui, name, &C::FONT_NORMAL, theme.text_sm, color);
+                                    ui.horizontal(|ui| {
+                                        for slot in 0..2 {
+                                            let mut pattern =
+                                                params.morph_patterns.read().unwrap().bands[band]
+                                                    [slot]
+                                                    .clone();
+                                            let res = ui
+                                                .push_id((band, slot), |ui| {
+                                                    ui.add(PatternEditor::new(
+                                                        &mut pattern,
+                                                        vec2(100.0, 50.0),
+                                                        color,
+                                                        theme,
+                                                    ))
+                                                })
+                                                .inner;
+                                            if res.changed() {
+                                                params.morph_patterns.write().unwrap().bands
+                                                    [band][slot] = pattern;
+                                            }
+                                        }
+                                    });
+                                }
+                                ui.separator();
+
+                                // modulation section
+                                rt(
                                     ui,
-                                    &params.bypass_low,
-                                    setter,
-                                    ButtonContent::Text(
-                                        "X",
-                                        FontId::new(C::TEXT_BASE, C::FONT_NORMAL),
-                                    ),
-                                    vec2(22.0, 22.0),
-                                    C::FG_ORANGE,
-                                    C::FG_WHITE,
-                                    C::BG_NORMAL,
+                                    "Modulation",
+                                    &C::FONT_NORMAL,
+                                    theme.text_base,
+                                    theme.fg_dim,
                                 );
+                                for (i, slot) in params.modulation.iter().enumerate() {
+                                    rt(
+                                        ui,
+                                        format!("Slot {}", i + 1),
+                                        &C::FONT_NORMAL,
+                                        theme.text_sm,
+                                        theme.fg_dim,
+                                    );
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Source",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(ParamSlider::for_param(&slot.source, setter));
+                                    });
+                                    if matches!(slot.source.value(), ModSource::Off) {
+                                        continue;
+                                    }
+                                    if matches!(slot.source.value(), ModSource::CC) {
+                                        ui.horizontal(|ui| {
+                                            rt(
+                                                ui,
+                                                "CC",
+                                                &C::FONT_NORMAL,
+                                                theme.text_sm,
+                                                theme.fg_dim,
+                                            );
+                                            ui.add(ParamSlider::for_param(&slot.cc, setter));
+                                        });
+                                    }
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Target",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(ParamSlider::for_param(&slot.target, setter));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Channel",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(ParamSlider::for_param(&slot.channel, setter));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Band",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(ParamSlider::for_param(&slot.band, setter));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Amount",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(Knob::for_param(
+                                            &slot.amount,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_green,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &slot.amount,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                    });
+                                }
                             });
                         });
 
                     egui::CentralPanel::default()
-                        .frame(egui::Frame::none().fill(C::BG_NORMAL))
+                        .frame(egui::Frame::none().fill(theme.bg_normal))
                         .show(ctx, |ui| {
                             ui.style_mut().spacing.scroll = ScrollStyle::solid();
                             ScrollArea::vertical().show(ui, |ui| {
-                                // channels
-                                let channel_count =
-                                    if matches!(params.midi_mode.value(), MIDIProcessingMode::Omni)
-                                    {
-                                        1
-                                    } else {
-                                        16
-                                    };
-
-                                for i in 0..channel_count {
+                                // spectrum analyzer, drag the band edges to set the crossovers
+                                ui.add(SpectrumAnalyzer::new(
+                                    &state.spectrum_input,
+                                    &state.spectrum_output,
+                                    sample_rate,
+                                    &params.low_crossover,
+                                    &params.high_crossover,
+                                    setter,
+                                    vec2(ui.available_width(), 120.0),
+                                    theme,
+                                ));
+
+                                // crossover response, evaluated from the same filters as the
+                                // audio thread uses
+                                ui.horizontal(|ui| {
+                                    rt(
+                                        ui,
+                                        "Crossover response",
+                                        &C::FONT_NORMAL,
+                                        theme.text_sm,
+                                        theme.fg_dim,
+                                    );
+                                    let res = simple_block_button(
+                                        ui,
+                                        state.show_phase,
+                                        ButtonContent::Text(
+                                            "Phase",
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                        ),
+                                        vec2(52.0, 22.0),
+                                        theme.fg_blue,
+                                        theme.fg_bright,
+                                        theme.bg_normal,
+                                        theme,
+                                    );
+                                    if res.clicked() {
+                                        state.show_phase = !state.show_phase;
+                                    }
+                                });
+                                let [low_crossover, high_crossover] = clamp_crossovers(
+                                    params.low_crossover.value(),
+                                    params.high_crossover.value(),
+                                );
+                                let splitter = ThreeBandSplitter::new(
+                                    params.crossover_slope.value(),
+                                    low_crossover.into(),
+                                    high_crossover.into(),
+                                    sample_rate.into(),
+                                );
+                                ui.add(FrequencyResponse::new(
+                                    &splitter,
+                                    state.show_phase,
+                                    vec2(ui.available_width(), 80.0),
+                                    theme.bands(),
+                                    theme,
+                                ));
+
+                                // scope, synced to the song while the host is playing
+                                ui.horizontal(|ui| {
+                                    rt(ui, "Scope", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
+                                    for (beats, label) in [(1, "1"), (2, "2"), (3, "3"), (4, "4")] {
+                                        let res = simple_block_button(
+                                            ui,
+                                            state.scope_beats == beats,
+                                            ButtonContent::Text(
+                                                label,
+                                                FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            ),
+                                            vec2(22.0, 22.0),
+                                            theme.fg_orange,
+                                            theme.fg_bright,
+                                            theme.bg_normal,
+                                            theme,
+                                        );
+                                        if res.clicked() {
+                                            state.scope_beats = beats;
+                                        }
+                                    }
+                                    rt(ui, "beats", &C::FONT_NORMAL, theme.text_sm, theme.fg_dim);
+                                });
+                                let (first_frame, frames) =
+                                    captures.scope.read_frames(SCOPE_FRAMES);
+                                let triggers = captures.scope.read_triggers();
+                                ui.add(Scope::new(
+                                    first_frame,
+                                    &frames,
+                                    &triggers,
+                                    state.scope_beats,
+                                    scope_frames_per_beat(sample_rate),
+                                    vec2(ui.available_width(), 80.0),
+                                    [
+                                        Some(theme.fg_blue),
+                                        Some(theme.fg_purple),
+                                        Some(theme.fg_yellow),
+                                    ],
+                                    theme,
+                                ));
+
+                                // overview of every channel, click a row to edit it below
+                                let mut active_channel = active_channel(&params);
+                                let res = ui.add(ChannelOverview::new(
+                                    &params.channels[..channel_count(&params)],
+                                    &mut active_channel,
+                                    &state.channel_activity,
+                                    setter,
+                                    ui.available_width(),
+                                    OVERVIEW_ROW_HEIGHT,
+                                    theme,
+                                ));
+                                if res.changed() {
+                                    set_active_channel(&params, active_channel);
+                                }
+
+                                // the selected channel
+                                {
+                                    let i = active_channel;
                                     let ch = &params.channels[i];
 
                                     rt(
                                         ui,
                                         format!("Channel {}", i),
                                         &C::FONT_NORMAL,
-                                        C::TEXT_BASE,
-                                        C::FG_GREY,
+                                        theme.text_base,
+                                        theme.fg_dim,
+                                    );
+
+                                    // the preview is rendered every frame, so it follows the knobs
+                                    let lookahead = params.lookahead.value() / 1000.0;
+                                    let curves = ch.value(lookahead).preview(
+                                        lookahead,
+                                        PREVIEW_TEMPO,
+                                        &params.morph_patterns.read().unwrap(),
                                     );
+                                    ui.add(EnvelopePreview::new(
+                                        &curves,
+                                        (lookahead * PREVIEW_SAMPLE_RATE).round() as usize,
+                                        vec2(300.0, 60.0),
+                                        theme.bands(),
+                                        theme,
+                                    ));
+                                    if params.seq_enabled.value() {
+                                        ui.horizontal(|ui| {
+                                            let step_count = params.seq_steps.value() as usize;
+                                            let mut steps =
+                                                *params.sequencer.read().unwrap().channel_steps(i);
+                                            let res = ui.add(StepGrid::new(
+                                                &mut steps[..step_count],
+                                                vec2(9.0, 22.0),
+                                                2.0,
+                                                theme.fg_orange,
+                                                theme,
+                                            ));
+                                            if res.changed() {
+                                                let mut sequencer =
+                                                    params.sequencer.write().unwrap();
+                                                for (step, velocity) in steps.iter().enumerate() {
+                                                    sequencer.set_velocity(i, step, *velocity);
+                                                }
+                                            }
+
+                                            let res = simple_block_button(
+                                                ui,
+                                                false,
+                                                ButtonContent::Text(
+                                                    "Clear",
+                                                    FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                                ),
+                                                vec2(52.0, 22.0),
+                                                theme.fg_red,
+                                                theme.fg_bright,
+                                                theme.bg_normal,
+                                                theme,
+                                            );
+                                            if res.clicked() {
+                                                params.sequencer.write().unwrap().clear_channel(i);
+                                            }
+                                        });
+                                    }
+                                    if matches!(params.midi_mode.value(), MIDIProcessingMode::Map) {
+                                        ui.horizontal(|ui| {
+                                            let learning = state.learning_channel == Some(i);
+                                            let res = simple_block_button(
+                                                ui,
+                                                learning,
+                                                ButtonContent::Text(
+                                                    "Learn",
+                                                    FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                                ),
+                                                vec2(52.0, 22.0),
+                                                theme.fg_green,
+                                                theme.fg_bright,
+                                                theme.bg_normal,
+                                                theme,
+                                            );
+                                            if res.clicked() {
+                                                if learning {
+                                                    state.learning_channel = None;
+                                                } else {
+                                                    state.learning_channel = Some(i);
+                                                    state.learn_counter = last_note_on.load().0;
+                                                }
+                                            }
+
+                                            let res = simple_block_button(
+                                                ui,
+                                                false,
+                                                ButtonContent::Text(
+                                                    "Clear",
+                                                    FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                                ),
+                                                vec2(52.0, 22.0),
+                                                theme.fg_red,
+                                                theme.fg_bright,
+                                                theme.bg_normal,
+                                                theme,
+                                            );
+                                            if res.clicked() {
+                                                params.note_map.write().unwrap().clear_channel(i);
+                                            }
+
+                                            // list the notes assigned to this channel
+                                            let notes: Vec<String> = {
+                                                let note_map = params.note_map.read().unwrap();
+                                                note_map
+                                                    .notes_for_channel(i)
+                                                    .map(|note| {
+                                                        match note_map.entry(note).midi_channel {
+                                                            Some(x) => format!(
+                                                                "{} (ch {})",
+                                                                note_name(note),
+                                                                x + 1
+                                                            ),
+                                                            None => note_name(note),
+                                                        }
+                                                    })
+                                                    .collect()
+                                            };
+                                            let text = if learning {
+                                                String::from("Press a key...")
+                                            } else if notes.is_empty() {
+                                                String::from("No notes")
+                                            } else {
+                                                notes.join(", ")
+                                            };
+                                            rt(
+                                                ui,
+                                                text,
+                                                &C::FONT_NORMAL,
+                                                theme.text_sm,
+                                                theme.fg_dim,
+                                            );
+                                        });
+                                    }
                                     ui.horizontal(|ui| {
                                         ui.add(Knob::for_param(
                                             &ch.high_precomp,
                                             setter,
                                             24.0,
                                             KnobStyle::Analog {
-                                                highlight_color: C::FG_YELLOW,
+                                                highlight_color: theme.fg_yellow,
                                                 line_width: 2.0,
                                             },
+                                            theme,
                                         ));
                                         ui.add(KnobText::for_param(
                                             &ch.high_precomp,
                                             setter,
                                             vec2(60.0, 24.0),
-                                            FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                            C::FG_GREY,
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
                                             true,
                                             true,
                                             false,
+                                            theme,
+                                        ));
+
+                                        ui.add(Knob::for_param(
+                                            &ch.high_hold,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_yellow,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.high_hold,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
                                         ));
 
                                         ui.add(Knob::for_param(
@@ -639,19 +2156,21 @@ pub(crate) fn create_gui(
                                             setter,
                                             24.0,
                                             KnobStyle::Analog {
-                                                highlight_color: C::FG_YELLOW,
+                                                highlight_color: theme.fg_yellow,
                                                 line_width: 2.0,
                                             },
+                                            theme,
                                         ));
                                         ui.add(KnobText::for_param(
                                             &ch.high_decay,
                                             setter,
                                             vec2(60.0, 24.0),
-                                            FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                            C::FG_GREY,
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
                                             true,
                                             true,
                                             false,
+                                            theme,
                                         ));
 
                                         ui.add(Knob::for_param(
@@ -659,19 +2178,21 @@ pub(crate) fn create_gui(
                                             setter,
                                             24.0,
                                             KnobStyle::Analog {
-                                                highlight_color: C::FG_WHITE,
+                                                highlight_color: theme.fg_bright,
                                                 line_width: 2.0,
                                             },
+                                            theme,
                                         ));
                                         ui.add(KnobText::for_param(
                                             &ch.high_db,
                                             setter,
                                             vec2(60.0, 24.0),
-                                            FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                            C::FG_GREY,
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
                                             true,
                                             true,
                                             false,
+                                            theme,
                                         ));
                                     });
                                     ui.horizontal(|ui| {
@@ -680,19 +2201,43 @@ pub(crate) fn create_gui(
                                             setter,
                                             24.0,
                                             KnobStyle::Analog {
-                                                highlight_color: C::FG_PURPLE,
+                                                highlight_color: theme.fg_purple,
                                                 line_width: 2.0,
                                             },
+                                            theme,
                                         ));
                                         ui.add(KnobText::for_param(
                                             &ch.mid_precomp,
                                             setter,
                                             vec2(60.0, 24.0),
-                                            FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                            C::FG_GREY,
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
                                             true,
                                             true,
                                             false,
+                                            theme,
+                                        ));
+
+                                        ui.add(Knob::for_param(
+                                            &ch.mid_hold,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_purple,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.mid_hold,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
                                         ));
 
                                         ui.add(Knob::for_param(
@@ -700,19 +2245,21 @@ pub(crate) fn create_gui(
                                             setter,
                                             24.0,
                                             KnobStyle::Analog {
-                                                highlight_color: C::FG_PURPLE,
+                                                highlight_color: theme.fg_purple,
                                                 line_width: 2.0,
                                             },
+                                            theme,
                                         ));
                                         ui.add(KnobText::for_param(
                                             &ch.mid_decay,
                                             setter,
                                             vec2(60.0, 24.0),
-                                            FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                            C::FG_GREY,
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
                                             true,
                                             true,
                                             false,
+                                            theme,
                                         ));
 
                                         ui.add(Knob::for_param(
@@ -720,19 +2267,21 @@ pub(crate) fn create_gui(
                                             setter,
                                             24.0,
                                             KnobStyle::Analog {
-                                                highlight_color: C::FG_WHITE,
+                                                highlight_color: theme.fg_bright,
                                                 line_width: 2.0,
                                             },
+                                            theme,
                                         ));
                                         ui.add(KnobText::for_param(
                                             &ch.mid_db,
                                             setter,
                                             vec2(60.0, 24.0),
-                                            FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                            C::FG_GREY,
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
                                             true,
                                             true,
                                             false,
+                                            theme,
                                         ));
                                     });
                                     ui.horizontal(|ui| {
@@ -741,19 +2290,43 @@ pub(crate) fn create_gui(
                                             setter,
                                             24.0,
                                             KnobStyle::Analog {
-                                                highlight_color: C::FG_BLUE,
+                                                highlight_color: theme.fg_blue,
                                                 line_width: 2.0,
                                             },
+                                            theme,
                                         ));
                                         ui.add(KnobText::for_param(
                                             &ch.low_precomp,
                                             setter,
                                             vec2(60.0, 24.0),
-                                            FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                            C::FG_GREY,
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+
+                                        ui.add(Knob::for_param(
+                                            &ch.low_hold,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_blue,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.low_hold,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
                                             true,
                                             true,
                                             false,
+                                            theme,
                                         ));
 
                                         ui.add(Knob::for_param(
@@ -761,19 +2334,21 @@ pub(crate) fn create_gui(
                                             setter,
                                             24.0,
                                             KnobStyle::Analog {
-                                                highlight_color: C::FG_BLUE,
+                                                highlight_color: theme.fg_blue,
                                                 line_width: 2.0,
                                             },
+                                            theme,
                                         ));
                                         ui.add(KnobText::for_param(
                                             &ch.low_decay,
                                             setter,
                                             vec2(60.0, 24.0),
-                                            FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                            C::FG_GREY,
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
                                             true,
                                             true,
                                             false,
+                                            theme,
                                         ));
 
                                         ui.add(Knob::for_param(
@@ -781,518 +2356,498 @@ pub(crate) fn create_gui(
                                             setter,
                                             24.0,
                                             KnobStyle::Analog {
-                                                highlight_color: C::FG_WHITE,
+                                                highlight_color: theme.fg_bright,
                                                 line_width: 2.0,
                                             },
+                                            theme,
                                         ));
                                         ui.add(KnobText::for_param(
                                             &ch.low_db,
                                             setter,
                                             vec2(60.0, 24.0),
-                                            FontId::new(C::TEXT_SM, C::FONT_NORMAL),
-                                            C::FG_GREY,
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
                                             true,
                                             true,
                                             false,
+                                            theme,
                                         ));
                                     });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "High",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_yellow,
+                                        );
+                                        ui.add(
+                                            ParamSlider::for_param(&ch.high_attack_curve, setter)
+                                                .with_width(90.0),
+                                        );
+                                        ui.add(
+                                            ParamSlider::for_param(&ch.high_release_curve, setter)
+                                                .with_width(90.0),
+                                        );
+                                        ui.add(Knob::for_param(
+                                            &ch.high_curvature,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_yellow,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.high_curvature,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                        ui.add(Knob::for_param(
+                                            &ch.high_morph,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_yellow,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.high_morph,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Mid",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_purple,
+                                        );
+                                        ui.add(
+                                            ParamSlider::for_param(&ch.mid_attack_curve, setter)
+                                                .with_width(90.0),
+                                        );
+                                        ui.add(
+                                            ParamSlider::for_param(&ch.mid_release_curve, setter)
+                                                .with_width(90.0),
+                                        );
+                                        ui.add(Knob::for_param(
+                                            &ch.mid_curvature,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_purple,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.mid_curvature,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                        ui.add(Knob::for_param(
+                                            &ch.mid_morph,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_purple,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.mid_morph,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Low",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_blue,
+                                        );
+                                        ui.add(
+                                            ParamSlider::for_param(&ch.low_attack_curve, setter)
+                                                .with_width(90.0),
+                                        );
+                                        ui.add(
+                                            ParamSlider::for_param(&ch.low_release_curve, setter)
+                                                .with_width(90.0),
+                                        );
+                                        ui.add(Knob::for_param(
+                                            &ch.low_curvature,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_blue,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.low_curvature,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                        ui.add(Knob::for_param(
+                                            &ch.low_morph,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_blue,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.low_morph,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Release",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(ParamSlider::for_param(&ch.release_model, setter));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Velocity",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(Knob::for_param(
+                                            &ch.velocity_depth,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_green,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.velocity_depth,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+
+                                        ui.add(Knob::for_param(
+                                            &ch.velocity_decay,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_green,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.velocity_decay,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+
+                                        ui.add(Knob::for_param(
+                                            &ch.velocity_curve,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_green,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.velocity_curve,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+
+                                        ui.add(Knob::for_param(
+                                            &ch.velocity_morph,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_green,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.velocity_morph,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Choke",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(Knob::for_param(
+                                            &ch.choke_group,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_red,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.choke_group,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+
+                                        ui.add(Knob::for_param(
+                                            &ch.choke_time,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_red,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.choke_time,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Voices",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(Knob::for_param(
+                                            &ch.polyphony,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_bright,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.polyphony,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                        ui.add(ParamSlider::for_param(&ch.retrigger, setter));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Humanize",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(Knob::for_param(
+                                            &ch.probability,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_bright,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.probability,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+
+                                        ui.add(Knob::for_param(
+                                            &ch.humanize_depth,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_bright,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.humanize_depth,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+
+                                        ui.add(Knob::for_param(
+                                            &ch.humanize_decay,
+                                            setter,
+                                            24.0,
+                                            KnobStyle::Analog {
+                                                highlight_color: theme.fg_bright,
+                                                line_width: 2.0,
+                                            },
+                                            theme,
+                                        ));
+                                        ui.add(KnobText::for_param(
+                                            &ch.humanize_decay,
+                                            setter,
+                                            vec2(60.0, 24.0),
+                                            FontId::new(theme.text_sm, C::FONT_NORMAL),
+                                            theme.fg_dim,
+                                            true,
+                                            true,
+                                            false,
+                                            theme,
+                                        ));
+                                    });
+                                    ui.horizontal(|ui| {
+                                        rt(
+                                            ui,
+                                            "Offset",
+                                            &C::FONT_NORMAL,
+                                            theme.text_sm,
+                                            theme.fg_dim,
+                                        );
+                                        ui.add(ParamSlider::for_param(&ch.offset_mode, setter));
+                                        match ch.offset_mode.value() {
+                                            OffsetMode::Time => {
+                                                ui.add(ParamSlider::for_param(
+                                                    &ch.offset_time,
+                                                    setter,
+                                                ));
+                                            }
+                                            OffsetMode::Note => {
+                                                ui.add(ParamSlider::for_param(
+                                                    &ch.offset_notes,
+                                                    setter,
+                                                ));
+                                            }
+                                        }
+                                    });
                                 }
                             });
                         });
                 });
+
+            // an edit ends when the mouse is released, so a drag can be undone all at once
+            undo_history.end_frame(&params, !ctx.input(|x| x.pointer.any_down()));
         },
-    )
+    )?;
+    Some(Box::new(UndoEditor::new(
+        editor,
+        plugin.undo_history.clone(),
+    )))
 }
-
-// pub(crate) fn create_gui(
-//     plugin: &mut Malt,
-//     _async_executor: AsyncExecutor<Malt>,
-// ) -> Option<Box<dyn Editor>> {
-//     let params = plugin.params.clone();
-//     let egui_state = plugin.params.editor_state.clone();
-//     create_egui_editor(
-//         plugin.params.editor_state.clone(),
-//         UIState::new(),
-//         |ctx, state| {
-//             // Load new fonts
-//             {
-//                 use egui::{FontData, FontDefinitions, FontFamily};
-
-//                 let mut fonts = FontDefinitions::empty();
-
-//                 // Load font data
-//                 fonts.font_data.insert(
-//                     "Inter".into(),
-//                     FontData::from_static(include_bytes!("../../fonts/Inter-Regular.ttf")),
-//                 );
-//                 fonts.font_data.insert(
-//                     "Inter Bold".into(),
-//                     FontData::from_static(include_bytes!("../../fonts/Inter-Bold.ttf")),
-//                 );
-
-//                 // Define font priority
-//                 fonts
-//                     .families
-//                     .entry(FontFamily::Proportional)
-//                     .or_insert(Default::default())
-//                     .push("Inter".into());
-//                 fonts
-//                     .families
-//                     .entry(FontFamily::Name("bold".into()))
-//                     .or_insert(Default::default())
-//                     .push("Inter Bold".into());
-
-//                 ctx.set_fonts(fonts)
-//             }
-
-//             // Override GUI styling
-//             {
-//                 use egui::FontFamily::Proportional;
-//                 use egui::FontId;
-//                 use egui::Style;
-//                 use egui::TextStyle;
-//                 use egui::Visuals;
-
-//                 let mut style = (*ctx.style()).clone();
-
-//                 // font sizes
-//                 style.text_styles = [
-//                     (TextStyle::Heading, FontId::new(16.0, Proportional)),
-//                     (TextStyle::Body, FontId::new(11.0, Proportional)),
-//                     (TextStyle::Small, FontId::new(10.0, Proportional)),
-//                     (TextStyle::Button, FontId::new(12.0, Proportional)),
-//                     // nih-plug's ParamSlider uses monospace for some reason,
-//                     // need to add this or else ParamSlider will panic
-//                     (TextStyle::Monospace, FontId::new(11.0, Proportional)),
-//                 ]
-//                 .into();
-
-//                 // make background red to help identify places with no background
-//                 style.visuals.panel_fill = Color32::RED;
-
-//                 // disable item spacing, do everything manually
-//                 style.spacing.item_spacing = Vec2::ZERO;
-
-//                 style.interaction.selectable_labels = false;
-
-//                 ctx.set_style(style);
-//             }
-
-//             // Enable loading image resources
-//             egui_extras::install_image_loaders(ctx);
-//         },
-//         move |ctx, setter, state| {
-//             ResizableWindow::new("resizable-window")
-//                 .min_size(vec2(GUI_MINIMUM_WIDTH as f32, GUI_MINIMUM_HEIGHT as f32))
-//                 .show(ctx, &egui_state, |ui| {
-//                     let header_frame = egui::Frame::none().fill(C::BG_DARK);
-//                     const HEADER_HEIGHT: f32 = 25.0;
-
-//                     // Header
-//                     egui::TopBottomPanel::top("header_panel")
-//                         .show_separator_line(false)
-//                         .exact_height(HEADER_HEIGHT)
-//                         .frame(header_frame.clone())
-//                         .show(ctx, |ui| {
-//                             ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
-//                                 // Left side
-//                                 let left_side = |ui: &mut Ui| {
-//                                     ui.add_space(12.0);
-//                                     rt(ui, "sai audio", &C::FONT_NORMAL, C::TEXT_LARGE, C::FG_GREY);
-//                                     ui.add_space(10.0);
-//                                     rt(ui, "Malt", &C::FONT_BOLD, C::TEXT_LARGE, C::FG_WHITE);
-//                                 };
-//                                 // Right side
-//                                 // Widgets must be inserted in reverse order here
-//                                 let right_side = |ui: &mut Ui| {
-//                                     let res = simple_block_button(
-//                                         ui,
-//                                         state.help_enabled,
-//                                         ButtonContent::Text(
-//                                             "?",
-//                                             FontId::new(C::TEXT_BASE, C::FONT_BOLD.clone()),
-//                                         ),
-//                                         vec2(22.0, 25.0),
-//                                         C::FG_GREEN,
-//                                         C::FG_GREY,
-//                                         C::BG_DARK,
-//                                     );
-//                                     if res.clicked() {
-//                                         state.help_enabled = !state.help_enabled;
-//                                     }
-//                                 };
-
-//                                 ui.with_layout(
-//                                     egui::Layout::left_to_right(egui::Align::Center),
-//                                     left_side,
-//                                 );
-//                                 ui.with_layout(
-//                                     egui::Layout::right_to_left(egui::Align::Center),
-//                                     right_side,
-//                                 );
-//                             })
-//                         });
-
-//                     // Footer
-//                     egui::TopBottomPanel::bottom("footer_panel")
-//                         .show_separator_line(false)
-//                         .exact_height(HEADER_HEIGHT)
-//                         .frame(header_frame.clone())
-//                         .show(ctx, |ui| {
-//                             ui.columns(5, |cols| {
-//                                 let available_size = cols[0].available_size();
-
-//                                 cols[0].add_sized(available_size, |ui: &mut Ui| -> Response {
-//                                     custom_block_button(
-//                                         ui,
-//                                         vec2(22.0, 22.0),
-//                                         Color32::TRANSPARENT,
-//                                         C::FG_WHITE.gamma_multiply(0.1),
-//                                         Color32::TRANSPARENT,
-//                                         |ui, res, painter, state| {
-//                                             draw_texts(
-//                                                 &painter,
-//                                                 &ui.style().clone(),
-//                                                 available_size.x,
-//                                                 res.rect.center(),
-//                                                 [
-//                                                     rt_obj(
-//                                                         ui,
-//                                                         "Overlap: ",
-//                                                         &C::FONT_NORMAL,
-//                                                         C::TEXT_SM,
-//                                                         C::FG_GREY,
-//                                                     ),
-//                                                     rt_obj(
-//                                                         ui,
-//                                                         "Replace",
-//                                                         &C::FONT_NORMAL,
-//                                                         C::TEXT_SM,
-//                                                         C::FG_WHITE,
-//                                                     ),
-//                                                 ],
-//                                             );
-//                                         },
-//                                     )
-//                                 });
-//                                 cols[1].add_sized(available_size, |ui: &mut Ui| -> Response {
-//                                     custom_block_button(
-//                                         ui,
-//                                         vec2(22.0, 22.0),
-//                                         Color32::TRANSPARENT,
-//                                         C::FG_WHITE.gamma_multiply(0.1),
-//                                         Color32::TRANSPARENT,
-//                                         |ui, res, painter, state| {
-//                                             draw_texts(
-//                                                 &painter,
-//                                                 &ui.style().clone(),
-//                                                 available_size.x,
-//                                                 res.rect.center(),
-//                                                 [
-//                                                     rt_obj(
-//                                                         ui,
-//                                                         "Lookahead: ",
-//                                                         &C::FONT_NORMAL,
-//                                                         C::TEXT_SM,
-//                                                         C::FG_GREY,
-//                                                     ),
-//                                                     rt_obj(
-//                                                         ui,
-//                                                         "10ms",
-//                                                         &C::FONT_NORMAL,
-//                                                         C::TEXT_SM,
-//                                                         C::FG_WHITE,
-//                                                     ),
-//                                                 ],
-//                                             );
-//                                         },
-//                                     )
-//                                 });
-//                                 cols[2].add_sized(available_size, |ui: &mut Ui| -> Response {
-//                                     custom_block_button(
-//                                         ui,
-//                                         vec2(22.0, 22.0),
-//                                         Color32::TRANSPARENT,
-//                                         C::FG_WHITE.gamma_multiply(0.1),
-//                                         Color32::TRANSPARENT,
-//                                         |ui, res, painter, state| {
-//                                             draw_texts(
-//                                                 &painter,
-//                                                 &ui.style().clone(),
-//                                                 available_size.x,
-//                                                 res.rect.center(),
-//                                                 [
-//                                                     rt_obj(
-//                                                         ui,
-//                                                         "Smooth: ",
-//                                                         &C::FONT_NORMAL,
-//                                                         C::TEXT_SM,
-//                                                         C::FG_GREY,
-//                                                     ),
-//                                                     rt_obj(
-//                                                         ui,
-//                                                         "On",
-//                                                         &C::FONT_NORMAL,
-//                                                         C::TEXT_SM,
-//                                                         C::FG_WHITE,
-//                                                     ),
-//                                                 ],
-//                                             );
-//                                         },
-//                                     )
-//                                 });
-//                                 cols[3].add_sized(available_size, |ui: &mut Ui| -> Response {
-//                                     custom_block_button(
-//                                         ui,
-//                                         vec2(22.0, 22.0),
-//                                         Color32::TRANSPARENT,
-//                                         C::FG_WHITE.gamma_multiply(0.1),
-//                                         Color32::TRANSPARENT,
-//                                         |ui, res, painter, state| {
-//                                             draw_texts(
-//                                                 &painter,
-//                                                 &ui.style().clone(),
-//                                                 available_size.x,
-//                                                 res.rect.center(),
-//                                                 [rt_obj(
-//                                                     ui,
-//                                                     "Bypass",
-//                                                     &C::FONT_NORMAL,
-//                                                     C::TEXT_SM,
-//                                                     C::FG_WHITE,
-//                                                 )],
-//                                             );
-//                                         },
-//                                     )
-//                                 });
-//                                 cols[4].add_sized(available_size, |ui: &mut Ui| -> Response {
-//                                     custom_block_button(
-//                                         ui,
-//                                         vec2(22.0, 22.0),
-//                                         Color32::TRANSPARENT,
-//                                         C::FG_WHITE.gamma_multiply(0.1),
-//                                         Color32::TRANSPARENT,
-//                                         |ui, res, painter, state| {
-//                                             draw_texts(
-//                                                 &painter,
-//                                                 &ui.style().clone(),
-//                                                 available_size.x,
-//                                                 res.rect.center(),
-//                                                 [
-//                                                     rt_obj(
-//                                                         ui,
-//                                                         "Mix: ",
-//                                                         &C::FONT_NORMAL,
-//                                                         C::TEXT_SM,
-//                                                         C::FG_GREY,
-//                                                     ),
-//                                                     rt_obj(
-//                                                         ui,
-//                                                         "100%",
-//                                                         &C::FONT_NORMAL,
-//                                                         C::TEXT_SM,
-//                                                         C::FG_WHITE,
-//                                                     ),
-//                                                 ],
-//                                             );
-//                                         },
-//                                     )
-//                                 });
-//                             })
-//                         });
-
-//                     const BAND_WIDGET_WIDTH: f32 = 248.0;
-//                     const BAND_WIDGET_HEIGHT: f32 = 113.0;
-
-//                     // right-side controls (fixed width, variable height)
-//                     egui::SidePanel::right("controls_panel")
-//                         .exact_width(BAND_WIDGET_WIDTH)
-//                         .show_separator_line(false)
-//                         .resizable(false)
-//                         .frame(egui::Frame::none().fill(C::BG_LIGHT))
-//                         .show(ctx, |ui| {
-//                             // TODO: Handle 2-band or 1-band scenario
-
-//                             let rect = ui.max_rect();
-
-//                             // subtract 2 pixels (1px per divider line)
-//                             let band_height = (rect.height() - 2.0) / 3.0;
-
-//                             ui.allocate_ui_with_layout(
-//                                 vec2(BAND_WIDGET_WIDTH, band_height),
-//                                 Layout::left_to_right(Align::Center),
-//                                 |ui| {
-//                                     panel_band(
-//                                         ui,
-//                                         "HIGH",
-//                                         &params.channels[0].high_precomp,
-//                                         &params.channels[0].high_decay,
-//                                         &params.channels[0].high_db,
-//                                     )
-//                                 },
-//                             );
-
-//                             ui.label(format!("band_height: {:?}", band_height));
-
-//                             let knob = Knob::for_param(
-//                                 &params.channels[0].low_db,
-//                                 setter,
-//                                 34.0,
-//                                 KnobStyle::Analog {
-//                                     highlight_color: C::FG_YELLOW,
-//                                     line_width: 2.0,
-//                                 },
-//                             );
-//                             ui.add(knob);
-
-//                             let knob = Knob::for_param(
-//                                 &params.channels[0].low_decay,
-//                                 setter,
-//                                 44.0,
-//                                 KnobStyle::Analog {
-//                                     highlight_color: C::FG_PURPLE,
-//                                     line_width: 2.0,
-//                                 },
-//                             );
-//                             ui.add(knob);
-
-//                             let knob = Knob::for_param(
-//                                 &params.channels[0].low_decay,
-//                                 setter,
-//                                 15.0,
-//                                 KnobStyle::Donut {
-//                                     line_width: 4.0,
-//                                     text: Some(KnobDonutText {
-//                                         spacing: 0.0,
-//                                         width: 70.0,
-//                                         font_id: FontId::new(C::TEXT_XS, C::FONT_NORMAL),
-//                                         color: C::FG_GREY,
-//                                     }),
-//                                 },
-//                             );
-//                             ui.add(knob);
-
-//                             let knob = KnobText::for_param(
-//                                 &params.low_crossover,
-//                                 setter,
-//                                 vec2(100.0, 30.0),
-//                                 FontId::new(C::TEXT_XS, C::FONT_NORMAL),
-//                                 C::FG_GREY,
-//                                 true,
-//                                 true,
-//                                 false,
-//                             );
-//                             ui.add(knob);
-
-//                             ui.add(BlockButton::new(
-//                                 ButtonContent::Text(
-//                                     "M",
-//                                     FontId::new(12.0, FontFamily::Name("bold".into())),
-//                                 ),
-//                                 vec2(22.0, 22.0),
-//                                 Color32::WHITE,
-//                                 Color32::WHITE,
-//                                 Color32::from_white_alpha(128),
-//                                 Color32::TRANSPARENT,
-//                                 Color32::from_white_alpha(26),
-//                                 Color32::from_white_alpha(26),
-//                             ));
-
-//                             ui.add(BlockButton::new(
-//                                 ButtonContent::Text(
-//                                     "S",
-//                                     FontId::new(12.0, FontFamily::Name("bold".into())),
-//                                 ),
-//                                 vec2(22.0, 22.0),
-//                                 Color32::WHITE,
-//                                 Color32::WHITE,
-//                                 Color32::from_white_alpha(128),
-//                                 Color32::TRANSPARENT,
-//                                 Color32::from_white_alpha(26),
-//                                 Color32::from_white_alpha(26),
-//                             ));
-
-//                             if ui.button("Hello").clicked() {
-//                                 nih_log!("Hello");
-//                             }
-//                         });
-
-//                     // left-side analyser (variable size)
-//                     egui::CentralPanel::default().show(ctx, |ui| {
-//                         // TODO: Add a proper custom widget instead of reusing a progress bar
-//                         // let peak_meter =
-//                         //     util::gain_to_db(peak_meter.load(std::sync::atomic::Ordering::Relaxed));
-//                         // let peak_meter_text = if peak_meter > util::MINUS_INFINITY_DB {
-//                         //     format!("{peak_meter:.1} dBFS")
-//                         // } else {
-//                         //     String::from("-inf dBFS")
-//                         // };
-
-//                         // let peak_meter_normalized = (peak_meter + 60.0) / 60.0;
-//                         // ui.allocate_space(egui::Vec2::splat(2.0));
-//                         // ui.add(
-//                         //     egui::widgets::ProgressBar::new(peak_meter_normalized).text(peak_meter_text),
-//                         // );
-
-//                         // This is a fancy widget that can get all the information it needs to properly
-//                         // display and modify the parameter from the parametr itself
-//                         // It's not yet fully implemented, as the text is missing.
-//                         ui.label("gain_reduction");
-//                         ui.add(widgets::ParamSlider::for_param(
-//                             &params.channels[0].low_db,
-//                             setter,
-//                         ));
-//                         ui.label("precomp");
-//                         ui.add(widgets::ParamSlider::for_param(
-//                             &params.channels[0].low_precomp,
-//                             setter,
-//                         ));
-//                         ui.label("release");
-//                         ui.add(widgets::ParamSlider::for_param(
-//                             &params.channels[0].low_decay,
-//                             setter,
-//                         ));
-//                         // ui.label("low_crossover");
-//                         // ui.add(widgets::ParamSlider::for_param(
-//                         //     &params.low_crossover,
-//                         //     setter,
-//                         // ));
-//                         // ui.label("high_crossover");
-//                         // ui.add(widgets::ParamSlider::for_param(
-//                         //     &params.high_crossover,
-//                         //     setter,
-//                         // ));
-//                         // ui.label("low_gain");
-//                         // ui.add(widgets::ParamSlider::for_param(&params.low_gain, setter));
-//                         // ui.label("mid_gain");
-//                         // ui.add(widgets::ParamSlider::for_param(&params.mid_gain, setter));
-//                         // ui.label("high_gain");
-//                         // ui.add(widgets::ParamSlider::for_param(&params.high_gain, setter));
-
-//                         // ui.label(
-//                         //     "Also gain, but with a lame widget. Can't even render the value correctly!",
-//                         // );
-//                         // // This is a simple naieve version of a parameter slider that's not aware of how
-//                         // // the parameters work
-//                         // ui.add(
-//                         //     egui::widgets::Slider::from_get_set(-30.0..=30.0, |new_value| {
-//                         //         match new_value {
-//                         //             Some(new_value_db) => {
-//                         //                 let new_value = util::gain_to_db(new_value_db as f32);
-
-//                         //                 setter.begin_set_parameter(&params.gain);
-//                         //                 setter.set_parameter(&params.gain, new_value);
-//                         //                 setter.end_set_parameter(&params.gain);
-
-//                         //                 new_value_db
-//                         //             }
-//                         //             None => util::gain_to_db(params.gain.value()) as f64,
-//                         //         }
-//                         //     })
-//                         //     .suffix(" dB"),
-//                         // );
-//                     });
-//                 });
-//         },
-//     )
-// }
@@ -0,0 +1,88 @@
+use super::palette::Theme;
+use nih_plug_egui::egui::{pos2, Color32, Pos2, Response, Sense, Shape, Stroke, Ui, Vec2, Widget};
+
+/// Plot of the gain reduction of each band over the course of a single note. Reductions grow
+/// downwards, boosts grow upwards.
+pub(crate) struct EnvelopePreview<'a> {
+    /// Gain reduction in dB of the low, mid and high bands, one value per sample
+    curves: &'a [Vec<f32>; 3],
+    /// The sample where the note plays, after the lookahead
+    note_sample: usize,
+    size: Vec2,
+    colors: [Color32; 3],
+    theme: &'a Theme,
+}
+
+impl<'a> EnvelopePreview<'a> {
+    pub(crate) fn new(
+        curves: &'a [Vec<f32>; 3],
+        note_sample: usize,
+        size: Vec2,
+        colors: [Color32; 3],
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            curves,
+            note_sample,
+            size,
+            colors,
+            theme,
+        }
+    }
+}
+
+impl<'a> Widget for EnvelopePreview<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let response = ui.allocate_response(self.size, Sense::hover());
+        let rect = response.rect;
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, self.theme.bg_deep);
+
+        // fit every curve and the note marker in the plot
+        let len = self
+            .curves
+            .iter()
+            .map(|curve| curve.len())
+            .max()
+            .unwrap_or(0)
+            .max(self.note_sample + 1)
+            .max(2);
+        let values = self.curves.iter().flatten();
+        let min_db = values.clone().fold(0.0f32, |a, b| a.min(*b));
+        let max_db = values.fold(0.0f32, |a, b| a.max(*b));
+        let range_db = (max_db - min_db).max(1.0);
+
+        let to_screen = |i: usize, db: f32| {
+            pos2(
+                rect.left() + rect.width() * i as f32 / (len - 1) as f32,
+                rect.top() + rect.height() * (db - min_db) / range_db,
+            )
+        };
+
+        // the level with no gain change
+        let zero = to_screen(0, 0.0);
+        painter.hline(
+            rect.left()..=rect.right(),
+            zero.y,
+            Stroke::new(1.0, self.theme.fg_faint),
+        );
+
+        for (curve, color) in self.curves.iter().zip(self.colors) {
+            let points: Vec<Pos2> = curve
+                .iter()
+                .enumerate()
+                .map(|(i, db)| to_screen(i, *db))
+                .collect();
+            painter.add(Shape::line(points, Stroke::new(1.5, color)));
+        }
+
+        let note_x = to_screen(self.note_sample, 0.0).x;
+        painter.vline(
+            note_x,
+            rect.top()..=rect.bottom(),
+            Stroke::new(1.0, self.theme.fg_dim),
+        );
+
+        response
+    }
+}
@@ -0,0 +1,94 @@
+//! Small radix-2 FFT for the spectrum analyzer. It runs on the editor thread, so it is free to
+//! allocate.
+
+use std::f32::consts::PI;
+
+/// In-place iterative radix-2 FFT. The length must be a power of 2.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two());
+    debug_assert_eq!(n, im.len());
+
+    // bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Magnitude spectrum of the given samples in dB, with a Hann window applied. A full-scale sine
+/// reads 0 dB. Returns `samples.len() / 2` bins, where bin `i` is at `i * sample_rate / len`.
+pub(crate) fn spectrum_db(samples: &[f32]) -> Vec<f32> {
+    let n = samples.len();
+    let mut re: Vec<f32> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, x)| x * (0.5 - 0.5 * (2.0 * PI * i as f32 / n as f32).cos()))
+        .collect();
+    let mut im = vec![0.0; n];
+    fft(&mut re, &mut im);
+
+    // the Hann window halves the amplitude, and a real sine is split across 2 bins
+    let scale = 4.0 / n as f32;
+    re.iter()
+        .zip(im.iter())
+        .take(n / 2)
+        .map(|(re, im)| nih_plug::util::gain_to_db((re * re + im * im).sqrt() * scale))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod spectrum_db {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // a full-scale sine in the middle of bin 64 peaks there at 0dB
+            let n = 1024;
+            let samples: Vec<f32> = (0..n)
+                .map(|i| (2.0 * PI * 64.0 * i as f32 / n as f32).sin())
+                .collect();
+            let spectrum = spectrum_db(&samples);
+            assert_eq!(spectrum.len(), n / 2);
+
+            let peak = spectrum
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .unwrap();
+            assert_eq!(peak.0, 64);
+            assert!(peak.1.abs() < 0.1, "{}", peak.1);
+        }
+    }
+}
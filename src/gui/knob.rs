@@ -1,4 +1,4 @@
-use super::palette as C;
+use super::palette::Theme;
 use nih_plug::prelude::{Param, ParamSetter};
 use nih_plug_egui::egui::{
     epaint::{CircleShape, PathShape, PathStroke},
@@ -37,6 +37,7 @@ pub(crate) struct Knob<'a, P: Param> {
     style: KnobStyle,
     param: &'a P,
     param_setter: &'a ParamSetter<'a>,
+    theme: &'a Theme,
 }
 
 impl<'a, P: Param> Knob<'a, P> {
@@ -45,21 +46,19 @@ impl<'a, P: Param> Knob<'a, P> {
     const ARC_START: f32 = -3.0 / 8.0 * TAU;
     const ARC_END: f32 = -9.0 / 8.0 * TAU;
 
-    const LINE_COLOR: Color32 = C::FG_WHITE;
-    const BG_COLOR: Color32 = C::PANEL_KNOB_RIM_BG;
-    const KNOB_COLOR: Color32 = C::BG_NORMAL;
-
     pub(crate) fn for_param(
         param: &'a P,
         param_setter: &'a ParamSetter,
         size: f32,
         style: KnobStyle,
+        theme: &'a Theme,
     ) -> Self {
         Knob {
             size,
             style,
             param,
             param_setter,
+            theme,
         }
     }
 
@@ -172,7 +171,7 @@ impl<'a, P: Param> Widget for Knob<'a, P> {
                         closed: false,
                         fill: Default::default(),
                         // improve rendering by making outline overlap with knob center a bit
-                        stroke: PathStroke::new(line_width + 2.0, Self::BG_COLOR),
+                        stroke: PathStroke::new(line_width + 2.0, self.theme.knob_rim),
                     });
                     painter.add(shape);
                 }
@@ -203,7 +202,7 @@ impl<'a, P: Param> Widget for Knob<'a, P> {
                         center,
                         radius: center_radius,
                         stroke: Stroke::NONE,
-                        fill: Self::KNOB_COLOR,
+                        fill: self.theme.bg_normal,
                     });
                     painter.add(shape);
                 }
@@ -233,7 +232,7 @@ impl<'a, P: Param> Widget for Knob<'a, P> {
                         points: vec![start_point, end_point],
                         closed: false,
                         fill: Default::default(),
-                        stroke: PathStroke::new(*line_width, Self::LINE_COLOR),
+                        stroke: PathStroke::new(*line_width, self.theme.fg_bright),
                     });
                     painter.add(line_shape);
 
@@ -243,14 +242,14 @@ impl<'a, P: Param> Widget for Knob<'a, P> {
                         let end_ball = Shape::Circle(CircleShape {
                             center: end_point,
                             radius: ball_radius,
-                            fill: Self::LINE_COLOR,
+                            fill: self.theme.fg_bright,
                             stroke: Stroke::NONE,
                         });
                         painter.add(end_ball);
                         let center_ball = Shape::Circle(CircleShape {
                             center: start_point,
                             radius: ball_radius,
-                            fill: Self::LINE_COLOR,
+                            fill: self.theme.fg_bright,
                             stroke: Stroke::NONE,
                         });
                         painter.add(center_ball);
@@ -280,7 +279,7 @@ impl<'a, P: Param> Widget for Knob<'a, P> {
                         ),
                         closed: false,
                         fill: Default::default(),
-                        stroke: PathStroke::new(*line_width, Self::BG_COLOR),
+                        stroke: PathStroke::new(*line_width, self.theme.knob_rim),
                     });
                     painter.add(shape);
                 }
@@ -299,7 +298,7 @@ impl<'a, P: Param> Widget for Knob<'a, P> {
                         closed: false,
                         fill: Default::default(),
                         // improve rendering by making outline overlap with knob center a bit
-                        stroke: PathStroke::new(*line_width, Self::LINE_COLOR),
+                        stroke: PathStroke::new(*line_width, self.theme.fg_bright),
                     });
                     painter.add(shape);
                 }
@@ -1,4 +1,4 @@
-use super::palette as C;
+use super::palette::Theme;
 use nih_plug::{
     nih_debug_assert_eq,
     prelude::{Param, ParamSetter},
@@ -25,6 +25,7 @@ pub(crate) struct KnobText<'a, P: Param> {
     allow_keyboard: bool,
     /// Whether or not to snap to nearest value when using keyboard input
     keyboard_snap: bool,
+    theme: &'a Theme,
 }
 
 impl<'a, P: Param> KnobText<'a, P> {
@@ -37,6 +38,7 @@ impl<'a, P: Param> KnobText<'a, P> {
         allow_drag: bool,
         allow_keyboard: bool,
         keyboard_snap: bool,
+        theme: &'a Theme,
     ) -> Self {
         KnobText {
             param,
@@ -47,6 +49,7 @@ impl<'a, P: Param> KnobText<'a, P> {
             allow_drag,
             allow_keyboard,
             keyboard_snap,
+            theme,
         }
     }
 
@@ -185,7 +188,7 @@ impl<'a, P: Param> KnobText<'a, P> {
                 |ui| {
                     TextEdit::singleline(&mut *text_buf)
                         .font(self.font_id)
-                        .text_color(C::FG_WHITE)
+                        .text_color(self.theme.fg_bright)
                         .desired_width(self.size.x)
                         .id(current_id)
                         .vertical_align(Align::Center)
@@ -0,0 +1,209 @@
+//! A small flex layout for the editor.
+//!
+//! Every rect is computed up front from the size of the window, in a single pass, before any
+//! widget is drawn. Nothing depends on sizes measured in a previous frame, which is what makes
+//! egui's own layouts (and egui_flex) flicker while the window is being resized.
+
+use nih_plug_egui::egui::{pos2, Rect};
+
+/// Size of an item along the axis it's laid out on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Size {
+    /// Fixed size in points
+    Fixed(f32),
+    /// Share of the space left over by the fixed items, relative to the weights of the other
+    /// flexible items. The share is kept between `min` and `max`.
+    Flex { weight: f32, min: f32, max: f32 },
+}
+
+impl Size {
+    /// Flexible item without any limits
+    pub(crate) const fn flex(weight: f32) -> Self {
+        Self::Flex {
+            weight,
+            min: 0.0,
+            max: f32::INFINITY,
+        }
+    }
+
+    /// Flexible item that is kept between `min` and `max`
+    pub(crate) const fn clamped(weight: f32, min: f32, max: f32) -> Self {
+        Self::Flex { weight, min, max }
+    }
+}
+
+/// Resolve the length of each item along a line of the given length. If the items don't fit,
+/// they overflow past the end of the line.
+fn resolve<const N: usize>(length: f32, sizes: [Size; N], gap: f32) -> [f32; N] {
+    let mut lengths = [0.0; N];
+    // whether an item's length is final
+    let mut frozen = [false; N];
+
+    let mut remaining = length - gap * N.saturating_sub(1) as f32;
+    for (i, size) in sizes.iter().enumerate() {
+        if let Size::Fixed(x) = size {
+            lengths[i] = *x;
+            frozen[i] = true;
+            remaining -= x;
+        }
+    }
+
+    // distribute the remaining space by weight. whenever an item hits its limit, it's frozen
+    // at that limit and the rest is distributed again among the other items
+    loop {
+        let total_weight: f32 = sizes
+            .iter()
+            .zip(frozen)
+            .filter(|(_, frozen)| !frozen)
+            .map(|(size, _)| match size {
+                Size::Flex { weight, .. } => *weight,
+                Size::Fixed(_) => 0.0,
+            })
+            .sum();
+
+        let mut clamped = false;
+        for (i, size) in sizes.iter().enumerate() {
+            let Size::Flex { weight, min, max } = size else {
+                continue;
+            };
+            if frozen[i] {
+                continue;
+            }
+            let share = if total_weight > 0.0 {
+                remaining.max(0.0) * weight / total_weight
+            } else {
+                0.0
+            };
+            lengths[i] = share.clamp(*min, max.max(*min));
+            if lengths[i] != share {
+                frozen[i] = true;
+                remaining -= lengths[i];
+                clamped = true;
+            }
+        }
+
+        if !clamped {
+            return lengths;
+        }
+    }
+}
+
+/// Offsets of the start and end of each item. Edges are rounded to whole points, so neighbouring
+/// items always share an edge and don't jitter by a pixel while resizing.
+fn edges<const N: usize>(start: f32, length: f32, sizes: [Size; N], gap: f32) -> [(f32, f32); N] {
+    let lengths = resolve(length, sizes, gap);
+    let mut position = start;
+    lengths.map(|length| {
+        let edge = (position.round(), (position + length).round());
+        position += length + gap;
+        edge
+    })
+}
+
+/// Split a rect into columns, from left to right
+pub(crate) fn columns<const N: usize>(rect: Rect, sizes: [Size; N], gap: f32) -> [Rect; N] {
+    edges(rect.left(), rect.width(), sizes, gap)
+        .map(|(left, right)| Rect::from_min_max(pos2(left, rect.top()), pos2(right, rect.bottom())))
+}
+
+/// Split a rect into rows, from top to bottom
+pub(crate) fn rows<const N: usize>(rect: Rect, sizes: [Size; N], gap: f32) -> [Rect; N] {
+    edges(rect.top(), rect.height(), sizes, gap)
+        .map(|(top, bottom)| Rect::from_min_max(pos2(rect.left(), top), pos2(rect.right(), bottom)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nih_plug_egui::egui::vec2;
+
+    mod columns {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // fixed items keep their size, flexible items share the rest by weight
+            let rect = Rect::from_min_size(pos2(10.0, 0.0), vec2(310.0, 50.0));
+            let [a, b, c] = columns(
+                rect,
+                [Size::Fixed(10.0), Size::flex(1.0), Size::flex(2.0)],
+                0.0,
+            );
+            assert_eq!((a.left(), a.right()), (10.0, 20.0));
+            assert_eq!((b.left(), b.right()), (20.0, 120.0));
+            assert_eq!((c.left(), c.right()), (120.0, 320.0));
+            assert_eq!(c.height(), 50.0);
+        }
+
+        #[test]
+        fn test_02() {
+            // gaps are left between items, but not around them
+            let rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(100.0, 10.0));
+            let [a, b] = columns(rect, [Size::flex(1.0), Size::flex(1.0)], 10.0);
+            assert_eq!((a.left(), a.right()), (0.0, 45.0));
+            assert_eq!((b.left(), b.right()), (55.0, 100.0));
+        }
+
+        #[test]
+        fn test_03() {
+            // clamped items give their excess to the other items
+            let rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(300.0, 10.0));
+            let [a, b] = columns(rect, [Size::clamped(1.0, 0.0, 50.0), Size::flex(1.0)], 0.0);
+            assert_eq!(a.width(), 50.0);
+            assert_eq!(b.width(), 250.0);
+
+            let [a, b] = columns(
+                rect,
+                [Size::clamped(1.0, 200.0, 250.0), Size::flex(1.0)],
+                0.0,
+            );
+            assert_eq!(a.width(), 200.0);
+            assert_eq!(b.width(), 100.0);
+        }
+
+        #[test]
+        fn test_04() {
+            // fractional sizes are rounded without leaving holes between items
+            let rect = Rect::from_min_size(pos2(0.5, 0.0), vec2(100.0, 10.0));
+            let parts = columns(rect, [Size::flex(1.0); 3], 0.0);
+            for pair in parts.windows(2) {
+                assert_eq!(pair[0].right(), pair[1].left());
+            }
+            for part in parts {
+                assert_eq!(part.left(), part.left().round());
+                assert!((part.width() - 100.0 / 3.0).abs() <= 1.0);
+            }
+        }
+
+        #[test]
+        fn test_05() {
+            // items that don't fit overflow, flexible items shrink to their minimum
+            let rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(50.0, 10.0));
+            let [a, b] = columns(
+                rect,
+                [Size::Fixed(40.0), Size::clamped(1.0, 20.0, 100.0)],
+                0.0,
+            );
+            assert_eq!((a.left(), a.right()), (0.0, 40.0));
+            assert_eq!((b.left(), b.right()), (40.0, 60.0));
+        }
+    }
+
+    mod rows {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            let rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(100.0, 200.0));
+            let [header, body, footer] = rows(
+                rect,
+                [Size::Fixed(25.0), Size::flex(1.0), Size::Fixed(25.0)],
+                0.0,
+            );
+            assert_eq!((header.top(), header.bottom()), (0.0, 25.0));
+            assert_eq!((body.top(), body.bottom()), (25.0, 175.0));
+            assert_eq!((footer.top(), footer.bottom()), (175.0, 200.0));
+            assert_eq!(body.width(), 100.0);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use super::palette::Theme;
+use nih_plug::util::gain_to_db;
+use nih_plug_egui::egui::{
+    pos2, vec2, Color32, Pos2, Rect, Response, Sense, Shape, Stroke, Ui, Vec2, Widget,
+};
+use std::collections::VecDeque;
+
+/// Lowest level shown by the peak meters, in dBFS
+const PEAK_FLOOR_DB: f32 = -60.0;
+
+/// Horizontal gain reduction meter. Reductions grow to the right of the zero line, boosts grow
+/// to the left.
+pub(crate) struct GainReductionMeter<'a> {
+    /// Gain reduction in dB, negative values are boosts
+    db: f32,
+    /// Largest boost shown, in dB
+    max_boost_db: f32,
+    /// Largest reduction shown, in dB
+    max_reduction_db: f32,
+    size: Vec2,
+    color: Color32,
+    theme: &'a Theme,
+}
+
+impl<'a> GainReductionMeter<'a> {
+    pub(crate) fn new(
+        db: f32,
+        max_boost_db: f32,
+        max_reduction_db: f32,
+        size: Vec2,
+        color: Color32,
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            db,
+            max_boost_db,
+            max_reduction_db,
+            size,
+            color,
+            theme,
+        }
+    }
+}
+
+impl<'a> Widget for GainReductionMeter<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let response = ui.allocate_response(self.size, Sense::hover());
+        let rect = response.rect;
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, self.theme.bg_deep);
+
+        let total_db = self.max_boost_db + self.max_reduction_db;
+        let db_to_x = |db: f32| {
+            let db = db.clamp(-self.max_boost_db, self.max_reduction_db);
+            rect.left() + rect.width() * (db + self.max_boost_db) / total_db
+        };
+
+        let zero_x = db_to_x(0.0);
+        let value_x = db_to_x(self.db);
+        let bar = Rect::from_x_y_ranges(
+            zero_x.min(value_x)..=zero_x.max(value_x),
+            rect.top()..=rect.bottom(),
+        );
+        painter.rect_filled(bar, 0.0, self.color);
+        painter.vline(
+            zero_x,
+            rect.top()..=rect.bottom(),
+            Stroke::new(1.0, self.theme.fg_faint),
+        );
+
+        response
+    }
+}
+
+/// Horizontal level meter for an absolute peak value
+pub(crate) struct PeakMeter<'a> {
+    /// Linear gain
+    peak: f32,
+    size: Vec2,
+    color: Color32,
+    theme: &'a Theme,
+}
+
+impl<'a> PeakMeter<'a> {
+    pub(crate) fn new(peak: f32, size: Vec2, color: Color32, theme: &'a Theme) -> Self {
+        Self {
+            peak,
+            size,
+            color,
+            theme,
+        }
+    }
+}
+
+impl<'a> Widget for PeakMeter<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let response = ui.allocate_response(self.size, Sense::hover());
+        let rect = response.rect;
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, self.theme.bg_deep);
+
+        let db = gain_to_db(self.peak).clamp(PEAK_FLOOR_DB, 0.0);
+        let width = rect.width() * (db - PEAK_FLOOR_DB) / -PEAK_FLOOR_DB;
+        let bar = Rect::from_min_size(rect.min, vec2(width, rect.height()));
+
+        // clipping turns the meter red
+        let color = if self.peak >= 1.0 {
+            self.theme.fg_red
+        } else {
+            self.color
+        };
+        painter.rect_filled(bar, 0.0, color);
+
+        response
+    }
+}
+
+/// Scrolling graph of the gain reduction of each band, the newest values are on the right
+pub(crate) struct GainReductionHistory<'a> {
+    /// Gain reduction of the low, mid and high bands in dB
+    history: &'a VecDeque<[f32; 3]>,
+    /// Number of values that fit in the graph
+    capacity: usize,
+    /// Largest reduction shown, in dB
+    max_reduction_db: f32,
+    size: Vec2,
+    colors: [Color32; 3],
+    theme: &'a Theme,
+}
+
+impl<'a> GainReductionHistory<'a> {
+    pub(crate) fn new(
+        history: &'a VecDeque<[f32; 3]>,
+        capacity: usize,
+        max_reduction_db: f32,
+        size: Vec2,
+        colors: [Color32; 3],
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            history,
+            capacity,
+            max_reduction_db,
+            size,
+            colors,
+            theme,
+        }
+    }
+}
+
+impl<'a> Widget for GainReductionHistory<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let response = ui.allocate_response(self.size, Sense::hover());
+        let rect = response.rect;
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, self.theme.bg_deep);
+
+        if self.capacity < 2 {
+            return response;
+        }
+
+        // reductions are drawn downwards from the top, boosts are clipped
+        let step = rect.width() / (self.capacity - 1) as f32;
+        let start_x = rect.right() - (self.history.len() as f32 - 1.0) * step;
+        for (band, color) in self.colors.into_iter().enumerate() {
+            let points: Vec<Pos2> = self
+                .history
+                .iter()
+                .enumerate()
+                .map(|(i, values)| {
+                    let db = values[band].clamp(0.0, self.max_reduction_db);
+                    pos2(
+                        start_x + i as f32 * step,
+                        rect.top() + rect.height() * db / self.max_reduction_db,
+                    )
+                })
+                .collect();
+            painter.add(Shape::line(points, Stroke::new(1.0, color)));
+        }
+
+        response
+    }
+}
@@ -1,7 +1,22 @@
 mod button;
 mod editor;
+mod envpreview;
+mod fft;
 mod knob;
 mod knobtext;
+mod layout;
+mod meters;
+mod overview;
 mod palette;
+mod paramlabel;
+mod patterneditor;
+mod response;
+mod scope;
+mod spectrum;
+mod stepgrid;
+mod undo;
+mod window;
 
 pub(crate) use editor::{create_gui, GUI_DEFAULT_HEIGHT, GUI_DEFAULT_WIDTH};
+pub(crate) use palette::ThemeName;
+pub(crate) use undo::UndoHistory;
@@ -0,0 +1,231 @@
+use super::palette::{self as C, Theme};
+use crate::ChannelParams;
+use nih_plug::prelude::{FloatParam, Param, ParamSetter};
+use nih_plug_egui::egui::{
+    pos2, vec2, Align2, Color32, FontId, Rect, Response, Sense, Stroke, Ui, Widget,
+};
+
+/// Width of the channel number column
+const LABEL_WIDTH: f32 = 24.0;
+/// Height of the band names above the grid
+const HEADER_HEIGHT: f32 = 14.0;
+/// Space between two bars, and between two bands
+const BAR_GAP: f32 = 2.0;
+const BAND_GAP: f32 = 8.0;
+
+/// Grid of every channel, with the depth, decay and precomp of each band drawn as mini-bars.
+///
+/// Clicking a row selects that channel. Dragging a bar sets its parameter, ctrl+clicking resets
+/// it. Rows light up according to `activity`, so you can see which channels are being triggered.
+pub(crate) struct ChannelOverview<'a> {
+    channels: &'a [ChannelParams],
+    /// The channel shown in the editor below the overview
+    selected: &'a mut usize,
+    /// How recently each channel was triggered, 0.0 -- 1.0
+    activity: &'a [f32; 16],
+    setter: &'a ParamSetter<'a>,
+    width: f32,
+    row_height: f32,
+    theme: &'a Theme,
+}
+
+impl<'a> ChannelOverview<'a> {
+    pub(crate) fn new(
+        channels: &'a [ChannelParams],
+        selected: &'a mut usize,
+        activity: &'a [f32; 16],
+        setter: &'a ParamSetter<'a>,
+        width: f32,
+        row_height: f32,
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            channels,
+            selected,
+            activity,
+            setter,
+            width,
+            row_height,
+            theme,
+        }
+    }
+}
+
+/// The parameters shown for each band: depth, decay and precomp
+fn band_params(ch: &ChannelParams) -> [[&FloatParam; 3]; 3] {
+    [
+        [&ch.low_db, &ch.low_decay, &ch.low_precomp],
+        [&ch.mid_db, &ch.mid_decay, &ch.mid_precomp],
+        [&ch.high_db, &ch.high_decay, &ch.high_precomp],
+    ]
+}
+
+impl<'a> Widget for ChannelOverview<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let rows = self.channels.len();
+        let row_height = self.row_height;
+        let size = vec2(self.width, HEADER_HEIGHT + rows as f32 * row_height);
+        let mut response = ui.allocate_response(size, Sense::click());
+        let rect = response.rect;
+
+        let band_width = (rect.width() - LABEL_WIDTH - 2.0 * BAND_GAP) / 3.0;
+        let bar_width = (band_width - 2.0 * BAR_GAP) / 3.0;
+        let band_colors = self.theme.bands();
+        let bar_rect = |row: usize, band: usize, bar: usize| {
+            let x = rect.left()
+                + LABEL_WIDTH
+                + band as f32 * (band_width + BAND_GAP)
+                + bar as f32 * (bar_width + BAR_GAP);
+            let y = rect.top() + HEADER_HEIGHT + row as f32 * row_height;
+            Rect::from_min_size(pos2(x, y + 2.0), vec2(bar_width, row_height - 4.0))
+        };
+
+        // clicking anywhere on a row selects the channel
+        if response.clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let row = ((pos.y - rect.top() - HEADER_HEIGHT) / row_height).floor();
+                if row >= 0.0 && (row as usize) < rows && row as usize != *self.selected {
+                    *self.selected = row as usize;
+                    response.mark_changed();
+                }
+            }
+        }
+
+        // inline editing, each bar behaves like a horizontal slider
+        for (row, ch) in self.channels.iter().enumerate() {
+            for (band, params) in band_params(ch).into_iter().enumerate() {
+                for (bar, param) in params.into_iter().enumerate() {
+                    let bar_rect = bar_rect(row, band, bar);
+                    let res = ui.interact(
+                        bar_rect,
+                        response.id.with((row, band, bar)),
+                        Sense::click_and_drag(),
+                    );
+
+                    if res.clicked() && ui.input(|x| x.modifiers.command) {
+                        self.setter.begin_set_parameter(param);
+                        self.setter
+                            .set_parameter(param, param.default_plain_value());
+                        self.setter.end_set_parameter(param);
+                    } else {
+                        if res.drag_started() {
+                            self.setter.begin_set_parameter(param);
+                        }
+                        if res.dragged() {
+                            if let Some(pos) = res.interact_pointer_pos() {
+                                let normalized =
+                                    ((pos.x - bar_rect.left()) / bar_rect.width()).clamp(0.0, 1.0);
+                                let value = param.preview_plain(normalized);
+                                if value != param.unmodulated_plain_value() {
+                                    self.setter.set_parameter(param, value);
+                                }
+                            }
+                        }
+                        if res.drag_stopped() {
+                            self.setter.end_set_parameter(param);
+                        }
+                    }
+
+                    // touching a bar selects its channel too
+                    if (res.clicked() || res.drag_started()) && row != *self.selected {
+                        *self.selected = row;
+                        response.mark_changed();
+                    }
+                }
+            }
+        }
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, self.theme.bg_deep);
+
+        // band names
+        for (band, (name, color)) in ["Low", "Mid", "High"]
+            .into_iter()
+            .zip(band_colors)
+            .enumerate()
+        {
+            let x = rect.left() + LABEL_WIDTH + band as f32 * (band_width + BAND_GAP);
+            painter.text(
+                pos2(x, rect.top()),
+                Align2::LEFT_TOP,
+                name,
+                FontId::new(self.theme.text_sm, C::FONT_NORMAL),
+                color,
+            );
+        }
+
+        let hovered_row = response
+            .hover_pos()
+            .map(|pos| ((pos.y - rect.top() - HEADER_HEIGHT) / row_height).floor())
+            .filter(|row| *row >= 0.0)
+            .map(|row| row as usize);
+
+        for (row, ch) in self.channels.iter().enumerate() {
+            let row_rect = Rect::from_min_size(
+                pos2(
+                    rect.left(),
+                    rect.top() + HEADER_HEIGHT + row as f32 * row_height,
+                ),
+                vec2(rect.width(), row_height),
+            );
+
+            // row background, lit up while the channel is being triggered
+            let mut background = if row == *self.selected {
+                self.theme.bg_normal
+            } else if hovered_row == Some(row) {
+                self.theme.bg_normal.gamma_multiply(0.6)
+            } else {
+                Color32::TRANSPARENT
+            };
+            let activity = self.activity[row].clamp(0.0, 1.0);
+            if activity > 0.0 {
+                background =
+                    background.lerp_to_gamma(self.theme.fg_orange.gamma_multiply(0.4), activity);
+            }
+            painter.rect_filled(row_rect, 0.0, background);
+            if row == *self.selected {
+                painter.vline(
+                    row_rect.left() + 1.0,
+                    row_rect.top()..=row_rect.bottom(),
+                    Stroke::new(2.0, self.theme.fg_orange),
+                );
+            }
+
+            painter.text(
+                pos2(row_rect.left() + LABEL_WIDTH / 2.0, row_rect.center().y),
+                Align2::CENTER_CENTER,
+                // same numbering as the channel titles
+                row.to_string(),
+                FontId::new(self.theme.text_sm, C::FONT_NORMAL),
+                if row == *self.selected {
+                    self.theme.fg_bright
+                } else {
+                    self.theme.fg_dim
+                },
+            );
+
+            for (band, params) in band_params(ch).into_iter().enumerate() {
+                for (bar, param) in params.into_iter().enumerate() {
+                    let bar_rect = bar_rect(row, band, bar);
+                    painter.rect_filled(bar_rect, 0.0, self.theme.fg_bright.gamma_multiply(0.08));
+                    let filled = Rect::from_min_size(
+                        bar_rect.min,
+                        vec2(
+                            bar_rect.width() * param.modulated_normalized_value(),
+                            bar_rect.height(),
+                        ),
+                    );
+                    // depth is drawn in full color, decay and precomp are dimmed
+                    let color = if bar == 0 {
+                        band_colors[band]
+                    } else {
+                        band_colors[band].gamma_multiply(0.6)
+                    };
+                    painter.rect_filled(filled, 0.0, color);
+                }
+            }
+        }
+
+        response
+    }
+}
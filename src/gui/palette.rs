@@ -1,60 +1,366 @@
-use std::sync::LazyLock;
+use std::{path::PathBuf, sync::LazyLock};
 
 use nih_plug_egui::egui::{Color32, FontFamily};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-// thanks chatgpt
-macro_rules! define_colors {
-    ($($name:ident = $value:expr;)*) => {
-        $(pub(crate) const $name: Color32 = $value;)*
+pub(crate) const FONT_NORMAL: FontFamily = FontFamily::Proportional;
+pub(crate) static FONT_BOLD: LazyLock<FontFamily> =
+    LazyLock::new(|| FontFamily::Name("bold".into()));
+
+/// Name of the theme file in the config directory, see [`load_custom_theme`]
+const THEME_FILE: &str = "theme.json";
+
+/// The themes that can be picked in the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+    /// Loaded from the user's theme file
+    Custom,
+}
+
+impl ThemeName {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            ThemeName::Dark => "Dark",
+            ThemeName::Light => "Light",
+            ThemeName::HighContrast => "High contrast",
+            ThemeName::Custom => "Custom",
+        }
+    }
+
+    /// The theme after this one, used to cycle through the themes
+    pub(crate) fn next(self) -> Self {
+        match self {
+            ThemeName::Dark => ThemeName::Light,
+            ThemeName::Light => ThemeName::HighContrast,
+            ThemeName::HighContrast => ThemeName::Custom,
+            ThemeName::Custom => ThemeName::Dark,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum ThemeError {
+    #[error("failed to read the theme file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse the theme file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid color for `{field}`, expected \"#rrggbb\" or \"#rrggbbaa\", got {value:?}")]
+    InvalidColor { field: &'static str, value: String },
+    #[error("invalid text size for `{field}`, expected a number above 0, got {value}")]
+    InvalidSize { field: &'static str, value: f32 },
+    #[error("a custom theme can't be based on itself")]
+    InvalidBase,
+}
+
+/// Parse a color in the form `#rrggbb` or `#rrggbbaa`
+fn parse_color(field: &'static str, value: &str) -> Result<Color32, ThemeError> {
+    let invalid = || ThemeError::InvalidColor {
+        field,
+        value: value.to_string(),
+    };
+    let hex = value.strip_prefix('#').ok_or_else(invalid)?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.bytes().all(|x| x.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Ok(Color32::from_rgba_unmultiplied(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        alpha,
+    ))
+}
+
+// defines the theme, and a theme file where every field is optional
+macro_rules! define_theme {
+    (
+        colors { $($(#[$color_meta:meta])* $color:ident,)* }
+        sizes { $($(#[$size_meta:meta])* $size:ident,)* }
+    ) => {
+        /// Colors and text sizes of the editor
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub(crate) struct Theme {
+            /// Whether egui's own widgets should use their dark style
+            pub(crate) dark: bool,
+            $($(#[$color_meta])* pub(crate) $color: Color32,)*
+            $($(#[$size_meta])* pub(crate) $size: f32,)*
+        }
+
+        /// A theme loaded from the user's theme file. Fields that are left out are taken from the
+        /// base theme.
+        #[derive(Debug, Default, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct ThemeFile {
+            base: Option<ThemeName>,
+            $($color: Option<String>,)*
+            $($size: Option<f32>,)*
+        }
+
+        impl ThemeFile {
+            fn into_theme(self) -> Result<Theme, ThemeError> {
+                let mut theme = match self.base.unwrap_or_default() {
+                    ThemeName::Custom => return Err(ThemeError::InvalidBase),
+                    name => Theme::builtin(name),
+                };
+                $(if let Some(value) = self.$color {
+                    theme.$color = parse_color(stringify!($color), &value)?;
+                })*
+                $(if let Some(value) = self.$size {
+                    // egui can't draw text with these sizes
+                    if !value.is_finite() || value <= 0.0 {
+                        return Err(ThemeError::InvalidSize {
+                            field: stringify!($size),
+                            value,
+                        });
+                    }
+                    theme.$size = value;
+                })*
+                Ok(theme)
+            }
+        }
     };
 }
 
-define_colors! {
-    // basic color definitions
-    BG_DARK = Color32::from_rgb(17, 17, 17);
-    BG_NORMAL = Color32::from_rgb(33, 33, 33);
-    BG_LIGHT = Color32::from_rgb(48, 48, 48); // for the knobs panel
+define_theme! {
+    colors {
+        /// Plots, meters and the title bar
+        bg_deep,
+        bg_normal,
+        /// The band controls in the main view
+        bg_raised,
+
+        /// Values, lines and anything that should stand out
+        fg_bright,
+        /// Labels
+        fg_dim,
+        /// Reference lines and inactive elements
+        fg_faint,
+        fg_red,
+        fg_purple,
+        fg_orange,
+        fg_yellow,
+        fg_blue,
+        fg_green,
+
+        /// The track behind the highlight of a knob
+        knob_rim,
+    }
+    sizes {
+        text_large,
+        text_base,
+        text_sm,
+        text_xs,
+    }
+}
+
+impl Theme {
+    pub(crate) const DARK: Self = Self {
+        dark: true,
+        bg_deep: Color32::from_rgb(17, 17, 17),
+        bg_normal: Color32::from_rgb(33, 33, 33),
+        bg_raised: Color32::from_rgb(48, 48, 48),
+        fg_bright: Color32::from_rgb(245, 245, 245),
+        fg_dim: Color32::from_rgb(158, 158, 158),
+        fg_faint: Color32::from_rgb(97, 97, 97),
+        fg_red: Color32::from_rgb(239, 154, 154),
+        fg_purple: Color32::from_rgb(206, 147, 216),
+        fg_orange: Color32::from_rgb(255, 204, 128),
+        fg_yellow: Color32::from_rgb(255, 245, 157),
+        fg_blue: Color32::from_rgb(129, 212, 250),
+        fg_green: Color32::from_rgb(165, 214, 167),
+        knob_rim: Color32::from_rgb(64, 64, 64),
+        text_large: 16.0,
+        text_base: 12.0,
+        text_sm: 11.0,
+        text_xs: 10.0,
+    };
 
-    FG_WHITE = Color32::from_rgb(245, 245, 245);
-    FG_GREY = Color32::from_rgb(158, 158, 158);
-    FG_DARK_GREY = Color32::from_rgb(97, 97, 97);
-    FG_RED = Color32::from_rgb(239, 154, 154);
-    FG_PURPLE = Color32::from_rgb(206, 147, 216);
-    FG_ORANGE = Color32::from_rgb(255, 204, 128);
-    FG_YELLOW = Color32::from_rgb(255, 245, 157);
-    FG_BLUE = Color32::from_rgb(129, 212, 250);
-    FG_GREEN = Color32::from_rgb(165, 214, 167);
+    pub(crate) const LIGHT: Self = Self {
+        dark: false,
+        bg_deep: Color32::from_rgb(250, 250, 250),
+        bg_normal: Color32::from_rgb(232, 232, 232),
+        bg_raised: Color32::from_rgb(218, 218, 218),
+        fg_bright: Color32::from_rgb(20, 20, 20),
+        fg_dim: Color32::from_rgb(92, 92, 92),
+        fg_faint: Color32::from_rgb(165, 165, 165),
+        fg_red: Color32::from_rgb(198, 40, 40),
+        fg_purple: Color32::from_rgb(142, 36, 170),
+        fg_orange: Color32::from_rgb(230, 120, 0),
+        fg_yellow: Color32::from_rgb(190, 145, 0),
+        fg_blue: Color32::from_rgb(2, 119, 189),
+        fg_green: Color32::from_rgb(46, 125, 50),
+        knob_rim: Color32::from_rgb(190, 190, 190),
+        text_large: 16.0,
+        text_base: 12.0,
+        text_sm: 11.0,
+        text_xs: 10.0,
+    };
 
-    // // program colors
-    // PANEL_BORDER = BG_DARK;
+    /// Pure black and white with saturated accents, and slightly larger text
+    pub(crate) const HIGH_CONTRAST: Self = Self {
+        dark: true,
+        bg_deep: Color32::from_rgb(0, 0, 0),
+        bg_normal: Color32::from_rgb(16, 16, 16),
+        bg_raised: Color32::from_rgb(36, 36, 36),
+        fg_bright: Color32::from_rgb(255, 255, 255),
+        fg_dim: Color32::from_rgb(220, 220, 220),
+        fg_faint: Color32::from_rgb(150, 150, 150),
+        fg_red: Color32::from_rgb(255, 85, 85),
+        fg_purple: Color32::from_rgb(230, 120, 255),
+        fg_orange: Color32::from_rgb(255, 170, 0),
+        fg_yellow: Color32::from_rgb(255, 255, 0),
+        fg_blue: Color32::from_rgb(0, 200, 255),
+        fg_green: Color32::from_rgb(0, 255, 120),
+        knob_rim: Color32::from_rgb(110, 110, 110),
+        text_large: 18.0,
+        text_base: 14.0,
+        text_sm: 13.0,
+        text_xs: 12.0,
+    };
 
-    // TITLEBAR_BG = BG_DARK;
-    // TITLEBAR_COMPANY_TEXT = TEXT_DIMMED;
-    // TITLEBAR_NAME_TEXT = TEXT_NORMAL;
-    // TITLEBAR_BUTTON = TEXT_DIMMED;
-    // TITLEBAR_HELP_ACTIVE = TEXT_GREEN;
+    /// One of the built-in themes. The custom theme falls back to the dark theme.
+    pub(crate) fn builtin(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark | ThemeName::Custom => Self::DARK,
+            ThemeName::Light => Self::LIGHT,
+            ThemeName::HighContrast => Self::HIGH_CONTRAST,
+        }
+    }
 
-    // BAND_LOW = TEXT_BLUE;
-    // BAND_MID = TEXT_PURPLE;
-    // BAND_HIGH = TEXT_YELLOW;
+    /// Colors of the low, mid and high bands
+    pub(crate) fn bands(&self) -> [Color32; 3] {
+        [self.fg_blue, self.fg_purple, self.fg_yellow]
+    }
+}
 
-    // ANALYZER_BG = BG_NORMAL;
+/// Directory for the user's configuration, following each platform's conventions
+fn config_dir() -> Option<PathBuf> {
+    let env_path = |key| {
+        std::env::var_os(key)
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(target_os = "windows") {
+        env_path("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_path("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_path("XDG_CONFIG_HOME").or_else(|| env_path("HOME").map(|home| home.join(".config")))
+    }
+}
 
-    // // for the knobs panel
-    // PANEL_BG = BG_LIGHT;
-    // PANEL_TEXT = TEXT_DIMMED;
+/// Path of the user's theme file, e.g. `~/.config/malt/theme.json` on Linux
+pub(crate) fn theme_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("malt").join(THEME_FILE))
+}
 
-    // PANEL_KNOB_BASE = BG_NORMAL;
-    // PANEL_KNOB_MARKER = TEXT_NORMAL;
-    PANEL_KNOB_RIM_BG = Color32::from_rgb(64, 64, 64);
-    // PANEL_KNOB_TEXT = Color32::from_rgb(64, 64, 64);
+/// Load the user's theme file. Returns `None` if there is no theme file.
+pub(crate) fn load_custom_theme() -> Result<Option<Theme>, ThemeError> {
+    let Some(path) = theme_path().filter(|path| path.exists()) else {
+        return Ok(None);
+    };
+    let text = std::fs::read_to_string(path)?;
+    parse_theme(&text).map(Some)
 }
 
-pub(crate) const TEXT_LARGE: f32 = 16.0;
-pub(crate) const TEXT_BASE: f32 = 12.0;
-pub(crate) const TEXT_SM: f32 = 11.0;
-pub(crate) const TEXT_XS: f32 = 10.0;
+fn parse_theme(text: &str) -> Result<Theme, ThemeError> {
+    serde_json::from_str::<ThemeFile>(text)?.into_theme()
+}
 
-pub(crate) const FONT_NORMAL: FontFamily = FontFamily::Proportional;
-pub(crate) static FONT_BOLD: LazyLock<FontFamily> =
-    LazyLock::new(|| FontFamily::Name("bold".into()));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod parse_color {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            assert_eq!(
+                parse_color("x", "#ff8000").unwrap(),
+                Color32::from_rgb(255, 128, 0)
+            );
+            assert_eq!(
+                parse_color("x", "#FF800080").unwrap(),
+                Color32::from_rgba_unmultiplied(255, 128, 0, 128)
+            );
+        }
+
+        #[test]
+        fn test_02() {
+            for value in ["ff8000", "#ff80", "#ff80000", "#gg8000", "#ff8é0"] {
+                assert!(
+                    matches!(
+                        parse_color("x", value),
+                        Err(ThemeError::InvalidColor { field: "x", .. })
+                    ),
+                    "{value}"
+                );
+            }
+        }
+    }
+
+    mod parse_theme {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // fields that are left out come from the base theme
+            let theme =
+                parse_theme(r##"{ "base": "light", "fg_blue": "#0000ff", "text_base": 13 }"##)
+                    .unwrap();
+            assert_eq!(theme.fg_blue, Color32::from_rgb(0, 0, 255));
+            assert_eq!(theme.text_base, 13.0);
+            assert_eq!(theme.bg_deep, Theme::LIGHT.bg_deep);
+            assert!(!theme.dark);
+
+            // the base defaults to the dark theme
+            assert_eq!(parse_theme("{}").unwrap(), Theme::DARK);
+        }
+
+        #[test]
+        fn test_02() {
+            assert!(matches!(
+                parse_theme(r#"{ "base": "custom" }"#),
+                Err(ThemeError::InvalidBase)
+            ));
+            assert!(matches!(
+                parse_theme(r#"{ "bg_deep": "black" }"#),
+                Err(ThemeError::InvalidColor {
+                    field: "bg_deep",
+                    ..
+                })
+            ));
+            // typos are reported instead of being silently ignored
+            assert!(matches!(
+                parse_theme(r##"{ "bg_dep": "#000000" }"##),
+                Err(ThemeError::Json(_))
+            ));
+        }
+
+        #[test]
+        fn test_03() {
+            // 1e39 doesn't fit in an f32, so it becomes infinity
+            for value in ["0", "-12", "0.0", "1e39"] {
+                let text = format!(r#"{{ "text_sm": {value} }}"#);
+                assert!(
+                    matches!(
+                        parse_theme(&text),
+                        Err(ThemeError::InvalidSize {
+                            field: "text_sm",
+                            ..
+                        })
+                    ),
+                    "{value}"
+                );
+            }
+        }
+    }
+}
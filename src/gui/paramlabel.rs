@@ -0,0 +1,125 @@
+use super::palette::Theme;
+use nih_plug::prelude::{Param, ParamSetter};
+use nih_plug_egui::egui::{
+    text::LayoutJob, Align, Color32, FontId, Response, Sense, TextFormat, Ui, Vec2, Widget,
+};
+
+/// One pixel dragged corresponds to this much change in the normalized parameter
+const DRAG_MULTIPLIER: f32 = 0.005;
+
+/// A "Name: value" label that edits its parameter, like the settings in the footer of the
+/// mockup. Clicking steps through the values of stepped parameters, dragging up and down changes
+/// continuous parameters, and ctrl+clicking resets the parameter.
+pub(crate) struct ParamLabel<'a, P: Param> {
+    param: &'a P,
+    param_setter: &'a ParamSetter<'a>,
+    name: &'static str,
+    /// When false, only the name is shown. It is highlighted while the parameter is on, which
+    /// suits toggles like "Bypass". An empty name only shows the value.
+    show_value: bool,
+    size: Vec2,
+    font_id: FontId,
+    theme: &'a Theme,
+}
+
+impl<'a, P: Param> ParamLabel<'a, P> {
+    pub(crate) fn for_param(
+        param: &'a P,
+        param_setter: &'a ParamSetter<'a>,
+        name: &'static str,
+        show_value: bool,
+        size: Vec2,
+        font_id: FontId,
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            param,
+            param_setter,
+            name,
+            show_value,
+            size,
+            font_id,
+            theme,
+        }
+    }
+
+    fn set_normalized_value(&self, normalized: f32) {
+        let value = self.param.preview_plain(normalized);
+        if value != self.param.unmodulated_plain_value() {
+            self.param_setter.set_parameter(self.param, value);
+        }
+    }
+}
+
+impl<'a, P: Param> Widget for ParamLabel<'a, P> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let mut response = ui.allocate_response(self.size, Sense::click_and_drag());
+        let value = self.param.unmodulated_normalized_value();
+
+        if response.clicked() {
+            self.param_setter.begin_set_parameter(self.param);
+            if ui.input(|x| x.modifiers.command) {
+                self.param_setter
+                    .set_parameter(self.param, self.param.default_plain_value());
+            } else if let Some(steps) = self.param.step_count() {
+                // step to the next value, wrapping around at the end
+                let index = (value * steps as f32).round() as usize;
+                self.set_normalized_value(((index + 1) % (steps + 1)) as f32 / steps as f32);
+            }
+            self.param_setter.end_set_parameter(self.param);
+            response.mark_changed();
+        } else if self.param.step_count().is_none() {
+            if response.drag_started() {
+                self.param_setter.begin_set_parameter(self.param);
+            }
+            let drag_distance = -response.drag_delta().y;
+            if drag_distance != 0.0 {
+                self.set_normalized_value(
+                    (value + drag_distance * DRAG_MULTIPLIER).clamp(0.0, 1.0),
+                );
+                response.mark_changed();
+            }
+            if response.drag_stopped() {
+                self.param_setter.end_set_parameter(self.param);
+            }
+        }
+
+        let painter = ui.painter_at(response.rect);
+        if response.hovered() || response.dragged() {
+            painter.rect_filled(response.rect, 0.0, self.theme.fg_bright.gamma_multiply(0.1));
+        }
+
+        let mut job = LayoutJob::default();
+        job.halign = Align::Center;
+        if self.show_value {
+            if !self.name.is_empty() {
+                job.append(
+                    &format!("{}: ", self.name),
+                    0.0,
+                    TextFormat::simple(self.font_id.clone(), self.theme.fg_dim),
+                );
+            }
+            job.append(
+                &self.param.normalized_value_to_string(value, true),
+                0.0,
+                TextFormat::simple(self.font_id.clone(), self.theme.fg_bright),
+            );
+        } else {
+            let color = if value > 0.5 {
+                self.theme.fg_orange
+            } else {
+                self.theme.fg_bright
+            };
+            job.append(
+                self.name,
+                0.0,
+                TextFormat::simple(self.font_id.clone(), color),
+            );
+        }
+        let galley = painter.layout_job(job);
+        let pos = response.rect.center() - galley.rect.center().to_vec2();
+        painter.galley(pos, galley, Color32::PLACEHOLDER);
+
+        response
+    }
+}
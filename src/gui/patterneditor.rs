@@ -0,0 +1,127 @@
+use super::palette::Theme;
+use crate::pattern::{CurveType, Pattern, Point};
+use nih_plug_egui::egui::{
+    pos2, Color32, Id, Pos2, Rect, Response, Sense, Shape, Stroke, Ui, Vec2, Widget,
+};
+
+/// Radius of the point handles
+const POINT_RADIUS: f32 = 3.5;
+/// How close the pointer must be to a point to grab it
+const GRAB_RADIUS: f32 = 8.0;
+/// Number of line segments used to draw the curve
+const CURVE_SEGMENTS: usize = 64;
+
+/// Editor for a single [`Pattern`]. Drag a point to move it, double-click to add a point,
+/// right-click a point to remove it.
+pub(crate) struct PatternEditor<'a> {
+    pattern: &'a mut Pattern,
+    size: Vec2,
+    color: Color32,
+    theme: &'a Theme,
+}
+
+impl<'a> PatternEditor<'a> {
+    pub(crate) fn new(
+        pattern: &'a mut Pattern,
+        size: Vec2,
+        color: Color32,
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            pattern,
+            size,
+            color,
+            theme,
+        }
+    }
+}
+
+fn to_screen(rect: Rect, x: f64, y: f64) -> Pos2 {
+    pos2(
+        rect.left() + x as f32 * rect.width(),
+        rect.bottom() - y as f32 * rect.height(),
+    )
+}
+
+fn from_screen(rect: Rect, pos: Pos2) -> (f64, f64) {
+    let x = (pos.x - rect.left()) / rect.width();
+    let y = (rect.bottom() - pos.y) / rect.height();
+    (x.clamp(0.0, 1.0) as f64, y.clamp(0.0, 1.0) as f64)
+}
+
+/// Index of the point closest to the given position, if it is within grabbing distance
+fn point_near(pattern: &Pattern, rect: Rect, pos: Pos2) -> Option<usize> {
+    pattern
+        .points()
+        .iter()
+        .map(|p| to_screen(rect, p.x(), p.y()).distance(pos))
+        .enumerate()
+        .filter(|(_, distance)| *distance <= GRAB_RADIUS)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
+
+impl<'a> Widget for PatternEditor<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let mut response = ui.allocate_response(self.size, Sense::click_and_drag());
+        let rect = response.rect;
+        let drag_id: Id = response.id.with("dragged point");
+
+        if response.drag_started() {
+            let grabbed = response
+                .interact_pointer_pos()
+                .and_then(|pos| point_near(self.pattern, rect, pos));
+            ui.data_mut(|data| data.insert_temp(drag_id, grabbed));
+        }
+
+        if let Some(pos) = response.interact_pointer_pos() {
+            if response.dragged() {
+                let grabbed = ui.data(|data| data.get_temp::<Option<usize>>(drag_id));
+                if let Some(Some(i)) = grabbed {
+                    let (x, y) = from_screen(rect, pos);
+                    if self.pattern.move_point(i, x, y).is_ok() {
+                        response.mark_changed();
+                    }
+                }
+            } else if response.double_clicked() {
+                let (x, y) = from_screen(rect, pos);
+                if let Some(point) = Point::new(x, y, 0.0, CurveType::Curve) {
+                    if self.pattern.insert_point(point).is_ok() {
+                        response.mark_changed();
+                    }
+                }
+            } else if response.secondary_clicked() {
+                if let Some(i) = point_near(self.pattern, rect, pos) {
+                    if self.pattern.remove_point(i).is_ok() {
+                        response.mark_changed();
+                    }
+                }
+            }
+        }
+
+        if response.drag_stopped() {
+            ui.data_mut(|data| data.remove::<Option<usize>>(drag_id));
+        }
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, self.theme.bg_deep);
+
+        let curve: Vec<Pos2> = (0..=CURVE_SEGMENTS)
+            .map(|i| {
+                let x = i as f64 / CURVE_SEGMENTS as f64;
+                to_screen(rect, x, self.pattern.get_y_at(x))
+            })
+            .collect();
+        painter.add(Shape::line(curve, Stroke::new(1.5, self.color)));
+
+        for p in self.pattern.points() {
+            painter.circle_filled(
+                to_screen(rect, p.x(), p.y()),
+                POINT_RADIUS,
+                self.theme.fg_bright,
+            );
+        }
+
+        response
+    }
+}
@@ -0,0 +1,126 @@
+use super::palette::Theme;
+use crate::ThreeBandSplitter;
+use nih_plug_egui::egui::{pos2, Color32, Pos2, Response, Sense, Shape, Stroke, Ui, Vec2, Widget};
+
+/// Lowest frequency shown by the plot
+const DISPLAY_MIN_HZ: f64 = 20.0;
+/// Highest frequency shown by the plot
+const DISPLAY_MAX_HZ: f64 = 20000.0;
+/// Range of the magnitude axis, in dB
+const DISPLAY_MIN_DB: f32 = -36.0;
+const DISPLAY_MAX_DB: f32 = 6.0;
+/// Number of frequencies the response is evaluated at
+const POINTS: usize = 256;
+
+/// Magnitude response of each band and of the summed output, evaluated from the coefficients of
+/// the crossover filters. Optionally draws the phase response as well.
+pub(crate) struct FrequencyResponse<'a> {
+    splitter: &'a ThreeBandSplitter,
+    show_phase: bool,
+    size: Vec2,
+    /// Colors of the low, mid and high bands
+    colors: [Color32; 3],
+    theme: &'a Theme,
+}
+
+impl<'a> FrequencyResponse<'a> {
+    pub(crate) fn new(
+        splitter: &'a ThreeBandSplitter,
+        show_phase: bool,
+        size: Vec2,
+        colors: [Color32; 3],
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            splitter,
+            show_phase,
+            size,
+            colors,
+            theme,
+        }
+    }
+}
+
+impl<'a> Widget for FrequencyResponse<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let response = ui.allocate_response(self.size, Sense::hover());
+        let rect = response.rect;
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, self.theme.bg_deep);
+
+        let db_to_y = |db: f32| {
+            let t = (db.clamp(DISPLAY_MIN_DB, DISPLAY_MAX_DB) - DISPLAY_MAX_DB)
+                / (DISPLAY_MIN_DB - DISPLAY_MAX_DB);
+            rect.top() + t * rect.height()
+        };
+        // -180 degrees at the bottom, +180 degrees at the top
+        let phase_to_y = |radians: f64| {
+            let t = 0.5 - radians / std::f64::consts::TAU;
+            rect.top() + t as f32 * rect.height()
+        };
+
+        // 0dB line
+        painter.hline(
+            rect.left()..=rect.right(),
+            db_to_y(0.0),
+            Stroke::new(1.0, self.theme.fg_faint),
+        );
+
+        // evaluate the response at log-spaced frequencies
+        let responses: Vec<(f32, [_; 4])> = (0..POINTS)
+            .map(|i| {
+                let t = i as f64 / (POINTS - 1) as f64;
+                let f = DISPLAY_MIN_HZ * (DISPLAY_MAX_HZ / DISPLAY_MIN_HZ).powf(t);
+                let [low, mid, high] = self.splitter.frequency_response(f);
+                let x = rect.left() + t as f32 * rect.width();
+                (x, [low, mid, high, low + mid + high])
+            })
+            .collect();
+
+        let colors = [
+            self.colors[0],
+            self.colors[1],
+            self.colors[2],
+            self.theme.fg_bright,
+        ];
+        for (curve, color) in colors.into_iter().enumerate() {
+            if self.show_phase {
+                // the phase wraps around at +-180 degrees, start a new line whenever it does
+                let mut segments: Vec<Vec<Pos2>> = vec![vec![]];
+                let mut previous_phase: Option<f64> = None;
+                for (x, values) in responses.iter() {
+                    let phase = values[curve].arg();
+                    if let Some(previous) = previous_phase {
+                        if (phase - previous).abs() > std::f64::consts::PI {
+                            segments.push(vec![]);
+                        }
+                    }
+                    previous_phase = Some(phase);
+                    segments
+                        .last_mut()
+                        .unwrap()
+                        .push(pos2(*x, phase_to_y(phase)));
+                }
+                for points in segments {
+                    painter.extend(Shape::dashed_line(
+                        &points,
+                        Stroke::new(1.0, color.gamma_multiply(0.5)),
+                        3.0,
+                        3.0,
+                    ));
+                }
+            }
+
+            let points: Vec<Pos2> = responses
+                .iter()
+                .map(|(x, values)| {
+                    let db = nih_plug::util::gain_to_db(values[curve].norm() as f32);
+                    pos2(*x, db_to_y(db))
+                })
+                .collect();
+            painter.add(Shape::line(points, Stroke::new(1.5, color)));
+        }
+
+        response
+    }
+}
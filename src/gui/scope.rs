@@ -0,0 +1,143 @@
+use super::palette::Theme;
+use crate::capture::ScopeFrame;
+use nih_plug_egui::egui::{pos2, Color32, Pos2, Response, Sense, Shape, Stroke, Ui, Vec2, Widget};
+
+/// Largest gain reduction shown by the overlay, in dB
+const MAX_REDUCTION_DB: f32 = 24.0;
+
+/// Waveform of the incoming and outgoing audio, with the gain reduction of each band drawn on
+/// top and a marker wherever a note lands.
+///
+/// While the host is playing, the view is synced to the song and redraws over itself every
+/// `beats` beats. Otherwise it scrolls, with the newest frames on the right.
+pub(crate) struct Scope<'a> {
+    /// Number of the first frame in `frames`
+    first_frame: usize,
+    frames: &'a [ScopeFrame],
+    /// Frame numbers where notes land
+    triggers: &'a [usize],
+    /// Length of the view in beats
+    beats: usize,
+    /// Used to convert beats to frames when the host isn't playing
+    frames_per_beat: f32,
+    size: Vec2,
+    /// Colors of the low, mid and high bands, `None` hides the band's gain reduction
+    colors: [Option<Color32>; 3],
+    theme: &'a Theme,
+}
+
+impl<'a> Scope<'a> {
+    pub(crate) fn new(
+        first_frame: usize,
+        frames: &'a [ScopeFrame],
+        triggers: &'a [usize],
+        beats: usize,
+        frames_per_beat: f32,
+        size: Vec2,
+        colors: [Option<Color32>; 3],
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            first_frame,
+            frames,
+            triggers,
+            beats,
+            frames_per_beat,
+            size,
+            colors,
+            theme,
+        }
+    }
+
+    /// Horizontal position of the given frame, 0.0 -- 1.0, or `None` if it's out of view
+    fn frame_position(&self, n: usize) -> Option<f32> {
+        let latest = self.frames.last()?;
+        let frame = self.frames.get(n.checked_sub(self.first_frame)?)?;
+        let beats = self.beats as f32;
+
+        if latest.beat.is_finite() {
+            // synced to the song, the current page is drawn over the previous page
+            if !frame.beat.is_finite() || frame.beat <= latest.beat - beats {
+                return None;
+            }
+            let page_start = (latest.beat / beats).floor() * beats;
+            Some((frame.beat - page_start).rem_euclid(beats) / beats)
+        } else {
+            // scrolling
+            let age = (self.first_frame + self.frames.len() - 1 - n) as f32;
+            let window = beats * self.frames_per_beat;
+            (age < window).then(|| 1.0 - age / window)
+        }
+    }
+}
+
+impl<'a> Widget for Scope<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let response = ui.allocate_response(self.size, Sense::hover());
+        let rect = response.rect;
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, self.theme.bg_deep);
+
+        let center_y = rect.center().y;
+        let to_x = |position: f32| rect.left() + position * rect.width();
+
+        // waveforms, drawn as peaks mirrored around the center line
+        for (i, frame) in self.frames.iter().enumerate() {
+            let Some(position) = self.frame_position(self.first_frame + i) else {
+                continue;
+            };
+            let x = to_x(position);
+            for (peak, color) in [
+                (frame.input, self.theme.fg_faint),
+                (frame.output, self.theme.fg_dim),
+            ] {
+                let height = peak.min(1.0) * rect.height() / 2.0;
+                painter.vline(
+                    x,
+                    (center_y - height)..=(center_y + height),
+                    Stroke::new(1.0, color),
+                );
+            }
+        }
+
+        // gain reduction of each band, growing down from the top
+        for (band, color) in self.colors.into_iter().enumerate() {
+            let Some(color) = color else {
+                continue;
+            };
+            let mut line: Vec<Pos2> = vec![];
+            let mut previous_position = f32::NEG_INFINITY;
+            for (i, frame) in self.frames.iter().enumerate() {
+                let Some(position) = self.frame_position(self.first_frame + i) else {
+                    continue;
+                };
+                // start a new line where the synced view wraps around
+                if position < previous_position {
+                    painter.add(Shape::line(
+                        std::mem::take(&mut line),
+                        Stroke::new(1.5, color),
+                    ));
+                }
+                previous_position = position;
+
+                let db = frame.gain_reduction[band].clamp(0.0, MAX_REDUCTION_DB);
+                let y = rect.top() + rect.height() * db / MAX_REDUCTION_DB;
+                line.push(pos2(to_x(position), y));
+            }
+            painter.add(Shape::line(line, Stroke::new(1.5, color)));
+        }
+
+        // note markers
+        for trigger in self.triggers.iter() {
+            if let Some(position) = self.frame_position(*trigger) {
+                painter.vline(
+                    to_x(position),
+                    rect.top()..=rect.bottom(),
+                    Stroke::new(1.0, self.theme.fg_orange),
+                );
+            }
+        }
+
+        response
+    }
+}
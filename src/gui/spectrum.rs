@@ -0,0 +1,206 @@
+use super::palette::Theme;
+use crate::{CROSSOVER_MAX_HZ, CROSSOVER_MIN_HZ, CROSSOVER_MIN_RATIO};
+use nih_plug::prelude::{FloatParam, Param, ParamSetter};
+use nih_plug_egui::egui::{pos2, Pos2, Rect, Response, Sense, Shape, Stroke, Ui, Vec2, Widget};
+
+/// Lowest frequency shown by the analyzer
+const DISPLAY_MIN_HZ: f32 = 20.0;
+/// Highest frequency shown by the analyzer
+const DISPLAY_MAX_HZ: f32 = 20000.0;
+/// Lowest level shown by the analyzer, in dB
+const DISPLAY_FLOOR_DB: f32 = -90.0;
+/// How close the pointer must be to a crossover to grab it
+const GRAB_DISTANCE: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Handle {
+    Low,
+    High,
+}
+
+/// Spectrum of the signal before and after processing, drawn behind the three bands. The band
+/// edges can be dragged to set the crossovers.
+pub(crate) struct SpectrumAnalyzer<'a> {
+    /// Spectrum before processing in dB, see [`super::fft::spectrum_db`]
+    input: &'a [f32],
+    /// Spectrum after processing in dB
+    output: &'a [f32],
+    sample_rate: f32,
+    low_crossover: &'a FloatParam,
+    high_crossover: &'a FloatParam,
+    setter: &'a ParamSetter<'a>,
+    size: Vec2,
+    theme: &'a Theme,
+}
+
+impl<'a> SpectrumAnalyzer<'a> {
+    pub(crate) fn new(
+        input: &'a [f32],
+        output: &'a [f32],
+        sample_rate: f32,
+        low_crossover: &'a FloatParam,
+        high_crossover: &'a FloatParam,
+        setter: &'a ParamSetter<'a>,
+        size: Vec2,
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            input,
+            output,
+            sample_rate,
+            low_crossover,
+            high_crossover,
+            setter,
+            size,
+            theme,
+        }
+    }
+
+    fn param(&self, handle: Handle) -> &'a FloatParam {
+        match handle {
+            Handle::Low => self.low_crossover,
+            Handle::High => self.high_crossover,
+        }
+    }
+
+    /// Keep the crossovers at least an octave apart, the same limit as the audio thread
+    fn clamp_frequency(&self, handle: Handle, hz: f32) -> f32 {
+        match handle {
+            Handle::Low => hz.clamp(
+                CROSSOVER_MIN_HZ,
+                self.high_crossover.value() / CROSSOVER_MIN_RATIO,
+            ),
+            Handle::High => hz.clamp(
+                self.low_crossover.value() * CROSSOVER_MIN_RATIO,
+                CROSSOVER_MAX_HZ,
+            ),
+        }
+    }
+}
+
+fn hz_to_x(rect: Rect, hz: f32) -> f32 {
+    let t = (hz / DISPLAY_MIN_HZ).ln() / (DISPLAY_MAX_HZ / DISPLAY_MIN_HZ).ln();
+    rect.left() + t * rect.width()
+}
+
+fn x_to_hz(rect: Rect, x: f32) -> f32 {
+    let t = (x - rect.left()) / rect.width();
+    DISPLAY_MIN_HZ * (DISPLAY_MAX_HZ / DISPLAY_MIN_HZ).powf(t)
+}
+
+fn db_to_y(rect: Rect, db: f32) -> f32 {
+    let t = db.clamp(DISPLAY_FLOOR_DB, 0.0) / DISPLAY_FLOOR_DB;
+    rect.top() + t * rect.height()
+}
+
+impl<'a> Widget for SpectrumAnalyzer<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let mut response = ui.allocate_response(self.size, Sense::click_and_drag());
+        let rect = response.rect;
+        let drag_id = response.id.with("dragged crossover");
+
+        let low_x = hz_to_x(rect, self.low_crossover.value());
+        let high_x = hz_to_x(rect, self.high_crossover.value());
+        let handle_near = |x: f32| {
+            let low_distance = (x - low_x).abs();
+            let high_distance = (x - high_x).abs();
+            if low_distance.min(high_distance) > GRAB_DISTANCE {
+                None
+            } else if low_distance < high_distance {
+                Some(Handle::Low)
+            } else {
+                Some(Handle::High)
+            }
+        };
+
+        // dragging a crossover
+        if response.drag_started() {
+            let handle = response
+                .interact_pointer_pos()
+                .and_then(|pos| handle_near(pos.x));
+            if let Some(handle) = handle {
+                self.setter.begin_set_parameter(self.param(handle));
+            }
+            ui.data_mut(|data| data.insert_temp(drag_id, handle));
+        }
+        let dragged = ui.data(|data| data.get_temp::<Option<Handle>>(drag_id).flatten());
+        if let (Some(handle), Some(pos)) = (dragged, response.interact_pointer_pos()) {
+            if response.dragged() {
+                let hz = self.clamp_frequency(handle, x_to_hz(rect, pos.x));
+                self.setter.set_parameter(self.param(handle), hz);
+                response.mark_changed();
+            }
+        }
+        if response.drag_stopped() {
+            if let Some(handle) = dragged {
+                self.setter.end_set_parameter(self.param(handle));
+            }
+            ui.data_mut(|data| data.remove::<Option<Handle>>(drag_id));
+        }
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, self.theme.bg_deep);
+
+        // band regions, the crossovers may have just been dragged
+        let low_x = hz_to_x(rect, self.low_crossover.value());
+        let high_x = hz_to_x(rect, self.high_crossover.value());
+        for (left, right, color) in [
+            (rect.left(), low_x, self.theme.fg_blue),
+            (low_x, high_x, self.theme.fg_purple),
+            (high_x, rect.right(), self.theme.fg_yellow),
+        ] {
+            let region = Rect::from_x_y_ranges(left..=right, rect.top()..=rect.bottom());
+            painter.rect_filled(region, 0.0, color.gamma_multiply(0.08));
+        }
+
+        // spectra
+        let bin_hz = self.sample_rate / (self.input.len() * 2).max(1) as f32;
+        let to_points = |spectrum: &[f32]| -> Vec<Pos2> {
+            spectrum
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(i, db)| (i as f32 * bin_hz, *db))
+                .filter(|(hz, _)| (DISPLAY_MIN_HZ..=DISPLAY_MAX_HZ).contains(hz))
+                .map(|(hz, db)| pos2(hz_to_x(rect, hz), db_to_y(rect, db)))
+                .collect()
+        };
+        painter.add(Shape::line(
+            to_points(self.input),
+            Stroke::new(1.0, self.theme.fg_faint),
+        ));
+        painter.add(Shape::line(
+            to_points(self.output),
+            Stroke::new(1.0, self.theme.fg_bright),
+        ));
+
+        // crossover handles, highlighted when hovered or dragged
+        let hovered = response
+            .hover_pos()
+            .and_then(|pos| handle_near(pos.x))
+            .or(dragged);
+        for (handle, x) in [(Handle::Low, low_x), (Handle::High, high_x)] {
+            let color = if hovered == Some(handle) {
+                self.theme.fg_bright
+            } else {
+                self.theme.fg_dim
+            };
+            painter.vline(x, rect.top()..=rect.bottom(), Stroke::new(2.0, color));
+        }
+
+        response
+    }
+}
+
+/// Combine a new spectrum into the displayed one. Peaks show up immediately and fall back
+/// gradually, which keeps the display readable.
+pub(crate) fn smooth_spectrum(shown: &mut Vec<f32>, new: &[f32], falloff_db: f32) {
+    if shown.len() != new.len() {
+        shown.clear();
+        shown.extend_from_slice(new);
+        return;
+    }
+    for (shown, new) in shown.iter_mut().zip(new) {
+        *shown = new.max(*shown - falloff_db);
+    }
+}
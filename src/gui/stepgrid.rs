@@ -0,0 +1,89 @@
+use super::palette::Theme;
+use nih_plug_egui::egui::{pos2, vec2, Color32, Rect, Response, Sense, Ui, Vec2, Widget};
+
+/// A row of sequencer steps. Clicking a step toggles it, dragging vertically sets its velocity.
+pub(crate) struct StepGrid<'a> {
+    /// The velocity of each step, 0.0 means the step is off
+    steps: &'a mut [f32],
+    step_size: Vec2,
+    spacing: f32,
+    color: Color32,
+    theme: &'a Theme,
+}
+
+impl<'a> StepGrid<'a> {
+    pub(crate) fn new(
+        steps: &'a mut [f32],
+        step_size: Vec2,
+        spacing: f32,
+        color: Color32,
+        theme: &'a Theme,
+    ) -> Self {
+        Self {
+            steps,
+            step_size,
+            spacing,
+            color,
+            theme,
+        }
+    }
+
+    fn step_rect(&self, rect: Rect, step: usize) -> Rect {
+        let x = rect.left() + step as f32 * (self.step_size.x + self.spacing);
+        Rect::from_min_size(pos2(x, rect.top()), self.step_size)
+    }
+}
+
+impl<'a> Widget for StepGrid<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let count = self.steps.len();
+        let size = vec2(
+            count as f32 * (self.step_size.x + self.spacing) - self.spacing,
+            self.step_size.y,
+        );
+        let mut response = ui.allocate_response(size, Sense::click_and_drag());
+        let rect = response.rect;
+
+        // find the step under the pointer
+        if let Some(pos) = response.interact_pointer_pos() {
+            let step_width = self.step_size.x + self.spacing;
+            let step = ((pos.x - rect.left()) / step_width).floor();
+            if step >= 0.0 && (step as usize) < count {
+                let step = step as usize;
+                if response.clicked() {
+                    // toggle the step
+                    self.steps[step] = if self.steps[step] > 0.0 { 0.0 } else { 1.0 };
+                    response.mark_changed();
+                } else if response.dragged() {
+                    // set the velocity from the vertical position, never fully turn the step off
+                    let velocity = 1.0 - (pos.y - rect.top()) / rect.height();
+                    self.steps[step] = velocity.clamp(0.05, 1.0);
+                    response.mark_changed();
+                }
+            }
+        }
+
+        let painter = ui.painter_at(rect);
+        for step in 0..count {
+            let step_rect = self.step_rect(rect, step);
+
+            // highlight the first step of every beat
+            let bg_color = if step % 4 == 0 {
+                self.theme.bg_raised
+            } else {
+                self.theme.bg_normal
+            };
+            painter.rect_filled(step_rect, 0.0, bg_color);
+
+            // velocity bar, growing from the bottom
+            let velocity = self.steps[step];
+            if velocity > 0.0 {
+                let mut bar_rect = step_rect;
+                bar_rect.set_top(step_rect.bottom() - step_rect.height() * velocity);
+                painter.rect_filled(bar_rect, 0.0, self.color);
+            }
+        }
+
+        response
+    }
+}
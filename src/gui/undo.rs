@@ -0,0 +1,566 @@
+use crate::notemap::NoteMap;
+use crate::pattern::MorphPatterns;
+use crate::sequencer::SequencerPattern;
+use crate::MaltParams;
+use nih_plug::prelude::*;
+use parking_lot::{Mutex, MutexGuard};
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Number of edits that can be undone, older edits are forgotten
+const HISTORY_LIMIT: usize = 100;
+
+/// The persisted fields of [`MaltParams`] that can be undone. The other persisted fields only
+/// hold the state of the editor.
+#[derive(Clone, PartialEq)]
+struct Fields {
+    note_map: NoteMap,
+    sequencer: SequencerPattern,
+    morph_patterns: MorphPatterns,
+}
+
+impl Fields {
+    fn read(params: &MaltParams) -> Self {
+        Self {
+            note_map: *params.note_map.read().unwrap(),
+            sequencer: *params.sequencer.read().unwrap(),
+            morph_patterns: params.morph_patterns.read().unwrap().clone(),
+        }
+    }
+
+    /// Whether the fields of `params` still have these values, this doesn't copy anything
+    fn matches(&self, params: &MaltParams) -> bool {
+        self.note_map == *params.note_map.read().unwrap()
+            && self.sequencer == *params.sequencer.read().unwrap()
+            && self.morph_patterns == *params.morph_patterns.read().unwrap()
+    }
+
+    fn write(&self, params: &MaltParams) {
+        *params.note_map.write().unwrap() = self.note_map;
+        *params.sequencer.write().unwrap() = self.sequencer;
+        params
+            .morph_patterns
+            .write()
+            .unwrap()
+            .clone_from(&self.morph_patterns);
+    }
+}
+
+enum Change {
+    /// A parameter changed from one normalized value to another
+    Param {
+        param: ParamPtr,
+        before: f32,
+        after: f32,
+    },
+    /// The persisted fields changed
+    Fields {
+        before: Box<Fields>,
+        after: Box<Fields>,
+    },
+}
+
+/// A parameter that is being changed, between `begin_set_parameter` and `end_set_parameter`
+struct Gesture {
+    param: ParamPtr,
+    before: f32,
+    /// The last value that was set, the parameter itself may only be updated later by the host
+    after: Option<f32>,
+}
+
+#[derive(Default)]
+struct History {
+    /// Edits that can be undone, oldest first. Each edit may change several things at once.
+    undo: VecDeque<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    gestures: Vec<Gesture>,
+    /// Finished changes that will become the next edit
+    pending: Vec<Change>,
+    /// The undoable fields as of the last frame. `None` until they have been read.
+    fields: Option<Box<Fields>>,
+}
+
+/// Undo and redo for the editor.
+///
+/// Parameter changes are recorded at the `end_set_parameter` boundaries of the editor's
+/// [`GuiContext`], see [`UndoEditor`]. The note map and the patterns are edited directly by the
+/// editor instead, so they are compared against their previous state after every frame. The
+/// history is owned by the plugin, so it's kept while the editor is closed. It's cleared when the
+/// host loads a state.
+#[derive(Default)]
+pub(crate) struct UndoHistory {
+    history: Mutex<History>,
+    /// Set while an edit is being undone or redone, so it isn't recorded again
+    replaying: AtomicBool,
+    /// Set when the host loads a state. The history no longer applies to the new state, so it is
+    /// cleared.
+    resync: AtomicBool,
+}
+
+impl UndoHistory {
+    /// Lock the history, clearing it first if the host has loaded a state since
+    fn lock(&self) -> MutexGuard<'_, History> {
+        let mut history = self.history.lock();
+        if self.resync.swap(false, Ordering::Relaxed) {
+            *history = History::default();
+        }
+        history
+    }
+
+    fn begin_set_parameter(&self, param: ParamPtr) {
+        if self.replaying.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut history = self.lock();
+        if !history.gestures.iter().any(|x| x.param == param) {
+            history.gestures.push(Gesture {
+                param,
+                // SAFETY: the parameters belong to `MaltParams`, which outlives the editor
+                before: unsafe { param.unmodulated_normalized_value() },
+                after: None,
+            });
+        }
+    }
+
+    fn set_parameter_normalized(&self, param: ParamPtr, normalized: f32) {
+        if self.replaying.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut history = self.lock();
+        if let Some(gesture) = history.gestures.iter_mut().find(|x| x.param == param) {
+            gesture.after = Some(normalized);
+        }
+    }
+
+    fn end_set_parameter(&self, param: ParamPtr) {
+        if self.replaying.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut history = self.lock();
+        let Some(index) = history.gestures.iter().position(|x| x.param == param) else {
+            return;
+        };
+        let gesture = history.gestures.swap_remove(index);
+        if let Some(after) = gesture.after.filter(|x| *x != gesture.before) {
+            history.pending.push(Change::Param {
+                param,
+                before: gesture.before,
+                after,
+            });
+        }
+    }
+
+    /// Record the changes made during the last frame. While `idle` is false, e.g. while the mouse
+    /// is held down, the changes are collected into a single edit.
+    pub(crate) fn end_frame(&self, params: &MaltParams, idle: bool) {
+        if !idle {
+            return;
+        }
+
+        let mut history = self.lock();
+        // only copy the fields once they have changed
+        let changed = match &history.fields {
+            Some(previous) => !previous.matches(params),
+            None => true,
+        };
+        if changed {
+            let fields = Box::new(Fields::read(params));
+            if let Some(before) = history.fields.replace(fields.clone()) {
+                history.pending.push(Change::Fields {
+                    before,
+                    after: fields,
+                });
+            }
+        }
+
+        if !history.pending.is_empty() {
+            let edit = std::mem::take(&mut history.pending);
+            history.undo.push_back(edit);
+            if history.undo.len() > HISTORY_LIMIT {
+                history.undo.pop_front();
+            }
+            history.redo.clear();
+        }
+    }
+
+    /// Revert the last edit. Returns false if there is nothing to undo.
+    pub(crate) fn undo(&self, context: &dyn GuiContext, params: &MaltParams) -> bool {
+        let mut history = self.lock();
+        let Some(edit) = history.undo.pop_back() else {
+            return false;
+        };
+        self.apply(&mut history, &edit, true, context, params);
+        history.redo.push(edit);
+        true
+    }
+
+    /// Apply the last undone edit again. Returns false if there is nothing to redo.
+    pub(crate) fn redo(&self, context: &dyn GuiContext, params: &MaltParams) -> bool {
+        let mut history = self.lock();
+        let Some(edit) = history.redo.pop() else {
+            return false;
+        };
+        self.apply(&mut history, &edit, false, context, params);
+        history.undo.push_back(edit);
+        true
+    }
+
+    /// Set everything in the edit to its value from before (`undo`) or after the edit
+    fn apply(
+        &self,
+        history: &mut History,
+        edit: &[Change],
+        undo: bool,
+        context: &dyn GuiContext,
+        params: &MaltParams,
+    ) {
+        self.replaying.store(true, Ordering::Relaxed);
+        // undo in reverse order, so a parameter that was changed twice ends up at its first value
+        let mut changes: Vec<&Change> = edit.iter().collect();
+        if undo {
+            changes.reverse();
+        }
+        for change in changes {
+            match change {
+                Change::Param {
+                    param,
+                    before,
+                    after,
+                } => {
+                    let value = if undo { *before } else { *after };
+                    // SAFETY: the parameters belong to `MaltParams`, which outlives the editor
+                    unsafe {
+                        context.raw_begin_set_parameter(*param);
+                        context.raw_set_parameter_normalized(*param, value);
+                        context.raw_end_set_parameter(*param);
+                    }
+                }
+                Change::Fields { before, after } => {
+                    let fields = if undo { before } else { after };
+                    fields.write(params);
+                    // the fields are changed on purpose, don't record them as a new edit
+                    history.fields = Some(fields.clone());
+                }
+            }
+        }
+        self.replaying.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Passes everything on to the host's [`GuiContext`], while recording parameter changes
+struct UndoContext {
+    context: Arc<dyn GuiContext>,
+    history: Arc<UndoHistory>,
+}
+
+impl GuiContext for UndoContext {
+    fn plugin_api(&self) -> PluginApi {
+        self.context.plugin_api()
+    }
+
+    fn request_resize(&self) -> bool {
+        self.context.request_resize()
+    }
+
+    unsafe fn raw_begin_set_parameter(&self, param: ParamPtr) {
+        self.history.begin_set_parameter(param);
+        self.context.raw_begin_set_parameter(param);
+    }
+
+    unsafe fn raw_set_parameter_normalized(&self, param: ParamPtr, normalized: f32) {
+        self.history.set_parameter_normalized(param, normalized);
+        self.context.raw_set_parameter_normalized(param, normalized);
+    }
+
+    unsafe fn raw_end_set_parameter(&self, param: ParamPtr) {
+        self.context.raw_end_set_parameter(param);
+        self.history.end_set_parameter(param);
+    }
+
+    fn get_state(&self) -> PluginState {
+        self.context.get_state()
+    }
+
+    fn set_state(&self, state: PluginState) {
+        self.context.set_state(state)
+    }
+}
+
+/// Wraps an editor, so the changes it makes go through an [`UndoContext`]
+pub(crate) struct UndoEditor {
+    editor: Box<dyn Editor>,
+    history: Arc<UndoHistory>,
+}
+
+impl UndoEditor {
+    pub(crate) fn new(editor: Box<dyn Editor>, history: Arc<UndoHistory>) -> Self {
+        Self { editor, history }
+    }
+}
+
+impl Editor for UndoEditor {
+    fn spawn(
+        &self,
+        parent: ParentWindowHandle,
+        context: Arc<dyn GuiContext>,
+    ) -> Box<dyn Any + Send> {
+        let context = Arc::new(UndoContext {
+            context,
+            history: self.history.clone(),
+        });
+        self.editor.spawn(parent, context)
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.editor.size()
+    }
+
+    fn set_scale_factor(&self, factor: f32) -> bool {
+        self.editor.set_scale_factor(factor)
+    }
+
+    fn param_value_changed(&self, id: &str, normalized_value: f32) {
+        self.editor.param_value_changed(id, normalized_value)
+    }
+
+    fn param_modulation_changed(&self, id: &str, modulation_offset: f32) {
+        self.editor.param_modulation_changed(id, modulation_offset)
+    }
+
+    fn param_values_changed(&self) {
+        // the host has loaded a state
+        self.history.resync.store(true, Ordering::Relaxed);
+        self.editor.param_values_changed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// Records the values that get set, without changing the parameters
+    #[derive(Default)]
+    struct StubContext {
+        sets: Mutex<Vec<(ParamPtr, f32)>>,
+    }
+
+    impl GuiContext for StubContext {
+        fn plugin_api(&self) -> PluginApi {
+            PluginApi::Clap
+        }
+
+        fn request_resize(&self) -> bool {
+            false
+        }
+
+        unsafe fn raw_begin_set_parameter(&self, _param: ParamPtr) {}
+
+        unsafe fn raw_set_parameter_normalized(&self, param: ParamPtr, normalized: f32) {
+            self.sets.lock().push((param, normalized));
+        }
+
+        unsafe fn raw_end_set_parameter(&self, _param: ParamPtr) {}
+
+        fn get_state(&self) -> PluginState {
+            PluginState {
+                version: String::new(),
+                params: BTreeMap::new(),
+                fields: BTreeMap::new(),
+            }
+        }
+
+        fn set_state(&self, _state: PluginState) {}
+    }
+
+    /// Drag a parameter through the given values, like a widget would
+    fn drag(history: &UndoHistory, param: ParamPtr, values: &[f32]) {
+        history.begin_set_parameter(param);
+        for value in values {
+            history.set_parameter_normalized(param, *value);
+        }
+        history.end_set_parameter(param);
+    }
+
+    fn undo_len(history: &UndoHistory) -> usize {
+        history.lock().undo.len()
+    }
+
+    mod end_frame {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // changes are grouped into one edit until the mouse is released
+            let params = MaltParams::default();
+            let history = UndoHistory::default();
+            let a = params.channels[0].low_db.as_ptr();
+            let b = params.channels[0].mid_db.as_ptr();
+
+            history.end_frame(&params, true);
+            drag(&history, a, &[0.1, 0.2]);
+            history.end_frame(&params, false);
+            drag(&history, b, &[0.3]);
+            history.end_frame(&params, false);
+            assert_eq!(undo_len(&history), 0);
+            history.end_frame(&params, true);
+            assert_eq!(undo_len(&history), 1);
+            assert_eq!(history.lock().undo[0].len(), 2);
+        }
+
+        #[test]
+        fn test_02() {
+            // gestures that don't change anything aren't recorded
+            let params = MaltParams::default();
+            let history = UndoHistory::default();
+            let a = params.channels[0].low_db.as_ptr();
+            let before = unsafe { a.unmodulated_normalized_value() };
+
+            drag(&history, a, &[]);
+            drag(&history, a, &[0.8, before]);
+            history.end_frame(&params, true);
+            assert_eq!(undo_len(&history), 0);
+        }
+
+        #[test]
+        fn test_03() {
+            // the oldest edits are forgotten
+            let params = MaltParams::default();
+            let history = UndoHistory::default();
+            let a = params.channels[0].low_db.as_ptr();
+
+            for i in 0..HISTORY_LIMIT + 10 {
+                drag(&history, a, &[i as f32 / 1000.0 + 0.001]);
+                history.end_frame(&params, true);
+            }
+            assert_eq!(undo_len(&history), HISTORY_LIMIT);
+        }
+
+        #[test]
+        fn test_04() {
+            // persisted fields are recorded, and undoing them doesn't record a new edit
+            let params = MaltParams::default();
+            let history = UndoHistory::default();
+            let context = StubContext::default();
+
+            history.end_frame(&params, true);
+            params.note_map.write().unwrap().assign(36, 5, None);
+            history.end_frame(&params, true);
+            assert_eq!(undo_len(&history), 1);
+
+            let changed = *params.note_map.read().unwrap();
+            assert!(history.undo(&context, &params));
+            assert_ne!(*params.note_map.read().unwrap(), changed);
+            history.end_frame(&params, true);
+            assert_eq!(undo_len(&history), 0);
+
+            assert!(history.redo(&context, &params));
+            assert_eq!(*params.note_map.read().unwrap(), changed);
+        }
+
+        #[test]
+        fn test_05() {
+            // fields changed in the same frame become one edit, frames without changes add nothing
+            let params = MaltParams::default();
+            let history = UndoHistory::default();
+            let context = StubContext::default();
+            let patterns = params.morph_patterns.read().unwrap().clone();
+
+            history.end_frame(&params, true);
+            params.sequencer.write().unwrap().set_velocity(0, 0, 1.0);
+            params.morph_patterns.write().unwrap().bands[0][0].clear();
+            history.end_frame(&params, true);
+            history.end_frame(&params, true);
+            assert_eq!(undo_len(&history), 1);
+            assert_eq!(history.lock().undo[0].len(), 1);
+
+            assert!(history.undo(&context, &params));
+            assert_eq!(params.sequencer.read().unwrap().velocity(0, 0), 0.0);
+            assert_eq!(*params.morph_patterns.read().unwrap(), patterns);
+        }
+    }
+
+    mod undo {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // undo replays an edit in reverse order, redo in the original order
+            let params = MaltParams::default();
+            let history = UndoHistory::default();
+            let context = StubContext::default();
+            let a = params.channels[0].low_db.as_ptr();
+            let b = params.channels[0].mid_db.as_ptr();
+            let a_before = unsafe { a.unmodulated_normalized_value() };
+            let b_before = unsafe { b.unmodulated_normalized_value() };
+
+            drag(&history, a, &[0.1]);
+            drag(&history, b, &[0.2]);
+            history.end_frame(&params, true);
+
+            assert!(history.undo(&context, &params));
+            assert_eq!(*context.sets.lock(), [(b, b_before), (a, a_before)]);
+            context.sets.lock().clear();
+
+            assert!(history.redo(&context, &params));
+            assert_eq!(*context.sets.lock(), [(a, 0.1), (b, 0.2)]);
+            assert!(!history.redo(&context, &params));
+        }
+
+        #[test]
+        fn test_02() {
+            // a new edit clears the redo stack
+            let params = MaltParams::default();
+            let history = UndoHistory::default();
+            let context = StubContext::default();
+            let a = params.channels[0].low_db.as_ptr();
+
+            drag(&history, a, &[0.1]);
+            history.end_frame(&params, true);
+            assert!(history.undo(&context, &params));
+            drag(&history, a, &[0.2]);
+            history.end_frame(&params, true);
+            assert!(!history.redo(&context, &params));
+        }
+
+        #[test]
+        fn test_03() {
+            // the changes made while replaying aren't recorded
+            let params = MaltParams::default();
+            let a = params.channels[0].low_db.as_ptr();
+            let context = UndoContext {
+                context: Arc::new(StubContext::default()),
+                history: Arc::new(UndoHistory::default()),
+            };
+
+            drag(&context.history, a, &[0.1]);
+            context.history.end_frame(&params, true);
+            assert!(context.history.undo(&context, &params));
+            context.history.end_frame(&params, true);
+            assert_eq!(undo_len(&context.history), 0);
+            assert!(context.history.redo(&context, &params));
+        }
+
+        #[test]
+        fn test_04() {
+            // loading a state clears the history
+            let params = MaltParams::default();
+            let history = UndoHistory::default();
+            let context = StubContext::default();
+            let a = params.channels[0].low_db.as_ptr();
+
+            drag(&history, a, &[0.1]);
+            history.end_frame(&params, true);
+            assert!(history.undo(&context, &params));
+            drag(&history, a, &[0.2]);
+            history.end_frame(&params, true);
+
+            history.resync.store(true, Ordering::Relaxed);
+            assert!(!history.undo(&context, &params));
+            assert!(!history.redo(&context, &params));
+            assert_eq!(context.sets.lock().len(), 1);
+        }
+    }
+}
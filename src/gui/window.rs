@@ -0,0 +1,86 @@
+use nih_plug_egui::{
+    egui::{
+        pos2, CentralPanel, Context, Frame, Id, InnerResponse, Rect, Response, Sense, Ui,
+        UiBuilder, Vec2,
+    },
+    EguiState,
+};
+use std::hash::Hash;
+
+/// Same as nih_plug_egui's `ResizableWindow`, but it can be zoomed in and out.
+///
+/// egui lays everything out in points. Once the zoom factor isn't 1, a point is no longer one
+/// unit of the window's size, so the size requested by the resize corner is scaled by the zoom.
+pub(crate) struct ZoomableWindow {
+    id: Id,
+    /// Smallest size of the contents, in points
+    min_size: Vec2,
+}
+
+impl ZoomableWindow {
+    pub(crate) fn new(id_source: impl Hash) -> Self {
+        Self {
+            id: Id::new(id_source),
+            min_size: Vec2::splat(16.0),
+        }
+    }
+
+    pub(crate) fn min_size(mut self, min_size: impl Into<Vec2>) -> Self {
+        self.min_size = min_size.into();
+        self
+    }
+
+    pub(crate) fn show<R>(
+        self,
+        ctx: &Context,
+        egui_state: &EguiState,
+        zoom: f32,
+        add_contents: impl FnOnce(&mut Ui) -> R,
+    ) -> InnerResponse<R> {
+        CentralPanel::default()
+            .frame(Frame::none())
+            .show(ctx, move |ui| {
+                let ui_rect = ui.clip_rect();
+                let mut content_ui =
+                    ui.new_child(UiBuilder::new().max_rect(ui_rect).layout(*ui.layout()));
+                let ret = add_contents(&mut content_ui);
+
+                let corner_size = Vec2::splat(ui.visuals().resize_corner_size);
+                let corner_rect = Rect::from_min_size(ui_rect.max - corner_size, corner_size);
+                let corner_response =
+                    ui.interact(corner_rect, self.id.with("corner"), Sense::drag());
+                if corner_response.dragged() {
+                    if let Some(pointer_pos) = corner_response.interact_pointer_pos() {
+                        let desired_size = (pointer_pos - ui_rect.min
+                            + 0.5 * corner_response.rect.size())
+                        .max(self.min_size);
+                        let window_size = desired_size * zoom;
+                        egui_state.set_requested_size((
+                            window_size.x.round() as u32,
+                            window_size.y.round() as u32,
+                        ));
+                    }
+                }
+                paint_resize_corner(ui, &corner_response);
+
+                ret
+            })
+    }
+}
+
+/// The diagonal lines in the bottom right corner
+fn paint_resize_corner(ui: &Ui, response: &Response) {
+    let stroke = ui.style().interact(response).fg_stroke;
+    let painter = ui.painter();
+    // move away from the edges of the window
+    let rect = response.rect.translate(-Vec2::splat(2.0));
+    let corner = painter.round_pos_to_pixels(rect.max);
+    let mut w = 2.0;
+    while w <= rect.width() && w <= rect.height() {
+        painter.line_segment(
+            [pos2(corner.x - w, corner.y), pos2(corner.x, corner.y - w)],
+            stroke,
+        );
+        w += 4.0;
+    }
+}
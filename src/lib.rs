@@ -1,35 +1,72 @@
 mod biquad;
+mod capture;
 mod envelope;
 mod gui;
+mod meters;
+mod modulation;
+mod notemap;
 mod parameter_formatters;
 mod pattern;
+mod random;
+mod sequencer;
 mod splitter;
 mod svf;
 
-use biquad::{FirstOrderLP, FixedQFilter};
-use envelope::Curve;
+use arrayvec::ArrayVec;
+use biquad::{Complex, FirstOrderLP, FixedQFilter};
+use capture::{Captures, ScopeFrame, SCOPE_DECIMATION};
 use envelope::Envelope;
+use envelope::{Curve, CurveShape, ReleaseModel};
 use formatters::v2s_f32_rounded;
+use meters::{gain_range_to_db, BufferMeters, Meters};
+use modulation::{ModSlotParams, ModSources, MOD_SLOTS};
 use nih_plug::prelude::*;
 use nih_plug_egui::EguiState;
-use parameter_formatters::{s2v_f32_ms_then_s, v2s_f32_ms_then_s};
+use notemap::{LastNoteOn, NoteMap};
+use parameter_formatters::{
+    s2v_f32_ms_then_s, s2v_i32_zero_as_off, v2s_f32_ms_then_s, v2s_i32_zero_as_off,
+};
+use pattern::MorphPatterns;
+use random::Rng;
 use ringbuffer::{AllocRingBuffer, RingBuffer};
+use sequencer::{SequencerPattern, StepRate};
 use splitter::MinimumThreeBand12Slope;
 use splitter::MinimumThreeBand24Slope;
-use std::sync::atomic::AtomicU8;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
 use util::db_to_gain;
 
-const CROSSOVER_MIN_HZ: f32 = 10.0;
-const CROSSOVER_MAX_HZ: f32 = 20000.0;
+pub(crate) const CROSSOVER_MIN_HZ: f32 = 10.0;
+pub(crate) const CROSSOVER_MAX_HZ: f32 = 20000.0;
+/// The high crossover must be at least this many times the low crossover, i.e. 1 octave apart
+pub(crate) const CROSSOVER_MIN_RATIO: f32 = 2.0;
 const MAX_LATENCY_SECONDS: f32 = 0.01;
+const CHOKE_GROUPS: i32 = 8;
+pub(crate) const MAX_BOOST_DB: f32 = 24.0;
+
+/// Returns the low and high crossovers that are actually used by the splitter
+pub(crate) fn clamp_crossovers(low: f32, high: f32) -> [f32; 2] {
+    // since high crossover will be 1 octave above this, this cannot be too high
+    let low = low.min(CROSSOVER_MAX_HZ / CROSSOVER_MIN_RATIO);
+    // limit high crossover to be 1 octave above low crossover
+    // (this is pro-mb's behaviour)
+    let high = high.max(low * CROSSOVER_MIN_RATIO);
+    [low, high]
+}
 
-enum ThreeBandSplitter {
+pub(crate) enum ThreeBandSplitter {
     ThreeBand24(splitter::MinimumThreeBand24Slope),
     ThreeBand12(splitter::MinimumThreeBand12Slope),
 }
 
 impl ThreeBandSplitter {
+    pub(crate) fn new(slope: Slope, f1: f64, f2: f64, sr: f64) -> Self {
+        match slope {
+            Slope::F24 => Self::ThreeBand24(MinimumThreeBand24Slope::new(f1, f2, sr)),
+            Slope::F12 => Self::ThreeBand12(MinimumThreeBand12Slope::new(f1, f2, sr)),
+        }
+    }
+
     fn split_bands(&mut self, sample: f64) -> [f64; 3] {
         match self {
             ThreeBandSplitter::ThreeBand24(splitter) => splitter.split_bands(sample),
@@ -37,6 +74,14 @@ impl ThreeBandSplitter {
         }
     }
 
+    /// The complex gain of the low, mid and high bands at frequency `f`
+    pub(crate) fn frequency_response(&self, f: f64) -> [Complex; 3] {
+        match self {
+            ThreeBandSplitter::ThreeBand24(splitter) => splitter.frequency_response(f),
+            ThreeBandSplitter::ThreeBand12(splitter) => splitter.frequency_response(f),
+        }
+    }
+
     pub(crate) fn set_frequencies(&mut self, f1: f64, f2: f64) {
         nih_debug_assert!(f1 < f2, "f1 must be less than f2");
 
@@ -56,14 +101,165 @@ enum EnvelopeOverlapMode {
     Max,
 }
 
+/// What happens to a voice once its release countdown reaches zero
+#[derive(Clone, Copy)]
+enum PendingRelease {
+    /// The note has ended, the envelopes hold and release as usual
+    NoteOff,
+    /// The voice got choked by another channel, fade out over the given duration (in seconds)
+    Choke(f32),
+}
+
 struct BandLinkedVoice {
+    /// Increases with every triggered voice, used for finding the oldest voice
+    id: u64,
     channel: usize,
+    /// The MIDI note that triggered this voice, used for matching note-off events.
+    /// This is `None` for voices triggered by the sequencer.
+    note: Option<u8>,
+    /// The MIDI channel that triggered this voice, used for matching note-off events
+    midi_channel: u8,
+    /// Velocity of the note that triggered this voice, 0.0 -- 1.0
+    velocity: f32,
+    /// Random offset added to the gain reduction of each band, in dB
+    depth_offset: f32,
+    /// Random scale factor applied to the decay of each band
+    decay_scale: f32,
+    /// Whether this voice is being held by a note (in gate mode)
+    gated: bool,
+    /// Number of samples between a note-off and the release of this voice, this is the lookahead
+    /// plus the channel's trigger offset
+    note_off_delay: u32,
+    /// Number of samples until the voice gets released, this gets set on note-off or when the
+    /// voice gets choked
+    release_countdown: Option<(u32, PendingRelease)>,
+    /// Whether this voice has been choked, its release no longer follows the decay parameters
+    choked: bool,
+    /// The decays the envelopes were last updated with, in seconds
+    releases: [f32; 3],
     low: Envelope,
     mid: Envelope,
     high: Envelope,
 }
 
 impl BandLinkedVoice {
+    /// Release this voice once the note-off reaches the delayed audio.
+    /// Does nothing if the voice isn't held, or if it is already being released.
+    fn note_off(&mut self) {
+        if self.gated && self.release_countdown.is_none() {
+            self.release_countdown = Some((self.note_off_delay, PendingRelease::NoteOff));
+        }
+    }
+
+    /// Fade out this voice after the given number of samples, overriding any pending note-off.
+    /// Does nothing if the voice is already choked.
+    fn choke(&mut self, delay_samples: u32, choke_seconds: f32) {
+        let choke_pending = matches!(self.release_countdown, Some((_, PendingRelease::Choke(_))));
+        if !self.choked && !choke_pending {
+            self.release_countdown = Some((delay_samples, PendingRelease::Choke(choke_seconds)));
+        }
+    }
+
+    /// Advance the release countdown by one sample, releasing the envelopes once it reaches zero.
+    fn tick_release_countdown(&mut self) {
+        let Some((countdown, release)) = self.release_countdown.as_mut() else {
+            return;
+        };
+
+        if *countdown == 0 {
+            match *release {
+                PendingRelease::NoteOff => {
+                    self.low.set_sustain(false);
+                    self.mid.set_sustain(false);
+                    self.high.set_sustain(false);
+                }
+                PendingRelease::Choke(seconds) => {
+                    self.low.choke(seconds);
+                    self.mid.choke(seconds);
+                    self.high.choke(seconds);
+                    self.choked = true;
+                }
+            }
+            self.gated = false;
+            self.release_countdown = None;
+        } else {
+            *countdown -= 1;
+        }
+    }
+
+    /// Update the decay (in seconds) of the low, mid and high bands. Only the bands whose decay
+    /// changed are updated.
+    fn set_releases(&mut self, releases: [f32; 3]) {
+        let [low, mid, high] = releases;
+        if self.releases[0] != low {
+            self.low.set_release(low);
+        }
+        if self.releases[1] != mid {
+            self.mid.set_release(mid);
+        }
+        if self.releases[2] != high {
+            self.high.set_release(high);
+        }
+        self.releases = releases;
+    }
+
+    /// Update the attack (in seconds) of the low, mid and high bands. To keep the bands aligned,
+    /// the attacks are only updated if every band can accept the change.
+    fn set_attacks(&mut self, [low, mid, high]: [f32; 3]) {
+        if self.low.can_set_attack(low)
+            && self.mid.can_set_attack(mid)
+            && self.high.can_set_attack(high)
+        {
+            self.low.set_attack(low);
+            self.mid.set_attack(mid);
+            self.high.set_attack(high);
+        }
+    }
+
+    /// The current envelope values of the low, mid and high bands, 0.0 -- 1.0
+    fn values(&self) -> [f32; 3] {
+        [self.low.value(), self.mid.value(), self.high.value()]
+    }
+
+    /// The gain reduction of each band at the peak of the envelope, in dB.
+    /// Negative values are a boost.
+    fn peak_dbs(&self, channel_params: &ChannelParamValues) -> [f32; 3] {
+        let depth_scale = channel_params.velocity_depth_scale(self.velocity);
+        [
+            channel_params.low_db,
+            channel_params.mid_db,
+            channel_params.high_db,
+        ]
+        .map(|db| {
+            // humanization varies the amount of reduction or boost, it never flips the direction,
+            // and a band that is turned off stays off
+            if db == 0.0 {
+                return 0.0;
+            }
+            db.signum() * (db.abs() + self.depth_offset).max(0.0) * depth_scale
+        })
+    }
+
+    /// The decay of each band after velocity and humanization, in seconds
+    fn decays(&self, channel_params: &ChannelParamValues) -> [f32; 3] {
+        let decay_scale = channel_params.velocity_decay_scale(self.velocity) * self.decay_scale;
+        [
+            channel_params.low_decay * decay_scale,
+            channel_params.mid_decay * decay_scale,
+            channel_params.high_decay * decay_scale,
+        ]
+    }
+
+    /// The gain change this voice is currently applying on its loudest band, in dB (unsigned)
+    fn current_db(&self, channel_params: &ChannelParamValues) -> f32 {
+        let [low, mid, high] = self.values();
+        let [low_db, mid_db, high_db] = self.peak_dbs(channel_params);
+        (low * low_db)
+            .abs()
+            .max((mid * mid_db).abs())
+            .max((high * high_db).abs())
+    }
+
     /// Returns the lowest progress of all the envelopes
     fn progress(&self) -> f32 {
         self.low
@@ -106,7 +302,7 @@ impl GainSmoother {
 }
 
 #[derive(Enum, PartialEq, Eq, Clone, Copy)]
-enum Slope {
+pub(crate) enum Slope {
     #[id = "fixed_24"]
     #[name = "24 dB/octave"]
     F24,
@@ -126,12 +322,124 @@ enum MIDIProcessingMode {
     #[id = "channels"]
     #[name = "Channels"]
     Channel,
+    #[id = "map"]
+    #[name = "Note map"]
+    Map,
+}
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum TriggerMode {
+    /// Each note triggers the full envelope, ignoring how long the note is
+    #[id = "trigger"]
+    #[name = "Trigger"]
+    Trigger,
+    /// The envelope stays at full depth while the note is held, then holds and releases after
+    /// the note ends. Held notes are also released when the host stops playback
+    #[id = "gate"]
+    #[name = "Gate"]
+    Gate,
+}
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum OffsetMode {
+    #[id = "time"]
+    #[name = "Time"]
+    Time,
+    /// Offset in 64th notes, follows the host's tempo
+    #[id = "note"]
+    #[name = "Note"]
+    Note,
+}
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum StealMode {
+    #[id = "oldest"]
+    #[name = "Oldest"]
+    Oldest,
+    /// Steal the voice applying the least gain reduction
+    #[id = "quietest"]
+    #[name = "Quietest"]
+    Quietest,
+    /// Steal the voice with the highest progress
+    #[id = "nearest_to_finish"]
+    #[name = "Nearest to finish"]
+    NearestToFinish,
+}
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum RetriggerMode {
+    /// A voice that replaces another voice of the same channel starts from zero
+    #[id = "restart"]
+    #[name = "Restart"]
+    Restart,
+    /// A voice that replaces another voice of the same channel starts from its current level
+    #[id = "legato"]
+    #[name = "Legato"]
+    Legato,
+}
+
+/// Find the voice to replace, out of the voices that match the filter.
+/// Returns `None` if no voices match the filter.
+fn find_voice_to_steal(
+    voices: &[Option<BandLinkedVoice>; MAX_VOICES],
+    channels: &[ChannelParamValues; 16],
+    mode: StealMode,
+    filter: impl Fn(&BandLinkedVoice) -> bool,
+) -> Option<usize> {
+    let candidates = voices
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, opt)| opt.as_ref().filter(|voice| filter(voice)).map(|v| (idx, v)));
+
+    let stolen = match mode {
+        StealMode::Oldest => candidates.min_by_key(|(_, voice)| voice.id),
+        StealMode::Quietest => candidates.min_by(|(_, voice1), (_, voice2)| {
+            let db1 = voice1.current_db(&channels[voice1.channel]);
+            let db2 = voice2.current_db(&channels[voice2.channel]);
+            db1.total_cmp(&db2)
+        }),
+        StealMode::NearestToFinish => candidates
+            .max_by(|(_, voice1), (_, voice2)| voice1.progress().total_cmp(&voice2.progress())),
+    };
+
+    stolen.map(|(idx, _)| idx)
+}
+
+/// A note that triggers an envelope channel on the current sample
+struct Trigger {
+    channel: usize,
+    /// `None` for sequencer steps
+    note: Option<u8>,
+    midi_channel: u8,
+    /// Note velocity, 0.0 -- 1.0
+    velocity: f32,
+    /// Whether the note was also released on the same sample
+    released: bool,
+}
+
+/// Add a trigger to the triggers of the current sample.
+///
+/// Outside of gate mode a channel is only triggered once per sample, with the loudest velocity,
+/// so that chords don't stack identical voices. In gate mode every note keeps its own trigger,
+/// so that its note-off can release it.
+fn push_trigger(triggers: &mut ArrayVec<Trigger, MAX_VOICES>, trigger: Trigger, gated: bool) {
+    if !gated {
+        if let Some(existing) = triggers.iter_mut().find(|x| x.channel == trigger.channel) {
+            existing.velocity = existing.velocity.max(trigger.velocity);
+            return;
+        }
+    }
+
+    // if there are more notes than voices, the extra notes can be ignored
+    let _ = triggers.try_push(trigger);
 }
 
 #[derive(Params)]
 struct MaltParams {
     #[nested(array, group = "channels")]
     pub channels: [ChannelParams; 16],
+    #[nested(array, group = "modulation")]
+    pub modulation: [ModSlotParams; MOD_SLOTS],
 
     #[id = "low_crossover"]
     pub(crate) low_crossover: FloatParam,
@@ -165,6 +473,17 @@ struct MaltParams {
     #[id = "bypass_high"]
     pub(crate) bypass_high: BoolParam,
 
+    /// Gate mode, the bands are attenuated by their floor and open up on each trigger
+    #[id = "invert"]
+    pub(crate) invert: BoolParam,
+    /// Resting gain reduction in inverted mode, 0.0 -- 90.0
+    #[id = "low_floor"]
+    pub(crate) low_floor: FloatParam,
+    #[id = "mid_floor"]
+    pub(crate) mid_floor: FloatParam,
+    #[id = "high_floor"]
+    pub(crate) high_floor: FloatParam,
+
     #[id = "bypass"]
     pub(crate) bypass: BoolParam,
     #[id = "mix"]
@@ -174,6 +493,26 @@ struct MaltParams {
     pub(crate) midi_mode: EnumParam<MIDIProcessingMode>,
     #[id = "midi_root_note"]
     pub(crate) midi_root_note: IntParam,
+    #[id = "trigger_mode"]
+    pub(crate) trigger_mode: EnumParam<TriggerMode>,
+    /// Which voice gets replaced when there are no free voices
+    #[id = "voice_steal"]
+    pub(crate) voice_steal: EnumParam<StealMode>,
+    /// Seed for the probability and humanization, the random sequence restarts whenever
+    /// playback starts
+    #[id = "seed"]
+    pub(crate) seed: IntParam,
+
+    #[id = "seq_enabled"]
+    pub(crate) seq_enabled: BoolParam,
+    /// Number of steps in the sequencer pattern, 1 -- `MAX_STEPS`
+    #[id = "seq_steps"]
+    pub(crate) seq_steps: IntParam,
+    #[id = "seq_rate"]
+    pub(crate) seq_rate: EnumParam<StepRate>,
+    /// Delays every second step, 0.0 -- 1.0
+    #[id = "seq_swing"]
+    pub(crate) seq_swing: FloatParam,
 
     /// The editor state, saved together with the parameter state so the custom scaling can be
     /// restored.
@@ -182,12 +521,28 @@ struct MaltParams {
     /// The channel being edited on the UI
     #[persist = "editor-state-active-channel"]
     editor_state_active_channel: Arc<AtomicU8>,
+    /// The color theme of the editor
+    #[persist = "editor-theme"]
+    editor_theme: Arc<RwLock<gui::ThemeName>>,
+    /// The zoom of the editor in percent
+    #[persist = "editor-zoom"]
+    editor_zoom: Arc<AtomicU8>,
+    /// Used when the MIDI mode is set to "Note map"
+    #[persist = "note-map"]
+    pub(crate) note_map: Arc<RwLock<NoteMap>>,
+    /// The steps of the built-in sequencer
+    #[persist = "sequencer"]
+    pub(crate) sequencer: Arc<RwLock<SequencerPattern>>,
+    /// The A and B patterns of each band, used by the "Pattern morph" curves
+    #[persist = "morph-patterns"]
+    pub(crate) morph_patterns: Arc<RwLock<MorphPatterns>>,
 }
 
 impl Default for MaltParams {
     fn default() -> Self {
         Self {
             channels: Default::default(),
+            modulation: Default::default(),
 
             low_crossover: FloatParam::new(
                 "Low crossover",
@@ -236,6 +591,47 @@ impl Default for MaltParams {
             bypass_mid: BoolParam::new("Bypass mid", false),
             bypass_high: BoolParam::new("Bypass high", false),
 
+            invert: BoolParam::new("Invert", false),
+            low_floor: FloatParam::new(
+                "Low floor",
+                24.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 90.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_unit(" dB")
+            .with_value_to_string(v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+            mid_floor: FloatParam::new(
+                "Mid floor",
+                24.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 90.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_unit(" dB")
+            .with_value_to_string(v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+            high_floor: FloatParam::new(
+                "High floor",
+                24.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 90.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_unit(" dB")
+            .with_value_to_string(v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
             bypass: BoolParam::new("Bypass", false),
             mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
                 .with_value_to_string(formatters::v2s_f32_percentage(3))
@@ -250,9 +646,36 @@ impl Default for MaltParams {
                     max: 127 - 15, // 15 less than max, because note range is 0..=15
                 },
             ),
+            trigger_mode: EnumParam::new("Trigger mode", TriggerMode::Trigger),
+            voice_steal: EnumParam::new("Voice stealing", StealMode::NearestToFinish),
+            seed: IntParam::new("Random seed", 0, IntRange::Linear { min: 0, max: 9999 })
+                .non_automatable(),
+
+            seq_enabled: BoolParam::new("Sequencer", false),
+            seq_steps: IntParam::new(
+                "Sequencer steps",
+                16,
+                IntRange::Linear {
+                    min: 1,
+                    max: sequencer::MAX_STEPS as i32,
+                },
+            ),
+            seq_rate: EnumParam::new("Sequencer rate", StepRate::Sixteenth),
+            seq_swing: FloatParam::new(
+                "Sequencer swing",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(3))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
 
             editor_state: EguiState::from_size(gui::GUI_DEFAULT_WIDTH, gui::GUI_DEFAULT_HEIGHT),
             editor_state_active_channel: Default::default(), // default to 0
+            editor_theme: Default::default(),
+            editor_zoom: Arc::new(AtomicU8::new(100)),
+            note_map: Default::default(),
+            sequencer: Default::default(),
+            morph_patterns: Default::default(),
         }
     }
 }
@@ -279,6 +702,14 @@ impl MaltParams {
         let lookahead = self.lookahead.value() / 1000.0; // convert to seconds
         let midi_mode = self.midi_mode.value();
         let midi_root_note = self.midi_root_note.value() as u8;
+        let trigger_mode = self.trigger_mode.value();
+        let voice_steal = self.voice_steal.value();
+        let seed = self.seed.value() as u32;
+        let seq_enabled = self.seq_enabled.value();
+        let seq_steps = self.seq_steps.value() as usize;
+        let seq_rate = self.seq_rate.value();
+        let seq_swing = self.seq_swing.value();
+        let invert = self.invert.value();
         let solo_low = self.solo_low.value();
         let solo_mid = self.solo_mid.value();
         let solo_high = self.solo_high.value();
@@ -297,6 +728,14 @@ impl MaltParams {
             lookahead,
             midi_mode,
             midi_root_note,
+            trigger_mode,
+            voice_steal,
+            seed,
+            seq_enabled,
+            seq_steps,
+            seq_rate,
+            seq_swing,
+            invert,
             solo_low,
             solo_mid,
             solo_high,
@@ -311,21 +750,16 @@ impl MaltParams {
     }
 
     fn next(&self, lookahead: f32) -> MaltParamsNexts {
-        let low_crossover = {
-            let value = self.low_crossover.smoothed.next();
-            // since high crossover will be 1 octave above this, this cannot be too high
-            value.min(CROSSOVER_MAX_HZ / 2.0)
-        };
-        let high_crossover = {
-            // limit high crossover to be 1 octave above low crossover
-            // (this is pro-mb's behaviour)
-            let value = self.high_crossover.smoothed.next();
-            let min_value = low_crossover * 2.0;
-            value.max(min_value)
-        };
+        let [low_crossover, high_crossover] = clamp_crossovers(
+            self.low_crossover.smoothed.next(),
+            self.high_crossover.smoothed.next(),
+        );
 
         let bypass = self.bypass.value();
         let mix = self.mix.smoothed.next();
+        let low_floor = self.low_floor.smoothed.next();
+        let mid_floor = self.mid_floor.smoothed.next();
+        let high_floor = self.high_floor.smoothed.next();
 
         let channels: [ChannelParamValues; 16] =
             self.channels.each_ref().map(|param| param.next(lookahead));
@@ -336,6 +770,9 @@ impl MaltParams {
             high_crossover,
             bypass,
             mix,
+            low_floor,
+            mid_floor,
+            high_floor,
         }
     }
 }
@@ -347,6 +784,14 @@ struct MaltParamValues {
     lookahead: f32,
     midi_mode: MIDIProcessingMode,
     midi_root_note: u8,
+    trigger_mode: TriggerMode,
+    voice_steal: StealMode,
+    seed: u32,
+    seq_enabled: bool,
+    seq_steps: usize,
+    seq_rate: StepRate,
+    seq_swing: f32,
+    invert: bool,
     solo_low: bool,
     solo_mid: bool,
     solo_high: bool,
@@ -359,16 +804,42 @@ struct MaltParamValues {
     output_bands: [bool; 3],
 }
 
+impl MaltParamValues {
+    /// Find the envelope channels that a MIDI note should be routed to, based on the MIDI mode.
+    /// Returns a bitmask where bit 0 is channel 0.
+    fn note_channels(&self, note_map: &NoteMap, note: u8, midi_channel: u8) -> u16 {
+        match &self.midi_mode {
+            MIDIProcessingMode::Omni => 1,
+            MIDIProcessingMode::Pitch => {
+                let range = self.midi_root_note..=(self.midi_root_note + 15);
+                if range.contains(&note) {
+                    1 << (note - self.midi_root_note)
+                } else {
+                    0
+                }
+            }
+            MIDIProcessingMode::Channel => 1 << (midi_channel & 0xf),
+            MIDIProcessingMode::Map => note_map.channels_for(note, midi_channel),
+        }
+    }
+}
+
 struct MaltParamsNexts {
     channels: [ChannelParamValues; 16],
     low_crossover: f32,
     high_crossover: f32,
     bypass: bool,
     mix: f32,
+    /// in dB
+    low_floor: f32,
+    /// in dB
+    mid_floor: f32,
+    /// in dB
+    high_floor: f32,
 }
 
 #[derive(Params)]
-struct ChannelParams {
+pub(crate) struct ChannelParams {
     #[id = "low_precomp"]
     pub(crate) low_precomp: FloatParam,
     #[id = "mid_precomp"]
@@ -376,6 +847,13 @@ struct ChannelParams {
     #[id = "high_precomp"]
     pub(crate) high_precomp: FloatParam,
 
+    #[id = "low_hold"]
+    pub(crate) low_hold: FloatParam,
+    #[id = "mid_hold"]
+    pub(crate) mid_hold: FloatParam,
+    #[id = "high_hold"]
+    pub(crate) high_hold: FloatParam,
+
     #[id = "low_decay"]
     pub(crate) low_decay: FloatParam,
     #[id = "mid_decay"]
@@ -383,13 +861,91 @@ struct ChannelParams {
     #[id = "high_decay"]
     pub(crate) high_decay: FloatParam,
 
-    // gain, 0.0 -- 90.0
+    #[id = "low_attack_curve"]
+    pub(crate) low_attack_curve: EnumParam<CurveShape>,
+    #[id = "mid_attack_curve"]
+    pub(crate) mid_attack_curve: EnumParam<CurveShape>,
+    #[id = "high_attack_curve"]
+    pub(crate) high_attack_curve: EnumParam<CurveShape>,
+    #[id = "low_release_curve"]
+    pub(crate) low_release_curve: EnumParam<CurveShape>,
+    #[id = "mid_release_curve"]
+    pub(crate) mid_release_curve: EnumParam<CurveShape>,
+    #[id = "high_release_curve"]
+    pub(crate) high_release_curve: EnumParam<CurveShape>,
+    /// Analog channels are monophonic, and retrigger from the current level
+    #[id = "release_model"]
+    pub(crate) release_model: EnumParam<ReleaseModel>,
+    /// Warps both the attack and release curves, -1.0 -- 1.0
+    #[id = "low_curvature"]
+    pub(crate) low_curvature: FloatParam,
+    #[id = "mid_curvature"]
+    pub(crate) mid_curvature: FloatParam,
+    #[id = "high_curvature"]
+    pub(crate) high_curvature: FloatParam,
+
+    // gain, -24.0 -- 90.0, negative values boost
     #[id = "low_db"]
     pub(crate) low_db: FloatParam,
     #[id = "mid_db"]
     pub(crate) mid_db: FloatParam,
     #[id = "high_db"]
     pub(crate) high_db: FloatParam,
+
+    /// How much velocity affects the gain reduction, 0.0 -- 1.0
+    #[id = "velocity_depth"]
+    pub(crate) velocity_depth: FloatParam,
+    /// How much velocity affects the decay, 0.0 -- 1.0
+    #[id = "velocity_decay"]
+    pub(crate) velocity_decay: FloatParam,
+    /// Curve applied to the velocity, -1.0 -- 1.0. Positive values make soft notes softer,
+    /// negative values make them louder, 0.0 is linear.
+    #[id = "velocity_curve"]
+    pub(crate) velocity_curve: FloatParam,
+    /// How much velocity affects the pattern morph, 0.0 -- 1.0
+    #[id = "velocity_morph"]
+    pub(crate) velocity_morph: FloatParam,
+
+    /// Blend between the A and B patterns, 0.0 -- 1.0. Only used by the "Pattern morph" curves.
+    #[id = "low_morph"]
+    pub(crate) low_morph: FloatParam,
+    #[id = "mid_morph"]
+    pub(crate) mid_morph: FloatParam,
+    #[id = "high_morph"]
+    pub(crate) high_morph: FloatParam,
+
+    /// Triggering this channel chokes the other channels in the same group, 0 for no group
+    #[id = "choke_group"]
+    pub(crate) choke_group: IntParam,
+    /// How long this channel takes to fade out when it gets choked
+    #[id = "choke_time"]
+    pub(crate) choke_time: FloatParam,
+
+    /// Maximum number of overlapping voices for this channel
+    #[id = "polyphony"]
+    pub(crate) polyphony: IntParam,
+    #[id = "retrigger"]
+    pub(crate) retrigger: EnumParam<RetriggerMode>,
+
+    /// Chance that a note triggers this channel, 0.0 -- 1.0
+    #[id = "probability"]
+    pub(crate) probability: FloatParam,
+    /// Maximum random variation of the gain reduction, in dB
+    #[id = "humanize_depth"]
+    pub(crate) humanize_depth: FloatParam,
+    /// Maximum random variation of the decay, 0.0 -- 1.0
+    #[id = "humanize_decay"]
+    pub(crate) humanize_decay: FloatParam,
+
+    /// Moves the envelope peak after (positive) or before (negative) the note
+    #[id = "offset_mode"]
+    pub(crate) offset_mode: EnumParam<OffsetMode>,
+    #[id = "offset_time"]
+    pub(crate) offset_time: FloatParam,
+    /// In 64th notes. Negative offsets duck earlier, but never earlier than the lookahead allows,
+    /// see [`Envelope::from_latency()`]
+    #[id = "offset_notes"]
+    pub(crate) offset_notes: IntParam,
 }
 
 impl Default for ChannelParams {
@@ -426,6 +982,40 @@ impl Default for ChannelParams {
             .with_value_to_string(v2s_f32_ms_then_s(4))
             .with_string_to_value(s2v_f32_ms_then_s()),
 
+            low_hold: FloatParam::new(
+                "Low hold",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(4))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+            mid_hold: FloatParam::new(
+                "Mid hold",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(4))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+            high_hold: FloatParam::new(
+                "High hold",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(4))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
             low_decay: FloatParam::new(
                 "Low decay",
                 100.0,
@@ -463,13 +1053,49 @@ impl Default for ChannelParams {
             .with_value_to_string(v2s_f32_ms_then_s(4))
             .with_string_to_value(s2v_f32_ms_then_s()),
 
+            low_attack_curve: EnumParam::new("Low attack curve", CurveShape::EaseInSine),
+            mid_attack_curve: EnumParam::new("Mid attack curve", CurveShape::EaseInSine),
+            high_attack_curve: EnumParam::new("High attack curve", CurveShape::EaseInSine),
+            low_release_curve: EnumParam::new("Low release curve", CurveShape::EaseInOutSine),
+            mid_release_curve: EnumParam::new("Mid release curve", CurveShape::EaseInOutSine),
+            high_release_curve: EnumParam::new("High release curve", CurveShape::EaseInOutSine),
+            release_model: EnumParam::new("Release model", ReleaseModel::TimeBounded),
+            low_curvature: FloatParam::new(
+                "Low curvature",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(v2s_f32_rounded(2)),
+            mid_curvature: FloatParam::new(
+                "Mid curvature",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(v2s_f32_rounded(2)),
+            high_curvature: FloatParam::new(
+                "High curvature",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(v2s_f32_rounded(2)),
+
             low_db: FloatParam::new(
                 "Low gain reduction",
                 0.0,
-                FloatRange::Skewed {
-                    min: 0.0,
+                FloatRange::SymmetricalSkewed {
+                    min: -MAX_BOOST_DB,
                     max: 90.0,
                     factor: FloatRange::skew_factor(-1.0),
+                    center: 0.0,
                 },
             )
             .with_unit(" dB")
@@ -478,10 +1104,11 @@ impl Default for ChannelParams {
             mid_db: FloatParam::new(
                 "Mid gain reduction",
                 0.0,
-                FloatRange::Skewed {
-                    min: 0.0,
+                FloatRange::SymmetricalSkewed {
+                    min: -MAX_BOOST_DB,
                     max: 90.0,
                     factor: FloatRange::skew_factor(-1.0),
+                    center: 0.0,
                 },
             )
             .with_unit(" dB")
@@ -490,50 +1117,241 @@ impl Default for ChannelParams {
             high_db: FloatParam::new(
                 "High gain reduction",
                 0.0,
-                FloatRange::Skewed {
-                    min: 0.0,
+                FloatRange::SymmetricalSkewed {
+                    min: -MAX_BOOST_DB,
                     max: 90.0,
                     factor: FloatRange::skew_factor(-1.0),
+                    center: 0.0,
                 },
             )
             .with_unit(" dB")
             .with_value_to_string(v2s_f32_rounded(2))
             .with_string_to_value(s2v_f32_ms_then_s()),
+
+            velocity_depth: FloatParam::new(
+                "Velocity to depth",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(3))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            velocity_decay: FloatParam::new(
+                "Velocity to decay",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(3))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            velocity_curve: FloatParam::new(
+                "Velocity curve",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(v2s_f32_rounded(2)),
+            velocity_morph: FloatParam::new(
+                "Velocity to morph",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(3))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            low_morph: FloatParam::new("Low morph", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(10.0))
+                .with_value_to_string(formatters::v2s_f32_percentage(3))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            mid_morph: FloatParam::new("Mid morph", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(10.0))
+                .with_value_to_string(formatters::v2s_f32_percentage(3))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            high_morph: FloatParam::new(
+                "High morph",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_value_to_string(formatters::v2s_f32_percentage(3))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            choke_group: IntParam::new(
+                "Choke group",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: CHOKE_GROUPS,
+                },
+            )
+            .with_value_to_string(v2s_i32_zero_as_off())
+            .with_string_to_value(s2v_i32_zero_as_off()),
+            choke_time: FloatParam::new(
+                "Choke time",
+                10.0,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: 500.0,
+                    factor: FloatRange::skew_factor(-1.6),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(4))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
+            polyphony: IntParam::new(
+                "Polyphony",
+                MAX_VOICES as i32,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_VOICES as i32,
+                },
+            ),
+            retrigger: EnumParam::new("Retrigger", RetriggerMode::Restart),
+
+            probability: FloatParam::new(
+                "Probability",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(3))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            humanize_depth: FloatParam::new(
+                "Humanize depth",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 12.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(v2s_f32_rounded(2)),
+            humanize_decay: FloatParam::new(
+                "Humanize decay",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(3))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            offset_mode: EnumParam::new("Offset mode", OffsetMode::Time),
+            offset_time: FloatParam::new(
+                "Offset",
+                0.0,
+                FloatRange::Linear {
+                    // negative offsets can't be earlier than the lookahead
+                    min: -MAX_LATENCY_SECONDS * 1000.0,
+                    max: 100.0,
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(4))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+            offset_notes: IntParam::new("Offset (notes)", 0, IntRange::Linear { min: -4, max: 16 })
+                .with_unit("/64"),
         }
     }
 }
 
 impl ChannelParams {
     fn next(&self, latency_seconds: f32) -> ChannelParamValues {
+        self.read(latency_seconds, |param| param.smoothed.next())
+    }
+
+    /// The current values without smoothing, for use outside of the audio thread
+    pub(crate) fn value(&self, latency_seconds: f32) -> ChannelParamValues {
+        self.read(latency_seconds, |param| param.value())
+    }
+
+    /// Read the values of all parameters, `float` reads the value of a single float parameter
+    fn read(&self, latency_seconds: f32, float: impl Fn(&FloatParam) -> f32) -> ChannelParamValues {
         let low_precomp = {
-            let value = self.low_precomp.smoothed.next() / 1000.0;
+            let value = float(&self.low_precomp) / 1000.0;
             value.min(latency_seconds)
         };
         let mid_precomp = {
-            let value = self.mid_precomp.smoothed.next() / 1000.0;
+            let value = float(&self.mid_precomp) / 1000.0;
             value.min(latency_seconds)
         };
         let high_precomp = {
-            let value = self.high_precomp.smoothed.next() / 1000.0;
+            let value = float(&self.high_precomp) / 1000.0;
             value.min(latency_seconds)
         };
-        let low_decay = self.low_decay.smoothed.next() / 1000.0;
-        let mid_decay = self.mid_decay.smoothed.next() / 1000.0;
-        let high_decay = self.high_decay.smoothed.next() / 1000.0;
-        let low_db = self.low_db.smoothed.next();
-        let mid_db = self.mid_db.smoothed.next();
-        let high_db = self.high_db.smoothed.next();
+        let low_hold = float(&self.low_hold) / 1000.0;
+        let mid_hold = float(&self.mid_hold) / 1000.0;
+        let high_hold = float(&self.high_hold) / 1000.0;
+        let low_decay = float(&self.low_decay) / 1000.0;
+        let mid_decay = float(&self.mid_decay) / 1000.0;
+        let high_decay = float(&self.high_decay) / 1000.0;
+        let low_attack_curve = self.low_attack_curve.value();
+        let mid_attack_curve = self.mid_attack_curve.value();
+        let high_attack_curve = self.high_attack_curve.value();
+        let low_release_curve = self.low_release_curve.value();
+        let mid_release_curve = self.mid_release_curve.value();
+        let high_release_curve = self.high_release_curve.value();
+        let release_model = self.release_model.value();
+        let low_curvature = float(&self.low_curvature);
+        let mid_curvature = float(&self.mid_curvature);
+        let high_curvature = float(&self.high_curvature);
+        let low_db = float(&self.low_db);
+        let mid_db = float(&self.mid_db);
+        let high_db = float(&self.high_db);
+        let velocity_depth = float(&self.velocity_depth);
+        let velocity_decay = float(&self.velocity_decay);
+        let velocity_curve = float(&self.velocity_curve);
+        let velocity_morph = float(&self.velocity_morph);
+        let low_morph = float(&self.low_morph);
+        let mid_morph = float(&self.mid_morph);
+        let high_morph = float(&self.high_morph);
+        let choke_group = self.choke_group.value();
+        let choke_time = float(&self.choke_time) / 1000.0;
+        let polyphony = self.polyphony.value() as usize;
+        let retrigger = self.retrigger.value();
+        let probability = float(&self.probability);
+        let humanize_depth = float(&self.humanize_depth);
+        let humanize_decay = float(&self.humanize_decay);
+        let offset_mode = self.offset_mode.value();
+        let offset_time = float(&self.offset_time) / 1000.0;
+        let offset_notes = self.offset_notes.value();
 
         ChannelParamValues {
             low_precomp,
             mid_precomp,
             high_precomp,
+            low_hold,
+            mid_hold,
+            high_hold,
             low_decay,
             mid_decay,
             high_decay,
+            low_attack_curve,
+            mid_attack_curve,
+            high_attack_curve,
+            low_release_curve,
+            mid_release_curve,
+            high_release_curve,
+            release_model,
+            low_curvature,
+            mid_curvature,
+            high_curvature,
             low_db,
             mid_db,
             high_db,
+            velocity_depth,
+            velocity_decay,
+            velocity_curve,
+            velocity_morph,
+            low_morph,
+            mid_morph,
+            high_morph,
+            choke_group,
+            choke_time,
+            polyphony,
+            retrigger,
+            probability,
+            humanize_depth,
+            humanize_decay,
+            offset_mode,
+            offset_time,
+            offset_notes,
         }
     }
 }
@@ -546,6 +1364,13 @@ pub(crate) struct ChannelParamValues {
     /// Precomp is in seconds
     pub(crate) high_precomp: f32,
 
+    /// Hold is in seconds
+    pub(crate) low_hold: f32,
+    /// Hold is in seconds
+    pub(crate) mid_hold: f32,
+    /// Hold is in seconds
+    pub(crate) high_hold: f32,
+
     /// Decay is in seconds
     pub(crate) low_decay: f32,
     /// Decay is in seconds
@@ -553,15 +1378,205 @@ pub(crate) struct ChannelParamValues {
     /// Decay is in seconds
     pub(crate) high_decay: f32,
 
-    /// Gain in dB, 0.0 -- +90.0
+    pub(crate) low_attack_curve: CurveShape,
+    pub(crate) mid_attack_curve: CurveShape,
+    pub(crate) high_attack_curve: CurveShape,
+    pub(crate) low_release_curve: CurveShape,
+    pub(crate) mid_release_curve: CurveShape,
+    pub(crate) high_release_curve: CurveShape,
+    pub(crate) release_model: ReleaseModel,
+    /// -1.0 -- 1.0
+    pub(crate) low_curvature: f32,
+    /// -1.0 -- 1.0
+    pub(crate) mid_curvature: f32,
+    /// -1.0 -- 1.0
+    pub(crate) high_curvature: f32,
+
+    /// Gain reduction in dB, -24.0 -- +90.0, negative values boost
     pub(crate) low_db: f32,
-    /// Gain in dB, 0.0 -- +90.0
+    /// Gain reduction in dB, -24.0 -- +90.0, negative values boost
     pub(crate) mid_db: f32,
-    /// Gain in dB, 0.0 -- +90.0
+    /// Gain reduction in dB, -24.0 -- +90.0, negative values boost
     pub(crate) high_db: f32,
+
+    /// How much velocity affects the gain reduction, 0.0 -- 1.0
+    pub(crate) velocity_depth: f32,
+    /// How much velocity affects the decay, 0.0 -- 1.0
+    pub(crate) velocity_decay: f32,
+    /// Curve applied to the velocity, -1.0 -- 1.0
+    pub(crate) velocity_curve: f32,
+    /// How much velocity affects the pattern morph, 0.0 -- 1.0
+    pub(crate) velocity_morph: f32,
+
+    /// 0.0 -- 1.0
+    pub(crate) low_morph: f32,
+    /// 0.0 -- 1.0
+    pub(crate) mid_morph: f32,
+    /// 0.0 -- 1.0
+    pub(crate) high_morph: f32,
+
+    /// 0 for no choke group
+    pub(crate) choke_group: i32,
+    /// Choke time is in seconds
+    pub(crate) choke_time: f32,
+
+    /// 1 -- `MAX_VOICES`
+    pub(crate) polyphony: usize,
+    pub(crate) retrigger: RetriggerMode,
+
+    /// 0.0 -- 1.0
+    pub(crate) probability: f32,
+    /// Humanize depth is in dB
+    pub(crate) humanize_depth: f32,
+    /// 0.0 -- 1.0
+    pub(crate) humanize_decay: f32,
+
+    pub(crate) offset_mode: OffsetMode,
+    /// Offset time is in seconds
+    pub(crate) offset_time: f32,
+    /// In 64th notes
+    pub(crate) offset_notes: i32,
+}
+
+impl ChannelParamValues {
+    /// Map a velocity (0.0 -- 1.0) to a scale factor (0.0 -- 1.0) for a given sensitivity.
+    ///
+    /// Positive curves make soft notes even softer, negative curves make them louder.
+    fn velocity_scale(velocity: f32, curve: f32, sensitivity: f32) -> f32 {
+        let curved = velocity.clamp(0.0, 1.0).powf(4.0_f32.powf(curve));
+        1.0 - sensitivity * (1.0 - curved)
+    }
+
+    /// Scale factor to apply to the gain reduction of a note with the given velocity
+    fn velocity_depth_scale(&self, velocity: f32) -> f32 {
+        Self::velocity_scale(velocity, self.velocity_curve, self.velocity_depth)
+    }
+
+    /// The trigger offset in seconds, for the given tempo in BPM
+    fn offset_seconds(&self, tempo: f64) -> f32 {
+        match self.offset_mode {
+            OffsetMode::Time => self.offset_time,
+            OffsetMode::Note => {
+                // a 64th note is a 16th of a beat
+                let beats = self.offset_notes as f64 / 16.0;
+                (beats * 60.0 / tempo) as f32
+            }
+        }
+    }
+
+    /// Scale factor to apply to the decay of a note with the given velocity
+    fn velocity_decay_scale(&self, velocity: f32) -> f32 {
+        Self::velocity_scale(velocity, self.velocity_curve, self.velocity_decay)
+    }
+
+    /// Create the envelopes of the low, mid and high bands for a new note
+    fn envelopes(
+        &self,
+        sample_rate: f32,
+        lookahead: f32,
+        offset: f32,
+        decays: [f32; 3],
+        patterns: &MorphPatterns,
+        morphs: [f32; 3],
+    ) -> [Envelope; 3] {
+        let precomps = [self.low_precomp, self.mid_precomp, self.high_precomp];
+        let holds = [self.low_hold, self.mid_hold, self.high_hold];
+        let attack_curves = [
+            self.low_attack_curve,
+            self.mid_attack_curve,
+            self.high_attack_curve,
+        ];
+        let release_curves = [
+            self.low_release_curve,
+            self.mid_release_curve,
+            self.high_release_curve,
+        ];
+        let curvatures = [self.low_curvature, self.mid_curvature, self.high_curvature];
+
+        std::array::from_fn(|band| {
+            let mut envelope = Envelope::from_latency(
+                sample_rate,
+                lookahead,
+                offset,
+                precomps[band],
+                holds[band],
+                decays[band],
+                Curve::with_patterns(
+                    attack_curves[band],
+                    curvatures[band],
+                    &patterns.bands[band],
+                    morphs[band],
+                ),
+                Curve::with_patterns(
+                    release_curves[band],
+                    curvatures[band],
+                    &patterns.bands[band],
+                    morphs[band],
+                ),
+            );
+            envelope.set_release_model(self.release_model);
+            envelope
+        })
+    }
+
+    /// Render the gain reduction of a full-velocity note without humanization, for the editor.
+    ///
+    /// Returns the gain reduction in dB of the low, mid and high bands, sampled at
+    /// [`PREVIEW_SAMPLE_RATE`]. The note is received at the first sample, and plays at
+    /// `lookahead` (in seconds).
+    pub(crate) fn preview(
+        &self,
+        lookahead: f32,
+        tempo: f64,
+        patterns: &MorphPatterns,
+    ) -> [Vec<f32>; 3] {
+        let mut envelopes = self.envelopes(
+            PREVIEW_SAMPLE_RATE,
+            lookahead,
+            self.offset_seconds(tempo),
+            [self.low_decay, self.mid_decay, self.high_decay],
+            patterns,
+            self.morphs(1.0),
+        );
+        let dbs = [self.low_db, self.mid_db, self.high_db];
+
+        let max_samples = (PREVIEW_MAX_SECONDS * PREVIEW_SAMPLE_RATE) as usize;
+        let mut rv: [Vec<f32>; 3] = Default::default();
+        for _ in 0..max_samples {
+            if envelopes.iter().all(|envelope| envelope.is_complete()) {
+                break;
+            }
+            for band in 0..3 {
+                let value = envelopes[band].tick().unwrap_or(0.0);
+                rv[band].push(value * dbs[band]);
+            }
+        }
+        rv
+    }
+
+    /// The pattern morph of the low, mid and high bands for a note with the given velocity.
+    /// Softer notes morph towards pattern A.
+    fn morphs(&self, velocity: f32) -> [f32; 3] {
+        let scale = Self::velocity_scale(velocity, self.velocity_curve, self.velocity_morph);
+        [self.low_morph, self.mid_morph, self.high_morph].map(|x| (x * scale).clamp(0.0, 1.0))
+    }
 }
 
 const MAX_VOICES: usize = 32;
+/// Sample rate of the envelope preview in the editor
+pub(crate) const PREVIEW_SAMPLE_RATE: f32 = 1000.0;
+/// Longest envelope preview, in seconds
+const PREVIEW_MAX_SECONDS: f32 = 4.0;
+
+/// Returns whichever value is furthest from zero
+#[inline(always)]
+pub(crate) fn max_magnitude(a: f32, b: f32) -> f32 {
+    if b.abs() > a.abs() {
+        b
+    } else {
+        a
+    }
+}
 
 pub struct Malt {
     params: Arc<MaltParams>,
@@ -570,7 +1585,36 @@ pub struct Malt {
     max_latency_samples: usize,
     // audio processing stuff:
     voices: [Option<BandLinkedVoice>; MAX_VOICES],
-    current_releases: [[f32; 3]; MAX_VOICES],
+    /// The id of the next triggered voice
+    next_voice_id: u64,
+    /// Used for the trigger probability and humanization
+    rng: Rng,
+    /// The seed that `rng` was last seeded with
+    current_seed: u32,
+    /// Whether the transport was playing on the previous buffer
+    was_playing: bool,
+    current_attacks: [[f32; 3]; MAX_VOICES],
+    mod_sources: ModSources,
+    /// Copy of the note map, so it can be used while the editor is modifying it
+    note_map: NoteMap,
+    /// Shared with the editor for MIDI learn
+    last_note_on: Arc<LastNoteOn>,
+    /// Shared with the editor for metering
+    meters: Arc<Meters>,
+    /// Shared with the editor for the spectrum analyzer and scope
+    captures: Arc<Captures>,
+    /// The editor's undo history, kept here so it survives closing the editor
+    undo_history: Arc<gui::UndoHistory>,
+    /// The scope frame that is currently being accumulated
+    scope_frame: ScopeFrame,
+    /// Gain range of each band in the current scope frame as `(min, max)`
+    scope_gain_ranges: [(f32, f32); 3],
+    /// Number of samples in the current scope frame
+    scope_samples: usize,
+    /// Copy of the sequencer pattern, so it can be used while the editor is modifying it
+    sequencer: SequencerPattern,
+    /// Copy of the morph patterns, so they can be used while the editor is modifying them
+    morph_patterns: MorphPatterns,
     smoother: Option<GainSmoother>,
     splitter_l: ThreeBandSplitter,
     splitter_r: ThreeBandSplitter,
@@ -589,7 +1633,22 @@ impl Default for Malt {
             max_latency_samples: 0,
             current_slope: Slope::F24,
             voices: [const { None }; MAX_VOICES],
-            current_releases: [[0.0; 3]; MAX_VOICES],
+            next_voice_id: 0,
+            rng: Rng::new(0),
+            current_seed: 0,
+            was_playing: false,
+            current_attacks: [[0.0; 3]; MAX_VOICES],
+            mod_sources: ModSources::default(),
+            note_map: NoteMap::empty(),
+            last_note_on: Default::default(),
+            meters: Default::default(),
+            captures: Default::default(),
+            undo_history: Default::default(),
+            scope_frame: Default::default(),
+            scope_gain_ranges: [(1.0, 1.0); 3],
+            scope_samples: 0,
+            sequencer: Default::default(),
+            morph_patterns: Default::default(),
             smoother: None,
             splitter_l: ThreeBandSplitter::ThreeBand24(MinimumThreeBand24Slope::new(0.0, 0.0, 0.0)),
             splitter_r: ThreeBandSplitter::ThreeBand24(MinimumThreeBand24Slope::new(0.0, 0.0, 0.0)),
@@ -648,41 +1707,31 @@ impl Plugin for Malt {
         };
         self.latency_buf_r = self.latency_buf_l.clone();
 
+        self.captures.sample_rate.store(self.sr, Ordering::Relaxed);
+
         true
     }
 
     fn reset(&mut self) {
         // setup filters
         self.current_slope = self.params.crossover_slope.value();
-        match self.current_slope {
-            Slope::F24 => {
-                self.splitter_l = ThreeBandSplitter::ThreeBand24(MinimumThreeBand24Slope::new(
-                    1000.0,
-                    2000.0,
-                    self.sr.into(),
-                ));
-                self.splitter_r = ThreeBandSplitter::ThreeBand24(MinimumThreeBand24Slope::new(
-                    1000.0,
-                    2000.0,
-                    self.sr.into(),
-                ));
-            }
-            Slope::F12 => {
-                self.splitter_l = ThreeBandSplitter::ThreeBand12(MinimumThreeBand12Slope::new(
-                    1000.0,
-                    2000.0,
-                    self.sr.into(),
-                ));
-                self.splitter_r = ThreeBandSplitter::ThreeBand12(MinimumThreeBand12Slope::new(
-                    1000.0,
-                    2000.0,
-                    self.sr.into(),
-                ));
-            }
-        }
+        self.splitter_l =
+            ThreeBandSplitter::new(self.current_slope, 1000.0, 2000.0, self.sr.into());
+        self.splitter_r =
+            ThreeBandSplitter::new(self.current_slope, 1000.0, 2000.0, self.sr.into());
 
         // clear all envelopes
         self.voices = [const { None }; MAX_VOICES];
+        self.meters.reset();
+        self.captures.input.clear();
+        self.captures.output.clear();
+        self.scope_frame = Default::default();
+        self.scope_gain_ranges = [(1.0, 1.0); 3];
+        self.scope_samples = 0;
+
+        // restart the random sequence, so that renders are reproducible
+        self.current_seed = self.params.seed.value() as u32;
+        self.rng.reseed(self.current_seed);
     }
 
     fn process(
@@ -696,32 +1745,61 @@ impl Plugin for Malt {
         let sample_rate = ctx.transport().sample_rate;
         let param_values = self.params.value();
 
+        // the editor may be holding the lock, if so just keep using the old map
+        if let Ok(note_map) = self.params.note_map.try_read() {
+            self.note_map = *note_map;
+        }
+        if let Ok(sequencer) = self.params.sequencer.try_read() {
+            self.sequencer = *sequencer;
+        }
+        if let Ok(morph_patterns) = self.params.morph_patterns.try_read() {
+            // patterns have a fixed capacity, so this doesn't allocate
+            self.morph_patterns.clone_from(&morph_patterns);
+        }
+
         // handle crossover slope change
         {
             if param_values.crossover_slope != self.current_slope {
                 // replace splitters with new slopes
                 self.current_slope = param_values.crossover_slope;
-                match self.current_slope {
-                    Slope::F24 => {
-                        self.splitter_l = ThreeBandSplitter::ThreeBand24(
-                            MinimumThreeBand24Slope::new(1000.0, 2000.0, sample_rate.into()),
-                        );
-                        self.splitter_r = ThreeBandSplitter::ThreeBand24(
-                            MinimumThreeBand24Slope::new(1000.0, 2000.0, sample_rate.into()),
-                        );
-                    }
-                    Slope::F12 => {
-                        self.splitter_l = ThreeBandSplitter::ThreeBand12(
-                            MinimumThreeBand12Slope::new(1000.0, 2000.0, sample_rate.into()),
-                        );
-                        self.splitter_r = ThreeBandSplitter::ThreeBand12(
-                            MinimumThreeBand12Slope::new(1000.0, 2000.0, sample_rate.into()),
-                        );
-                    }
-                }
+                self.splitter_l =
+                    ThreeBandSplitter::new(self.current_slope, 1000.0, 2000.0, sample_rate.into());
+                self.splitter_r =
+                    ThreeBandSplitter::new(self.current_slope, 1000.0, 2000.0, sample_rate.into());
             }
         }
 
+        // restart the random sequence when playback starts or the seed changes
+        let playing = ctx.transport().playing;
+        if param_values.seed != self.current_seed || (playing && !self.was_playing) {
+            self.current_seed = param_values.seed;
+            self.rng.reseed(self.current_seed);
+        }
+        // hosts don't always send note-offs when playback stops, so release all held voices to
+        // avoid hanging notes
+        if !playing && self.was_playing {
+            for voice in self.voices.iter_mut().flatten() {
+                voice.note_off();
+            }
+        }
+        self.was_playing = playing;
+
+        // used for trigger offsets in note lengths
+        let tempo = ctx.transport().tempo.unwrap_or(120.0);
+
+        // position of the host while it is playing, as `(position, beats per sample)`
+        let song_clock = {
+            let transport = ctx.transport();
+            match (transport.pos_beats(), transport.tempo) {
+                (Some(pos_beats), Some(tempo)) if playing => {
+                    Some((pos_beats, tempo / 60.0 / sample_rate as f64))
+                }
+                _ => None,
+            }
+        };
+        // the sequencer only runs while the host is playing
+        let sequencer_clock = song_clock.filter(|_| param_values.seq_enabled);
+
         // handle smoothing change
         if param_values.smoothing && self.smoother.is_none() {
             self.smoother = Some(GainSmoother::new(sample_rate as f64));
@@ -755,130 +1833,311 @@ impl Plugin for Malt {
 
         let mut next_event = ctx.next_event();
 
+        // gain range of each band as `(min, max)`, and the absolute input and output peaks
+        let mut gain_ranges = [(1.0f32, 1.0f32); 3];
+        let mut input_peak: f32 = 0.0;
+        let mut output_peak: f32 = 0.0;
+        let mut channel_triggers: u16 = 0;
+        let gated = matches!(param_values.trigger_mode, TriggerMode::Gate);
+
         for (sample_id, mut channel_samples) in buffer.iter_samples().enumerate() {
-            let params = self.params.next(param_values.lookahead);
+            let mut params = self.params.next(param_values.lookahead);
+            modulation::apply_modulation(
+                &self.params.modulation,
+                &self.mod_sources,
+                &mut params.channels,
+            );
 
             // handle MIDI events
-            let mut channel_triggered: [bool; 16] = [false; 16];
+            let mut triggers: ArrayVec<Trigger, MAX_VOICES> = ArrayVec::new();
             while let Some(event) = next_event {
                 if event.timing() != sample_id as u32 {
                     break;
                 }
 
-                if let NoteEvent::NoteOn { channel, note, .. } = event {
-                    let channel: Option<usize> = match &param_values.midi_mode {
-                        MIDIProcessingMode::Omni => Some(0),
-                        MIDIProcessingMode::Pitch => {
-                            let range =
-                                param_values.midi_root_note..=(param_values.midi_root_note + 15);
-                            if range.contains(&note) {
-                                Some((note - param_values.midi_root_note) as usize)
-                            } else {
-                                None
+                match event {
+                    NoteEvent::NoteOn {
+                        channel: midi_channel,
+                        note,
+                        velocity,
+                        ..
+                    } => {
+                        self.last_note_on.store(note, midi_channel);
+
+                        let channels =
+                            param_values.note_channels(&self.note_map, note, midi_channel);
+                        for channel in (0..16).filter(|i| channels & (1 << i) != 0) {
+                            let trigger = Trigger {
+                                channel,
+                                note: Some(note),
+                                midi_channel,
+                                velocity,
+                                released: false,
+                            };
+                            push_trigger(&mut triggers, trigger, gated);
+                        }
+                    }
+                    // chokes end the note just like a note-off, so gated voices don't hang
+                    NoteEvent::NoteOff {
+                        channel: midi_channel,
+                        note,
+                        ..
+                    }
+                    | NoteEvent::Choke {
+                        channel: midi_channel,
+                        note,
+                        ..
+                    } => {
+                        // the note may have started on this same sample, without a voice yet
+                        let mut released_pending_trigger = false;
+                        for trigger in triggers.iter_mut() {
+                            if !trigger.released
+                                && trigger.note == Some(note)
+                                && trigger.midi_channel == midi_channel
+                            {
+                                trigger.released = true;
+                                released_pending_trigger = true;
                             }
                         }
-                        MIDIProcessingMode::Channel => Some(channel as usize),
-                    };
 
-                    if let Some(channel) = channel {
-                        channel_triggered[channel] = true;
+                        if !released_pending_trigger {
+                            // delay the release by the lookahead, so that it lines up with
+                            // the delayed audio
+                            for voice in self.voices.iter_mut().flatten() {
+                                if voice.note == Some(note) && voice.midi_channel == midi_channel {
+                                    voice.note_off();
+                                }
+                            }
+                        }
+                    }
+                    NoteEvent::MidiCC { cc, value, .. } => {
+                        self.mod_sources.set_cc(cc, value);
+                    }
+                    NoteEvent::MidiChannelPressure { pressure, .. } => {
+                        self.mod_sources.set_channel_pressure(pressure);
+                    }
+                    NoteEvent::PolyPressure {
+                        channel: midi_channel,
+                        note,
+                        pressure,
+                        ..
+                    } => {
+                        let channels =
+                            param_values.note_channels(&self.note_map, note, midi_channel);
+                        for channel in (0..16).filter(|i| channels & (1 << i) != 0) {
+                            self.mod_sources.set_poly_pressure(channel, pressure);
+                        }
                     }
+                    _ => (),
                 }
 
                 next_event = ctx.next_event();
             }
 
+            // trigger the sequencer steps that start on this sample
+            if let Some((pos_beats, beats_per_sample)) = sequencer_clock {
+                let beat = pos_beats + sample_id as f64 * beats_per_sample;
+                let step = sequencer::step_starting_at(
+                    beat,
+                    beats_per_sample,
+                    param_values.seq_rate.beats(),
+                    param_values.seq_swing as f64,
+                    param_values.seq_steps,
+                );
+                if let Some(step) = step {
+                    for channel in 0..16 {
+                        let velocity = self.sequencer.velocity(channel, step);
+                        if velocity <= 0.0 {
+                            continue;
+                        }
+
+                        // steps have no length, so they release immediately in gate mode
+                        let trigger = Trigger {
+                            channel,
+                            note: None,
+                            midi_channel: 0,
+                            velocity,
+                            released: true,
+                        };
+                        push_trigger(&mut triggers, trigger, gated);
+                    }
+                }
+            }
+
             // update existing envelopes (if any)
-            for voice in self.voices.iter_mut() {
+            for (voice_idx, voice) in self.voices.iter_mut().enumerate() {
                 let Some(voice) = voice else {
                     continue;
                 };
 
-                // update releases of voices
-                let ChannelParamValues {
-                    low_decay: new_low,
-                    mid_decay: new_mid,
-                    high_decay: new_high,
-                    ..
-                } = params.channels[voice.channel];
-                let [current_low, current_mid, current_high] =
-                    &mut self.current_releases[voice.channel];
-
-                if *current_low != new_low {
-                    voice.low.set_release(new_low);
-                    *current_low = new_low;
+                // release gated voices whose notes have ended, and fade out choked voices
+                voice.tick_release_countdown();
+                if voice.choked {
+                    continue;
                 }
 
-                if *current_mid != new_mid {
-                    voice.mid.set_release(new_mid);
-                    *current_mid = new_low;
-                }
+                // update the pattern morph of voices
+                let channel_params = &params.channels[voice.channel];
+                let [low_morph, mid_morph, high_morph] = channel_params.morphs(voice.velocity);
+                voice.low.set_morph(low_morph);
+                voice.mid.set_morph(mid_morph);
+                voice.high.set_morph(high_morph);
 
-                if *current_high != new_high {
-                    voice.high.set_release(new_high);
-                    *current_high = new_high;
+                // update releases of voices
+                let releases = voice.decays(channel_params);
+                voice.set_releases(releases);
+
+                // update attacks of voices that haven't started attacking yet
+                let new_attacks = [
+                    channel_params.low_precomp,
+                    channel_params.mid_precomp,
+                    channel_params.high_precomp,
+                ];
+                if self.current_attacks[voice_idx] != new_attacks {
+                    voice.set_attacks(new_attacks);
+                    self.current_attacks[voice_idx] = new_attacks;
                 }
             }
 
-            // trigger notes in envelope
-            for (channel, triggered) in channel_triggered.iter().enumerate() {
-                if !triggered {
+            // drop triggers based on each channel's probability
+            triggers.retain(|trigger| {
+                self.rng.next_f32() < params.channels[trigger.channel].probability
+            });
+
+            // mark where the triggers land in the delayed audio, for the scope
+            if !triggers.is_empty() {
+                let frames_ahead =
+                    (self.scope_samples + lookahead_samples as usize) / SCOPE_DECIMATION;
+                self.captures
+                    .scope
+                    .push_trigger(self.captures.scope.frame_count() + frames_ahead);
+            }
+            for trigger in triggers.iter() {
+                channel_triggers |= 1 << trigger.channel;
+            }
+
+            // choke voices of other channels in the same choke group, this is delayed by the
+            // lookahead (and trigger offset) so that it lines up with the delayed audio
+            for trigger in triggers.iter() {
+                let trigger_params = &params.channels[trigger.channel];
+                let choke_group = trigger_params.choke_group;
+                if choke_group == 0 {
                     continue;
                 }
 
-                let insertion_idx = {
-                    // insert into an empty cell
-                    match self.voices.iter().position(|x| x.is_none()) {
-                        Some(idx) => idx,
-                        // if no empty cells, find the envelope that's closest to finishing
-                        None => {
-                            self.voices
-                                .iter()
-                                .enumerate()
-                                .max_by(|(_, opt1), (_, opt2)| match (opt1, opt2) {
-                                    (Some(voice1), Some(voice2)) => {
-                                        voice1.progress().total_cmp(&voice2.progress())
-                                    }
-                                    _ => unreachable!(),
-                                })
-                                .expect("envelope lane must have size of at least 1")
-                                .0
-                        }
+                let offset = trigger_params.offset_seconds(tempo);
+                let choke_delay = ((param_values.lookahead + offset) * sample_rate)
+                    .round()
+                    .max(0.0) as u32;
+                for voice in self.voices.iter_mut().flatten() {
+                    let voice_params = &params.channels[voice.channel];
+                    if voice.channel != trigger.channel && voice_params.choke_group == choke_group {
+                        voice.choke(choke_delay, voice_params.choke_time);
                     }
-                };
+                }
+            }
 
-                let voice = BandLinkedVoice {
+            // trigger notes in envelope
+            for trigger in triggers.iter() {
+                let channel = trigger.channel;
+                let channel_params = &params.channels[channel];
+
+                let channel_voice_count = self
+                    .voices
+                    .iter()
+                    .flatten()
+                    .filter(|voice| voice.channel == channel)
+                    .count();
+                // analog channels behave like a compressor, with a single voice that retriggers
+                // from its current level
+                let analog = channel_params.release_model == ReleaseModel::Analog;
+                let polyphony = if analog { 1 } else { channel_params.polyphony };
+                let legato = analog || channel_params.retrigger == RetriggerMode::Legato;
+
+                let insertion_idx = if channel_voice_count >= polyphony {
+                    // the channel is at its polyphony limit, replace one of its own voices
+                    find_voice_to_steal(
+                        &self.voices,
+                        &params.channels,
+                        param_values.voice_steal,
+                        |voice| voice.channel == channel,
+                    )
+                } else {
+                    // insert into an empty cell, otherwise replace any voice
+                    self.voices.iter().position(|x| x.is_none()).or_else(|| {
+                        find_voice_to_steal(
+                            &self.voices,
+                            &params.channels,
+                            param_values.voice_steal,
+                            |_| true,
+                        )
+                    })
+                }
+                .expect("envelope lane must have size of at least 1");
+
+                let offset = channel_params.offset_seconds(tempo);
+                let note_off_delay = ((param_values.lookahead + offset) * sample_rate)
+                    .round()
+                    .max(0.0) as u32;
+
+                // randomize the depth and decay of each hit
+                let depth_offset = self.rng.next_bipolar() * channel_params.humanize_depth;
+                let humanize_decay_scale =
+                    1.0 + self.rng.next_bipolar() * channel_params.humanize_decay;
+                let decay_scale =
+                    channel_params.velocity_decay_scale(trigger.velocity) * humanize_decay_scale;
+                let releases = [
+                    channel_params.low_decay * decay_scale,
+                    channel_params.mid_decay * decay_scale,
+                    channel_params.high_decay * decay_scale,
+                ];
+                let [low, mid, high] = channel_params.envelopes(
+                    sample_rate,
+                    param_values.lookahead,
+                    offset,
+                    releases,
+                    &self.morph_patterns,
+                    channel_params.morphs(trigger.velocity),
+                );
+                let mut voice = BandLinkedVoice {
+                    id: self.next_voice_id,
                     channel,
-                    low: Envelope::from_latency(
-                        sample_rate,
-                        param_values.lookahead,
-                        params.channels[channel].low_precomp,
-                        params.channels[channel].low_decay,
-                        Curve::EaseInSine,
-                        Curve::EaseInOutSine,
-                    ),
-                    mid: Envelope::from_latency(
-                        sample_rate,
-                        param_values.lookahead,
-                        params.channels[channel].mid_precomp,
-                        params.channels[channel].mid_decay,
-                        Curve::EaseInSine,
-                        Curve::EaseInOutSine,
-                    ),
-                    high: Envelope::from_latency(
-                        sample_rate,
-                        param_values.lookahead,
-                        params.channels[channel].high_precomp,
-                        params.channels[channel].high_decay,
-                        Curve::EaseInSine,
-                        Curve::EaseInOutSine,
-                    ),
+                    note: trigger.note,
+                    midi_channel: trigger.midi_channel,
+                    velocity: trigger.velocity,
+                    depth_offset,
+                    decay_scale: humanize_decay_scale,
+                    gated,
+                    note_off_delay,
+                    release_countdown: None,
+                    choked: false,
+                    releases,
+                    low,
+                    mid,
+                    high,
                 };
+                self.next_voice_id += 1;
+                // legato retriggers continue from the envelopes of the replaced voice
+                if let Some(replaced) = &self.voices[insertion_idx] {
+                    if replaced.channel == channel && legato {
+                        voice.low.continue_from(&replaced.low);
+                        voice.mid.continue_from(&replaced.mid);
+                        voice.high.continue_from(&replaced.high);
+                    }
+                }
+                if gated {
+                    voice.low.set_sustain(true);
+                    voice.mid.set_sustain(true);
+                    voice.high.set_sustain(true);
+                    if trigger.released {
+                        voice.note_off();
+                    }
+                }
                 self.voices[insertion_idx] = Some(voice);
-                self.current_releases[insertion_idx] = [
-                    params.channels[channel].low_decay,
-                    params.channels[channel].mid_decay,
-                    params.channels[channel].high_decay,
+                self.current_attacks[insertion_idx] = [
+                    channel_params.low_precomp,
+                    channel_params.mid_precomp,
+                    channel_params.high_precomp,
                 ];
             }
 
@@ -909,10 +2168,12 @@ impl Plugin for Malt {
                         let env_mid = voice.mid.tick().unwrap_or(0.0);
                         let env_high = voice.high.tick().unwrap_or(0.0);
 
-                        // db gain amount, positive, e.g. +12dB
-                        let db_low = env_low * params.channels[voice.channel].low_db;
-                        let db_mid = env_mid * params.channels[voice.channel].mid_db;
-                        let db_high = env_high * params.channels[voice.channel].high_db;
+                        // db gain amount, positive for reduction, e.g. +12dB
+                        let channel_params = &params.channels[voice.channel];
+                        let [peak_low, peak_mid, peak_high] = voice.peak_dbs(channel_params);
+                        let db_low = env_low * peak_low;
+                        let db_mid = env_mid * peak_mid;
+                        let db_high = env_high * peak_high;
 
                         [db_low, db_mid, db_high]
                     })
@@ -926,9 +2187,14 @@ impl Plugin for Malt {
                 //     }
                 // }
 
+                // reductions and boosts are combined by taking the largest magnitude
                 let rv = iter
                     .reduce(|[a_low, a_mid, a_high], [b_low, b_mid, b_high]| {
-                        [a_low.max(b_low), a_mid.max(b_mid), a_high.max(b_high)]
+                        [
+                            max_magnitude(a_low, b_low),
+                            max_magnitude(a_mid, b_mid),
+                            max_magnitude(a_high, b_high),
+                        ]
                     })
                     .unwrap_or([0.0, 0.0, 0.0]);
 
@@ -950,6 +2216,18 @@ impl Plugin for Malt {
                 rv
             };
 
+            // in inverted mode, the bands rest at their floor and the envelopes open them up.
+            // a depth past the floor fully opens the band, it never boosts it
+            let [low_db, mid_db, high_db] = if param_values.invert {
+                [
+                    (params.low_floor - low_db).max(0.0),
+                    (params.mid_floor - mid_db).max(0.0),
+                    (params.high_floor - high_db).max(0.0),
+                ]
+            } else {
+                [low_db, mid_db, high_db]
+            };
+
             // convert gain to scalar
             let mut low_gain = if param_values.bypass_low {
                 1.0
@@ -978,14 +2256,26 @@ impl Plugin for Malt {
             let mid_gain = calculate_final_gain(mid_gain, params.mix, params.bypass);
             let high_gain = calculate_final_gain(high_gain, params.mix, params.bypass);
 
+            for ((min, max), gain) in gain_ranges.iter_mut().zip([low_gain, mid_gain, high_gain]) {
+                *min = min.min(gain as f32);
+                *max = max.max(gain as f32);
+            }
+
             let latency_buf_offset = self.max_latency_samples - lookahead_samples as usize;
 
+            // mono sums for the spectrum analyzer, the input is captured after the latency so
+            // that it lines up with the output
+            let mut input_mono: f32 = 0.0;
+            let mut output_mono: f32 = 0.0;
+
             // left channel
             {
                 let sample = channel_samples.get_mut(0).unwrap();
+                input_peak = input_peak.max(sample.abs());
 
                 // the sample from eons ago (the latency)
                 let delayed_sample = *self.latency_buf_l.get(latency_buf_offset).unwrap();
+                input_mono += delayed_sample * 0.5;
                 // push sample to buffer queue
                 self.latency_buf_l.push(*sample);
 
@@ -1005,14 +2295,18 @@ impl Plugin for Malt {
                     }
                     rv as f32
                 };
+                output_peak = output_peak.max(sample.abs());
+                output_mono += *sample * 0.5;
             }
 
             // right channel
             {
                 let sample = channel_samples.get_mut(1).unwrap();
+                input_peak = input_peak.max(sample.abs());
 
                 // the sample from eons ago (the latency)
                 let delayed_sample = *self.latency_buf_r.get(latency_buf_offset).unwrap();
+                input_mono += delayed_sample * 0.5;
                 // push sample to buffer queue
                 self.latency_buf_r.push(*sample);
 
@@ -1032,9 +2326,68 @@ impl Plugin for Malt {
                     }
                     rv as f32
                 };
+                output_peak = output_peak.max(sample.abs());
+                output_mono += *sample * 0.5;
+            }
+
+            self.captures.input.push(input_mono);
+            self.captures.output.push(output_mono);
+
+            // accumulate the scope frame, and publish it once it is full
+            {
+                if self.scope_samples == 0 {
+                    // the delayed audio is heard at an earlier position in the song
+                    self.scope_frame.beat = match song_clock {
+                        Some((pos_beats, beats_per_sample)) => {
+                            let beat = pos_beats + sample_id as f64 * beats_per_sample
+                                - lookahead_samples as f64 * beats_per_sample;
+                            beat as f32
+                        }
+                        None => f32::NAN,
+                    };
+                }
+
+                let frame = &mut self.scope_frame;
+                frame.input = frame.input.max(input_mono.abs());
+                frame.output = frame.output.max(output_mono.abs());
+                for ((min, max), gain) in self
+                    .scope_gain_ranges
+                    .iter_mut()
+                    .zip([low_gain, mid_gain, high_gain])
+                {
+                    *min = min.min(gain as f32);
+                    *max = max.max(gain as f32);
+                }
+
+                self.scope_samples += 1;
+                if self.scope_samples == SCOPE_DECIMATION {
+                    for (band, (min, max)) in self.scope_gain_ranges.into_iter().enumerate() {
+                        frame.gain_reduction[band] = gain_range_to_db(min, max);
+                    }
+                    self.captures.scope.push_frame(frame);
+
+                    *frame = Default::default();
+                    self.scope_gain_ranges = [(1.0, 1.0); 3];
+                    self.scope_samples = 0;
+                }
             }
         }
 
+        // publish meters to the editor
+        {
+            let mut meters = BufferMeters {
+                input_peak,
+                output_peak,
+                channel_triggers,
+                ..Default::default()
+            };
+            for (band, (min, max)) in gain_ranges.into_iter().enumerate() {
+                meters.set_gain_range(band, min, max);
+            }
+            let active_voices = self.voices.iter().filter(|x| x.is_some()).count();
+            self.meters.publish(&meters, active_voices as u32);
+        }
+
         ProcessStatus::Normal
     }
 
@@ -1061,3 +2414,156 @@ impl Vst3Plugin for Malt {
 
 nih_export_clap!(Malt);
 nih_export_vst3!(Malt);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voice(decay: f32) -> BandLinkedVoice {
+        let envelope = || {
+            Envelope::new(
+                100.0,
+                0.0,
+                0.0,
+                0.0,
+                decay,
+                Curve::new(CurveShape::EaseInSine, 0.0),
+                Curve::new(CurveShape::EaseInSine, 0.0),
+            )
+        };
+        BandLinkedVoice {
+            id: 0,
+            channel: 0,
+            note: None,
+            midi_channel: 0,
+            velocity: 1.0,
+            depth_offset: 0.0,
+            decay_scale: 1.0,
+            gated: false,
+            note_off_delay: 0,
+            release_countdown: None,
+            choked: false,
+            releases: [decay; 3],
+            low: envelope(),
+            mid: envelope(),
+            high: envelope(),
+        }
+    }
+
+    mod note_off {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // gated voices are released once the delay has passed
+            let mut voice = voice(1.0);
+            voice.gated = true;
+            voice.note_off_delay = 2;
+            voice.note_off();
+            voice.tick_release_countdown();
+            voice.tick_release_countdown();
+            assert!(voice.gated);
+            voice.tick_release_countdown();
+            assert!(!voice.gated);
+            assert!(voice.release_countdown.is_none());
+        }
+
+        #[test]
+        fn test_02() {
+            // voices that aren't held ignore note-offs, even repeated ones
+            let mut voice = voice(1.0);
+            voice.note_off();
+            voice.note_off();
+            assert!(voice.release_countdown.is_none());
+        }
+
+        #[test]
+        fn test_03() {
+            // a second note-off doesn't restart the countdown
+            let mut voice = voice(1.0);
+            voice.gated = true;
+            voice.note_off_delay = 2;
+            voice.note_off();
+            voice.tick_release_countdown();
+            voice.note_off();
+            voice.tick_release_countdown();
+            voice.tick_release_countdown();
+            assert!(!voice.gated);
+        }
+    }
+
+    mod push_trigger {
+        use super::*;
+
+        fn trigger(channel: usize, note: u8, velocity: f32) -> Trigger {
+            Trigger {
+                channel,
+                note: Some(note),
+                midi_channel: 0,
+                velocity,
+                released: false,
+            }
+        }
+
+        #[test]
+        fn test_01() {
+            // a chord on the same channel triggers it once, with the loudest velocity
+            let mut triggers = ArrayVec::new();
+            push_trigger(&mut triggers, trigger(0, 36, 0.5), false);
+            push_trigger(&mut triggers, trigger(0, 38, 0.8), false);
+            push_trigger(&mut triggers, trigger(0, 40, 0.2), false);
+            push_trigger(&mut triggers, trigger(1, 42, 0.3), false);
+            assert_eq!(triggers.len(), 2);
+            assert_eq!(triggers[0].channel, 0);
+            assert_eq!(triggers[0].velocity, 0.8);
+            assert_eq!(triggers[1].channel, 1);
+        }
+
+        #[test]
+        fn test_02() {
+            // in gate mode every note keeps its own trigger
+            let mut triggers = ArrayVec::new();
+            push_trigger(&mut triggers, trigger(0, 36, 0.5), true);
+            push_trigger(&mut triggers, trigger(0, 38, 0.8), true);
+            assert_eq!(triggers.len(), 2);
+            assert_eq!(triggers[0].note, Some(36));
+            assert_eq!(triggers[1].note, Some(38));
+        }
+
+        #[test]
+        fn test_03() {
+            // extra notes are dropped once every voice is used
+            let mut triggers = ArrayVec::new();
+            for note in 0..(MAX_VOICES as u8 + 4) {
+                push_trigger(&mut triggers, trigger(0, note, 1.0), true);
+            }
+            assert_eq!(triggers.len(), MAX_VOICES);
+        }
+    }
+
+    mod set_releases {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // every band gets its own decay
+            let mut voice = voice(1.0);
+            voice.set_releases([0.5, 2.0, 3.0]);
+            assert_eq!(voice.low.duration_samples(), 50.0);
+            assert_eq!(voice.mid.duration_samples(), 200.0);
+            assert_eq!(voice.high.duration_samples(), 300.0);
+        }
+
+        #[test]
+        fn test_02() {
+            // voices on the same channel keep their own decays
+            let mut a = voice(1.0);
+            let mut b = voice(1.0);
+            a.set_releases([0.5; 3]);
+            b.set_releases([2.0; 3]);
+            a.set_releases([0.5; 3]);
+            assert_eq!(a.mid.duration_samples(), 50.0);
+            assert_eq!(b.mid.duration_samples(), 200.0);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Meter values shared between the audio thread and the editor.
+//!
+//! The audio thread accumulates the extremes of every buffer and merges them into these atomics.
+//! The editor takes the values once per frame, which resets them, so short peaks between two
+//! frames are never missed.
+
+use crate::max_magnitude;
+use atomic_float::AtomicF32;
+use std::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+
+/// Meter readings of a single buffer, accumulated on the audio thread
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BufferMeters {
+    /// Gain reduction of each band in dB, negative values are boosts. Stores the value with the
+    /// largest magnitude.
+    pub(crate) gain_reduction: [f32; 3],
+    /// Absolute peak of the input, linear gain
+    pub(crate) input_peak: f32,
+    /// Absolute peak of the output, linear gain
+    pub(crate) output_peak: f32,
+    /// Bit `n` is set when channel `n` was triggered
+    pub(crate) channel_triggers: u16,
+}
+
+impl BufferMeters {
+    /// Set the gain reduction of a band from the smallest and largest gain applied during the
+    /// buffer. Converting to dB once per buffer keeps `log10` out of the per-sample loop.
+    pub(crate) fn set_gain_range(&mut self, band: usize, min_gain: f32, max_gain: f32) {
+        self.gain_reduction[band] = gain_range_to_db(min_gain, max_gain);
+    }
+}
+
+/// The gain reduction in dB with the largest magnitude, from the smallest and largest gain that
+/// was applied. Negative values are boosts.
+pub(crate) fn gain_range_to_db(min_gain: f32, max_gain: f32) -> f32 {
+    let reduction = -nih_plug::util::gain_to_db(min_gain);
+    let boost = -nih_plug::util::gain_to_db(max_gain);
+    max_magnitude(reduction, boost)
+}
+
+/// Meter readings of a single editor frame
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct MeterReadings {
+    pub(crate) gain_reduction: [f32; 3],
+    pub(crate) input_peak: f32,
+    pub(crate) output_peak: f32,
+    pub(crate) active_voices: u32,
+    /// Bit `n` is set when channel `n` was triggered since the previous frame
+    pub(crate) channel_triggers: u16,
+}
+
+#[derive(Default)]
+pub(crate) struct Meters {
+    gain_reduction: [AtomicF32; 3],
+    input_peak: AtomicF32,
+    output_peak: AtomicF32,
+    active_voices: AtomicU32,
+    channel_triggers: AtomicU16,
+}
+
+impl Meters {
+    /// Called by the audio thread at the end of every buffer
+    pub(crate) fn publish(&self, buffer: &BufferMeters, active_voices: u32) {
+        for (meter, value) in self.gain_reduction.iter().zip(buffer.gain_reduction) {
+            let _ = meter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old| {
+                Some(max_magnitude(old, value))
+            });
+        }
+        self.input_peak
+            .fetch_max(buffer.input_peak, Ordering::Relaxed);
+        self.output_peak
+            .fetch_max(buffer.output_peak, Ordering::Relaxed);
+        self.active_voices.store(active_voices, Ordering::Relaxed);
+        self.channel_triggers
+            .fetch_or(buffer.channel_triggers, Ordering::Relaxed);
+    }
+
+    /// Called by the editor once per frame. Returns the extremes since the previous call, and
+    /// resets them.
+    pub(crate) fn take(&self) -> MeterReadings {
+        MeterReadings {
+            gain_reduction: [
+                self.gain_reduction[0].swap(0.0, Ordering::Relaxed),
+                self.gain_reduction[1].swap(0.0, Ordering::Relaxed),
+                self.gain_reduction[2].swap(0.0, Ordering::Relaxed),
+            ],
+            input_peak: self.input_peak.swap(0.0, Ordering::Relaxed),
+            output_peak: self.output_peak.swap(0.0, Ordering::Relaxed),
+            active_voices: self.active_voices.load(Ordering::Relaxed),
+            channel_triggers: self.channel_triggers.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    /// Clear all meters, e.g. when the plugin gets reset
+    pub(crate) fn reset(&self) {
+        for meter in self.gain_reduction.iter() {
+            meter.store(0.0, Ordering::Relaxed);
+        }
+        self.input_peak.store(0.0, Ordering::Relaxed);
+        self.output_peak.store(0.0, Ordering::Relaxed);
+        self.active_voices.store(0, Ordering::Relaxed);
+        self.channel_triggers.store(0, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,403 @@
+//! Modulation matrix, lets MIDI CCs and aftertouch scale the channel parameters in real time.
+
+use nih_plug::prelude::*;
+
+use crate::{
+    parameter_formatters::{s2v_i32_channel_or_all, v2s_i32_channel_or_all},
+    ChannelParamValues,
+};
+
+/// Number of slots in the modulation matrix
+pub(crate) const MOD_SLOTS: usize = 4;
+
+/// Shortest decay and precomp that modulation can scale down to, in seconds. Zero-length stages
+/// would divide by zero in the envelope.
+const MIN_DURATION_SECONDS: f32 = 0.001;
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum ModSource {
+    #[id = "off"]
+    #[name = "Off"]
+    Off,
+    #[id = "cc"]
+    #[name = "MIDI CC"]
+    CC,
+    #[id = "channel_pressure"]
+    #[name = "Channel pressure"]
+    ChannelPressure,
+    #[id = "poly_pressure"]
+    #[name = "Poly pressure"]
+    PolyPressure,
+}
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum ModTarget {
+    #[id = "depth"]
+    #[name = "Depth"]
+    Depth,
+    #[id = "decay"]
+    #[name = "Decay"]
+    Decay,
+    #[id = "precomp"]
+    #[name = "Precomp"]
+    Precomp,
+    #[id = "morph"]
+    #[name = "Pattern morph"]
+    Morph,
+}
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum ModBand {
+    #[id = "all"]
+    #[name = "All"]
+    All,
+    #[id = "low"]
+    #[name = "Low"]
+    Low,
+    #[id = "mid"]
+    #[name = "Mid"]
+    Mid,
+    #[id = "high"]
+    #[name = "High"]
+    High,
+}
+
+#[derive(Params)]
+pub(crate) struct ModSlotParams {
+    #[id = "mod_source"]
+    pub(crate) source: EnumParam<ModSource>,
+    /// Only used when the source is a MIDI CC
+    #[id = "mod_cc"]
+    pub(crate) cc: IntParam,
+    #[id = "mod_target"]
+    pub(crate) target: EnumParam<ModTarget>,
+    /// The envelope channel to modulate, -1 for all channels
+    #[id = "mod_channel"]
+    pub(crate) channel: IntParam,
+    #[id = "mod_band"]
+    pub(crate) band: EnumParam<ModBand>,
+    /// -1.0 -- 1.0, negative amounts invert the source
+    #[id = "mod_amount"]
+    pub(crate) amount: FloatParam,
+}
+
+impl Default for ModSlotParams {
+    fn default() -> Self {
+        Self {
+            source: EnumParam::new("Mod source", ModSource::Off),
+            cc: IntParam::new(
+                "Mod CC",
+                1, // default to the mod wheel
+                IntRange::Linear { min: 0, max: 127 },
+            ),
+            target: EnumParam::new("Mod target", ModTarget::Depth),
+            channel: IntParam::new("Mod channel", -1, IntRange::Linear { min: -1, max: 15 })
+                .with_value_to_string(v2s_i32_channel_or_all())
+                .with_string_to_value(s2v_i32_channel_or_all()),
+            band: EnumParam::new("Mod band", ModBand::All),
+            amount: FloatParam::new(
+                "Mod amount",
+                1.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_value_to_string(formatters::v2s_f32_percentage(3))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+/// The latest values of the modulation sources, all in range 0.0 -- 1.0. Sources are `None`
+/// until they are received for the first time.
+pub(crate) struct ModSources {
+    cc: [Option<f32>; 128],
+    channel_pressure: Option<f32>,
+    /// Poly pressure is tracked per envelope channel, rather than per note
+    poly_pressure: [Option<f32>; 16],
+}
+
+impl Default for ModSources {
+    fn default() -> Self {
+        Self {
+            // sources don't modulate anything until they are received for the first time, no
+            // matter the sign of the amount. Many controllers never send aftertouch at all.
+            cc: [None; 128],
+            channel_pressure: None,
+            poly_pressure: [None; 16],
+        }
+    }
+}
+
+impl ModSources {
+    pub(crate) fn set_cc(&mut self, cc: u8, value: f32) {
+        if let Some(x) = self.cc.get_mut(cc as usize) {
+            *x = Some(value);
+        }
+    }
+
+    pub(crate) fn set_channel_pressure(&mut self, value: f32) {
+        self.channel_pressure = Some(value);
+    }
+
+    pub(crate) fn set_poly_pressure(&mut self, channel: usize, value: f32) {
+        if let Some(x) = self.poly_pressure.get_mut(channel) {
+            *x = Some(value);
+        }
+    }
+
+    fn get(&self, source: ModSource, cc: i32, channel: usize) -> Option<f32> {
+        match source {
+            ModSource::Off => None,
+            ModSource::CC => self.cc.get(cc as usize).copied().flatten(),
+            ModSource::ChannelPressure => self.channel_pressure,
+            ModSource::PolyPressure => self.poly_pressure.get(channel).copied().flatten(),
+        }
+    }
+}
+
+/// Multiply the targeted band values of a channel by the given factor. Decay and precomp are
+/// kept at [`MIN_DURATION_SECONDS`] or above, unless they were already shorter.
+fn scale_target(values: &mut ChannelParamValues, target: ModTarget, band: ModBand, factor: f32) {
+    let [low, mid, high] = match target {
+        ModTarget::Depth => [&mut values.low_db, &mut values.mid_db, &mut values.high_db],
+        ModTarget::Decay => [
+            &mut values.low_decay,
+            &mut values.mid_decay,
+            &mut values.high_decay,
+        ],
+        ModTarget::Precomp => [
+            &mut values.low_precomp,
+            &mut values.mid_precomp,
+            &mut values.high_precomp,
+        ],
+        ModTarget::Morph => [
+            &mut values.low_morph,
+            &mut values.mid_morph,
+            &mut values.high_morph,
+        ],
+    };
+
+    let min_value = match target {
+        ModTarget::Decay | ModTarget::Precomp => MIN_DURATION_SECONDS,
+        ModTarget::Depth | ModTarget::Morph => 0.0,
+    };
+    let scale = |value: &mut f32| *value = (*value * factor).max(min_value.min(*value));
+
+    match band {
+        ModBand::All => {
+            scale(low);
+            scale(mid);
+            scale(high);
+        }
+        ModBand::Low => scale(low),
+        ModBand::Mid => scale(mid),
+        ModBand::High => scale(high),
+    }
+}
+
+/// Apply the modulation matrix to the channel values of the current sample.
+///
+/// Modulation only ever scales values down, a source at its maximum leaves the target unchanged
+/// (or at its minimum, for negative amounts). Sources that haven't been received yet are skipped.
+/// This keeps the targets within their parameter ranges (e.g. precomp never exceeds the
+/// lookahead).
+pub(crate) fn apply_modulation(
+    slots: &[ModSlotParams; MOD_SLOTS],
+    sources: &ModSources,
+    channels: &mut [ChannelParamValues; 16],
+) {
+    for slot in slots.iter() {
+        // always advance the smoother, even if the slot is unused
+        let amount = slot.amount.smoothed.next();
+
+        let source = slot.source.value();
+        if source == ModSource::Off || amount == 0.0 {
+            continue;
+        }
+
+        let cc = slot.cc.value();
+        let target = slot.target.value();
+        let band = slot.band.value();
+        let channel_range = match slot.channel.value() {
+            ..=-1 => 0..16,
+            channel => {
+                let channel = (channel as usize).min(15);
+                channel..(channel + 1)
+            }
+        };
+
+        for channel in channel_range {
+            let Some(value) = sources.get(source, cc, channel) else {
+                continue;
+            };
+
+            // negative amounts invert the source
+            let value = if amount >= 0.0 { value } else { 1.0 - value };
+            let factor = 1.0 - amount.abs() * (1.0 - value);
+
+            scale_target(&mut channels[channel], target, band, factor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChannelParams;
+
+    fn slot(amount: f32) -> ModSlotParams {
+        let slot = ModSlotParams {
+            source: EnumParam::new("Mod source", ModSource::CC),
+            ..Default::default()
+        };
+        slot.amount.smoothed.reset(amount);
+        slot
+    }
+
+    /// Apply a single CC slot to channels with 12 dB of depth on every band, returns the
+    /// low depth of the first channel
+    fn modulated_depth(amount: f32, cc_value: Option<f32>) -> f32 {
+        let slots = [slot(amount), slot(0.0), slot(0.0), slot(0.0)];
+        let mut sources = ModSources::default();
+        if let Some(value) = cc_value {
+            sources.set_cc(1, value);
+        }
+        let mut channels = std::array::from_fn(|_| {
+            let mut values = ChannelParams::default().value(0.0);
+            values.low_db = 12.0;
+            values.mid_db = 12.0;
+            values.high_db = 12.0;
+            values
+        });
+
+        apply_modulation(&slots, &sources, &mut channels);
+
+        channels[0].low_db
+    }
+
+    mod apply_modulation {
+        use super::*;
+
+        /// A CC that hasn't been received doesn't modulate, for either sign
+        #[test]
+        fn test_01() {
+            assert_eq!(modulated_depth(1.0, None), 12.0);
+            assert_eq!(modulated_depth(-1.0, None), 12.0);
+            assert_eq!(modulated_depth(0.5, None), 12.0);
+            assert_eq!(modulated_depth(-0.5, None), 12.0);
+        }
+
+        /// Positive amounts scale down as the CC goes down
+        #[test]
+        fn test_02() {
+            assert_eq!(modulated_depth(1.0, Some(1.0)), 12.0);
+            assert_eq!(modulated_depth(1.0, Some(0.5)), 6.0);
+            assert_eq!(modulated_depth(1.0, Some(0.0)), 0.0);
+            assert_eq!(modulated_depth(0.5, Some(0.0)), 6.0);
+        }
+
+        /// Negative amounts scale down as the CC goes up
+        #[test]
+        fn test_03() {
+            assert_eq!(modulated_depth(-1.0, Some(0.0)), 12.0);
+            assert_eq!(modulated_depth(-1.0, Some(0.5)), 6.0);
+            assert_eq!(modulated_depth(-1.0, Some(1.0)), 0.0);
+            assert_eq!(modulated_depth(-0.5, Some(1.0)), 6.0);
+        }
+
+        /// Other CCs and disabled slots leave the values unchanged
+        #[test]
+        fn test_04() {
+            let slots = [slot(1.0), slot(0.0), slot(0.0), slot(0.0)];
+            let mut sources = ModSources::default();
+            sources.set_cc(2, 0.0);
+            let mut channels = std::array::from_fn(|_| {
+                let mut values = ChannelParams::default().value(0.0);
+                values.low_db = 12.0;
+                values
+            });
+
+            apply_modulation(&slots, &sources, &mut channels);
+
+            assert!(channels.iter().all(|x| x.low_db == 12.0));
+        }
+
+        /// Pressure that hasn't been received doesn't modulate, once received it does
+        #[test]
+        fn test_05() {
+            let pressure_slot = |source: ModSource| {
+                let slot = ModSlotParams {
+                    source: EnumParam::new("Mod source", source),
+                    ..Default::default()
+                };
+                slot.amount.smoothed.reset(1.0);
+                slot
+            };
+            let slots = [
+                pressure_slot(ModSource::ChannelPressure),
+                pressure_slot(ModSource::PolyPressure),
+                slot(0.0),
+                slot(0.0),
+            ];
+            let mut sources = ModSources::default();
+            let mut channels = std::array::from_fn(|_| {
+                let mut values = ChannelParams::default().value(0.0);
+                values.low_db = 12.0;
+                values
+            });
+
+            apply_modulation(&slots, &sources, &mut channels);
+            assert!(channels.iter().all(|x| x.low_db == 12.0));
+
+            // poly pressure only reaches the channel it was received on
+            sources.set_channel_pressure(1.0);
+            sources.set_poly_pressure(3, 0.5);
+            apply_modulation(&slots, &sources, &mut channels);
+            assert_eq!(channels[0].low_db, 12.0);
+            assert_eq!(channels[3].low_db, 6.0);
+
+            sources.set_channel_pressure(0.0);
+            apply_modulation(&slots, &sources, &mut channels);
+            assert_eq!(channels[0].low_db, 0.0);
+        }
+
+        /// Decay and precomp never get scaled down to zero
+        #[test]
+        fn test_06() {
+            let target_slot = |target: ModTarget| {
+                let slot = ModSlotParams {
+                    source: EnumParam::new("Mod source", ModSource::CC),
+                    target: EnumParam::new("Mod target", target),
+                    ..Default::default()
+                };
+                slot.amount.smoothed.reset(1.0);
+                slot
+            };
+            let slots = [
+                target_slot(ModTarget::Decay),
+                target_slot(ModTarget::Precomp),
+                slot(0.0),
+                slot(0.0),
+            ];
+            let mut sources = ModSources::default();
+            sources.set_cc(1, 0.0);
+            let mut channels = std::array::from_fn(|_| {
+                let mut values = ChannelParams::default().value(0.01);
+                values.low_decay = 0.1;
+                values.low_precomp = 0.01;
+                // already below the minimum, stays as it is
+                values.high_precomp = 0.0;
+                values
+            });
+
+            apply_modulation(&slots, &sources, &mut channels);
+
+            assert_eq!(channels[0].low_decay, MIN_DURATION_SECONDS);
+            assert_eq!(channels[0].low_precomp, MIN_DURATION_SECONDS);
+            assert_eq!(channels[0].high_precomp, 0.0);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Note map, an editable mapping from MIDI notes to envelope channels.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+use thiserror::Error;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Name of a MIDI note, where note 60 is C4
+pub(crate) fn note_name(note: u8) -> String {
+    let octave = (note / 12) as i32 - 1;
+    format!("{}{}", NOTE_NAMES[(note % 12) as usize], octave)
+}
+
+/// Where a single MIDI note gets routed to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct NoteMapEntry {
+    /// Bitmask of the envelope channels this note triggers, bit 0 is channel 0
+    pub(crate) channels: u16,
+    /// Only accept the note from this MIDI channel, or from any MIDI channel if `None`
+    pub(crate) midi_channel: Option<u8>,
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum NoteMapError {
+    #[error("expected 128 entries, got {0}")]
+    WrongLength(usize),
+}
+
+/// Maps each of the 128 MIDI notes to any number of envelope channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "Vec<NoteMapEntry>", try_from = "Vec<NoteMapEntry>")]
+pub(crate) struct NoteMap {
+    entries: [NoteMapEntry; 128],
+}
+
+impl Default for NoteMap {
+    fn default() -> Self {
+        Self::gm_drums()
+    }
+}
+
+impl From<NoteMap> for Vec<NoteMapEntry> {
+    fn from(map: NoteMap) -> Self {
+        map.entries.to_vec()
+    }
+}
+
+impl TryFrom<Vec<NoteMapEntry>> for NoteMap {
+    type Error = NoteMapError;
+
+    fn try_from(entries: Vec<NoteMapEntry>) -> Result<Self, Self::Error> {
+        let len = entries.len();
+        let entries = entries
+            .try_into()
+            .map_err(|_| NoteMapError::WrongLength(len))?;
+        Ok(Self { entries })
+    }
+}
+
+impl NoteMap {
+    /// A map where no notes trigger anything
+    pub(crate) fn empty() -> Self {
+        Self {
+            entries: [NoteMapEntry::default(); 128],
+        }
+    }
+
+    /// Same behaviour as the "Pitch" MIDI mode, 16 notes starting from the root note
+    pub(crate) fn from_root_note(root_note: u8) -> Self {
+        let mut map = Self::empty();
+        for channel in 0..16 {
+            if let Some(note) = root_note.checked_add(channel as u8) {
+                map.assign(note, channel, None);
+            }
+        }
+        map
+    }
+
+    /// The General MIDI drum map, with similar instruments grouped into the same channel
+    pub(crate) fn gm_drums() -> Self {
+        const GROUPS: [&[u8]; 7] = [
+            // kick
+            &[35, 36],
+            // snare, side stick, clap
+            &[37, 38, 39, 40],
+            // closed and pedal hi-hat
+            &[42, 44],
+            // open hi-hat
+            &[46],
+            // toms
+            &[41, 43, 45, 47, 48, 50],
+            // crash, china, splash
+            &[49, 52, 55, 57],
+            // ride, ride bell
+            &[51, 53, 59],
+        ];
+
+        let mut map = Self::empty();
+        for (channel, notes) in GROUPS.iter().enumerate() {
+            for note in notes.iter() {
+                map.assign(*note, channel, None);
+            }
+        }
+        map
+    }
+
+    pub(crate) fn entry(&self, note: u8) -> &NoteMapEntry {
+        &self.entries[(note & 0x7f) as usize]
+    }
+
+    /// Return the bitmask of envelope channels that the given note triggers
+    pub(crate) fn channels_for(&self, note: u8, midi_channel: u8) -> u16 {
+        let entry = self.entry(note);
+        match entry.midi_channel {
+            Some(x) if x != midi_channel => 0,
+            _ => entry.channels,
+        }
+    }
+
+    /// Make a note trigger the given envelope channel, in addition to its existing channels.
+    ///
+    /// The MIDI channel filter of the note is only replaced if a new one is given, so that
+    /// assigning another envelope channel doesn't open up an existing filter.
+    pub(crate) fn assign(&mut self, note: u8, channel: usize, midi_channel: Option<u8>) {
+        let entry = &mut self.entries[(note & 0x7f) as usize];
+        entry.channels |= 1 << channel;
+        if midi_channel.is_some() {
+            entry.midi_channel = midi_channel;
+        }
+    }
+
+    /// Remove the given envelope channel from a note
+    pub(crate) fn unassign(&mut self, note: u8, channel: usize) {
+        let entry = &mut self.entries[(note & 0x7f) as usize];
+        entry.channels &= !(1 << channel);
+        if entry.channels == 0 {
+            entry.midi_channel = None;
+        }
+    }
+
+    /// Remove the given envelope channel from all notes
+    pub(crate) fn clear_channel(&mut self, channel: usize) {
+        for note in 0..128 {
+            self.unassign(note, channel);
+        }
+    }
+
+    /// All notes that trigger the given envelope channel
+    pub(crate) fn notes_for_channel(&self, channel: usize) -> impl Iterator<Item = u8> + '_ {
+        (0..128u8).filter(move |note| self.entry(*note).channels & (1 << channel) != 0)
+    }
+}
+
+/// The most recent note-on received by the plugin, used for MIDI learn in the editor.
+///
+/// Packed into a single atomic as `counter << 16 | midi_channel << 8 | note`. The counter is
+/// incremented on every note, so repeated presses of the same key can be told apart.
+#[derive(Default)]
+pub(crate) struct LastNoteOn(AtomicU32);
+
+impl LastNoteOn {
+    pub(crate) fn store(&self, note: u8, midi_channel: u8) {
+        let counter = (self.0.load(Ordering::Relaxed) >> 16).wrapping_add(1) & 0xffff;
+        let packed = (counter << 16) | ((midi_channel as u32) << 8) | note as u32;
+        self.0.store(packed, Ordering::Relaxed);
+    }
+
+    /// Returns `(counter, note, midi_channel)`
+    pub(crate) fn load(&self) -> (u16, u8, u8) {
+        let packed = self.0.load(Ordering::Relaxed);
+        ((packed >> 16) as u16, packed as u8, (packed >> 8) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod assign {
+        use super::*;
+
+        /// Channels are added to the existing ones
+        #[test]
+        fn test_01() {
+            let mut map = NoteMap::empty();
+            map.assign(36, 0, None);
+            map.assign(36, 3, None);
+
+            assert_eq!(
+                map.entry(36),
+                &NoteMapEntry {
+                    channels: 0b1001,
+                    midi_channel: None,
+                }
+            );
+            assert_eq!(map.entry(37), &NoteMapEntry::default());
+        }
+
+        /// Assigning without a filter keeps the existing filter
+        #[test]
+        fn test_02() {
+            let mut map = NoteMap::empty();
+            map.assign(36, 0, Some(9));
+            map.assign(36, 1, None);
+
+            assert_eq!(
+                map.entry(36),
+                &NoteMapEntry {
+                    channels: 0b11,
+                    midi_channel: Some(9),
+                }
+            );
+        }
+
+        /// Assigning with a filter replaces the existing filter
+        #[test]
+        fn test_03() {
+            let mut map = NoteMap::empty();
+            map.assign(36, 0, Some(9));
+            map.assign(36, 1, Some(2));
+            assert_eq!(map.entry(36).midi_channel, Some(2));
+
+            map.assign(38, 0, None);
+            map.assign(38, 1, Some(2));
+            assert_eq!(map.entry(38).midi_channel, Some(2));
+        }
+    }
+
+    mod channels_for {
+        use super::*;
+
+        /// Notes without a filter trigger on any MIDI channel
+        #[test]
+        fn test_01() {
+            let mut map = NoteMap::empty();
+            map.assign(36, 0, None);
+            map.assign(36, 2, None);
+
+            assert_eq!(map.channels_for(36, 0), 0b101);
+            assert_eq!(map.channels_for(36, 15), 0b101);
+            assert_eq!(map.channels_for(37, 0), 0);
+        }
+
+        /// Notes with a filter only trigger on their MIDI channel
+        #[test]
+        fn test_02() {
+            let mut map = NoteMap::empty();
+            map.assign(36, 1, Some(9));
+
+            assert_eq!(map.channels_for(36, 9), 0b10);
+            assert_eq!(map.channels_for(36, 0), 0);
+            assert_eq!(map.channels_for(36, 10), 0);
+        }
+
+        /// Notes above 127 wrap around instead of panicking
+        #[test]
+        fn test_03() {
+            let mut map = NoteMap::empty();
+            map.assign(1, 4, None);
+
+            assert_eq!(map.channels_for(129, 0), 0b10000);
+        }
+    }
+
+    mod clear_channel {
+        use super::*;
+
+        /// Only the given channel is removed, other channels keep their filter
+        #[test]
+        fn test_01() {
+            let mut map = NoteMap::empty();
+            map.assign(36, 0, Some(9));
+            map.assign(36, 1, None);
+            map.assign(38, 0, None);
+            map.clear_channel(0);
+
+            assert_eq!(
+                map.entry(36),
+                &NoteMapEntry {
+                    channels: 0b10,
+                    midi_channel: Some(9),
+                }
+            );
+            assert_eq!(map.entry(38), &NoteMapEntry::default());
+            assert_eq!(map.notes_for_channel(0).count(), 0);
+        }
+
+        /// Notes left without channels lose their filter, so assigning them again starts fresh
+        #[test]
+        fn test_02() {
+            let mut map = NoteMap::empty();
+            map.assign(36, 0, Some(9));
+            map.clear_channel(0);
+            assert_eq!(map.entry(36), &NoteMapEntry::default());
+
+            map.assign(36, 0, None);
+            assert_eq!(map.channels_for(36, 0), 0b1);
+        }
+
+        /// Clearing the default map removes the kicks only
+        #[test]
+        fn test_03() {
+            let mut map = NoteMap::gm_drums();
+            map.clear_channel(0);
+
+            assert_eq!(map.channels_for(35, 0), 0);
+            assert_eq!(map.channels_for(36, 0), 0);
+            assert_eq!(map.channels_for(38, 0), 0b10);
+            assert_eq!(
+                map.notes_for_channel(1).collect::<Vec<_>>(),
+                [37, 38, 39, 40]
+            );
+        }
+    }
+}
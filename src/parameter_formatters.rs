@@ -2,6 +2,8 @@ use std::sync::Arc;
 
 type V2SFormatter = dyn Fn(f32) -> String + Send + Sync;
 type S2VFormatter = dyn Fn(&str) -> Option<f32> + Send + Sync;
+type V2SFormatterI32 = dyn Fn(i32) -> String + Send + Sync;
+type S2VFormatterI32 = dyn Fn(&str) -> Option<i32> + Send + Sync;
 
 /// Format a `f32` Hertz value as a rounded `Hz` below 1000 Hz, and as a rounded `kHz` value above
 /// 1000 Hz. This already includes the unit.
@@ -68,6 +70,55 @@ pub(crate) fn s2v_f32_ms_then_s() -> Arc<S2VFormatter> {
     })
 }
 
+/// Format an envelope channel index, where negative values stand for all channels.
+pub(crate) fn v2s_i32_channel_or_all() -> Arc<V2SFormatterI32> {
+    Arc::new(move |value| {
+        if value < 0 {
+            String::from("All")
+        } else {
+            value.to_string()
+        }
+    })
+}
+
+/// Convert an input in the same format at that of [`v2s_i32_channel_or_all()`] to a channel
+/// index.
+pub(crate) fn s2v_i32_channel_or_all() -> Arc<S2VFormatterI32> {
+    Arc::new(move |string| {
+        let string = string.trim();
+
+        if string.eq_ignore_ascii_case("all") {
+            Some(-1)
+        } else {
+            string.parse().ok()
+        }
+    })
+}
+
+/// Format an integer value, where zero stands for "Off".
+pub(crate) fn v2s_i32_zero_as_off() -> Arc<V2SFormatterI32> {
+    Arc::new(move |value| {
+        if value == 0 {
+            String::from("Off")
+        } else {
+            value.to_string()
+        }
+    })
+}
+
+/// Convert an input in the same format at that of [`v2s_i32_zero_as_off()`] to an integer.
+pub(crate) fn s2v_i32_zero_as_off() -> Arc<S2VFormatterI32> {
+    Arc::new(move |string| {
+        let string = string.trim();
+
+        if string.eq_ignore_ascii_case("off") {
+            Some(0)
+        } else {
+            string.parse().ok()
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -170,4 +221,191 @@ mod tests {
             assert_eq!(s2v_f32_ms_then_s()(input), expected);
         }
     }
+
+    mod v2s_i32_channel_or_all {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            let input = -1;
+            let expected = "All";
+            assert_eq!(v2s_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_02() {
+            let input = 0;
+            let expected = "0";
+            assert_eq!(v2s_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_03() {
+            let input = 15;
+            let expected = "15";
+            assert_eq!(v2s_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_04() {
+            // every negative value stands for all channels
+            let input = -5;
+            let expected = "All";
+            assert_eq!(v2s_i32_channel_or_all()(input), expected);
+        }
+    }
+
+    mod s2v_i32_channel_or_all {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            let input = "All";
+            let expected = Some(-1);
+            assert_eq!(s2v_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_02() {
+            let input = " all ";
+            let expected = Some(-1);
+            assert_eq!(s2v_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_03() {
+            let input = "-1";
+            let expected = Some(-1);
+            assert_eq!(s2v_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_04() {
+            let input = "0";
+            let expected = Some(0);
+            assert_eq!(s2v_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_05() {
+            let input = "15";
+            let expected = Some(15);
+            assert_eq!(s2v_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_06() {
+            // out of range values are clamped by the parameter, not here
+            let input = "99";
+            let expected = Some(99);
+            assert_eq!(s2v_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_07() {
+            let input = "channel 3";
+            let expected = None;
+            assert_eq!(s2v_i32_channel_or_all()(input), expected);
+        }
+
+        #[test]
+        fn test_08() {
+            let input = "";
+            let expected = None;
+            assert_eq!(s2v_i32_channel_or_all()(input), expected);
+        }
+    }
+
+    mod v2s_i32_zero_as_off {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            let input = 0;
+            let expected = "Off";
+            assert_eq!(v2s_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_02() {
+            let input = 1;
+            let expected = "1";
+            assert_eq!(v2s_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_03() {
+            let input = 8;
+            let expected = "8";
+            assert_eq!(v2s_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_04() {
+            let input = -3;
+            let expected = "-3";
+            assert_eq!(v2s_i32_zero_as_off()(input), expected);
+        }
+    }
+
+    mod s2v_i32_zero_as_off {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            let input = "Off";
+            let expected = Some(0);
+            assert_eq!(s2v_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_02() {
+            let input = " off ";
+            let expected = Some(0);
+            assert_eq!(s2v_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_03() {
+            let input = "0";
+            let expected = Some(0);
+            assert_eq!(s2v_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_04() {
+            let input = "3";
+            let expected = Some(3);
+            assert_eq!(s2v_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_05() {
+            // out of range values are clamped by the parameter, not here
+            let input = "99";
+            let expected = Some(99);
+            assert_eq!(s2v_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_06() {
+            let input = "none";
+            let expected = None;
+            assert_eq!(s2v_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_07() {
+            let input = "1.5";
+            let expected = None;
+            assert_eq!(s2v_i32_zero_as_off()(input), expected);
+        }
+
+        #[test]
+        fn test_08() {
+            let input = "";
+            let expected = None;
+            assert_eq!(s2v_i32_zero_as_off()(input), expected);
+        }
+    }
 }
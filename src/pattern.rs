@@ -1,10 +1,16 @@
 //! Pattern module, represents a user-editable pattern thing.
 //! Code based on: https://github.com/tiagolr/gate1
 
+use arrayvec::ArrayVec;
 use nih_plug::{nih_debug_assert_failure, nih_error};
+use serde::{Deserialize, Deserializer, Serialize};
 use thiserror::Error;
 
-#[derive(Debug, Clone, Copy)]
+/// Maximum number of points in a pattern. Patterns have a fixed capacity, so that they can be
+/// copied on the audio thread without allocating.
+pub(crate) const MAX_POINTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub(crate) enum CurveType {
     Hold,
     Curve,
@@ -67,7 +73,7 @@ impl CurveType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub(crate) struct Point {
     x: f64,
     y: f64,
@@ -94,6 +100,14 @@ impl Point {
             kind,
         })
     }
+
+    pub(crate) fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub(crate) fn y(&self) -> f64 {
+        self.y
+    }
 }
 
 #[derive(Error, Debug)]
@@ -102,15 +116,41 @@ pub(crate) enum PatternError {
     EndPointConflict,
     #[error("the specified point is out of bounds")]
     PointOutOfBounds,
+    #[error("the pattern already has the maximum number of points")]
+    TooManyPoints,
+    #[error("the points don't form a valid pattern")]
+    InvalidPoints,
 }
 
 type Result<T, E = PatternError> = std::result::Result<T, E>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PatternData")]
 pub(crate) struct Pattern {
+    points: ArrayVec<Point, MAX_POINTS>,
+}
+
+/// A pattern as it is saved, checked by [`Pattern::new`] when it is loaded
+#[derive(Deserialize)]
+struct PatternData {
     points: Vec<Point>,
 }
 
+impl TryFrom<PatternData> for Pattern {
+    type Error = PatternError;
+
+    fn try_from(data: PatternData) -> Result<Self> {
+        let points: Option<Vec<Point>> = data
+            .points
+            .iter()
+            .map(|p| Point::new(p.x, p.y, p.tension, p.kind))
+            .collect();
+        points
+            .and_then(Self::new)
+            .ok_or(PatternError::InvalidPoints)
+    }
+}
+
 impl Default for Pattern {
     fn default() -> Self {
         Self::new(vec![
@@ -123,7 +163,7 @@ impl Default for Pattern {
 
 impl Pattern {
     pub(crate) fn new(points: Vec<Point>) -> Option<Self> {
-        if points.len() < 2 {
+        if points.len() < 2 || points.len() > MAX_POINTS {
             return None;
         }
 
@@ -146,13 +186,23 @@ impl Pattern {
             return None;
         }
 
-        Some(Self { points })
+        Some(Self {
+            points: points.into_iter().collect(),
+        })
+    }
+
+    pub(crate) fn points(&self) -> &[Point] {
+        &self.points
     }
 
-    pub(crate) fn insert_point(&mut self, p: Point) -> usize {
+    pub(crate) fn insert_point(&mut self, p: Point) -> Result<usize> {
+        if self.points.is_full() {
+            return Err(PatternError::TooManyPoints);
+        }
+
         // insert point, keeping the list sorted
         // if multiple points have the same x pos, insert at last of those points
-        match self.points.iter().rposition(|p2| p2.x <= p.x) {
+        let idx = match self.points.iter().rposition(|p2| p2.x <= p.x) {
             Some(prev_pos) => {
                 if prev_pos == self.len() - 1 {
                     // overlaps with rightmost point
@@ -178,7 +228,27 @@ impl Pattern {
                 self.points.insert(0, p);
                 0
             }
+        };
+
+        Ok(idx)
+    }
+
+    /// Move a point to a new position. The point can't move past its neighbours. The first and
+    /// last points can't be moved, so that the pattern keeps going from 0.0 to 1.0 like every
+    /// other envelope curve.
+    pub(crate) fn move_point(&mut self, i: usize, x: f64, y: f64) -> Result<()> {
+        if i >= self.len() {
+            return Err(PatternError::PointOutOfBounds);
+        }
+        if i == 0 || i == self.len() - 1 {
+            return Err(PatternError::EndPointConflict);
         }
+
+        let x = x.clamp(self.points[i - 1].x, self.points[i + 1].x);
+        let point = &mut self.points[i];
+        point.x = x;
+        point.y = y.clamp(0.0, 1.0);
+        Ok(())
     }
 
     pub(crate) fn remove_point_at_pos(&mut self, x: f64, y: f64) {
@@ -207,15 +277,14 @@ impl Pattern {
     }
 
     pub(crate) fn remove_points_in_range(&mut self, x1: f64, x2: f64) {
-        let mut mid_points: Vec<_> = self.points[1..(self.points.len() - 1)].iter().collect();
-        mid_points.retain(|p| x1 <= p.x && p.x <= x2);
-        mid_points.insert(0, &self.points.first().unwrap());
-        mid_points.push(&self.points.last().unwrap());
-
-        // clone all points
-        let new_points: Vec<_> = mid_points.iter().map(|p| (*p).clone()).collect();
-
-        self.points = new_points;
+        // the first and last points are always kept
+        let last = self.points.len() - 1;
+        let mut i = 0;
+        self.points.retain(|p| {
+            let keep = i == 0 || i == last || (x1 <= p.x && p.x <= x2);
+            i += 1;
+            keep
+        });
     }
 
     #[inline(always)]
@@ -249,11 +318,22 @@ impl Pattern {
         }
     }
 
+    /// Remove every point in between, leaving a flat line at 0.5
     pub(crate) fn clear(&mut self) {
-        self.points = vec![
+        self.points = [
             Point::new(0.0, 0.5, 0.0, CurveType::Curve).unwrap(),
             Point::new(1.0, 0.5, 0.0, CurveType::Curve).unwrap(),
-        ];
+        ]
+        .into_iter()
+        .collect();
+    }
+
+    /// Move the first point to 0.0 and the last point to 1.0, so the pattern can be used as an
+    /// envelope curve
+    fn pin_endpoints(&mut self) {
+        let last = self.points.len() - 1;
+        self.points[0].y = 0.0;
+        self.points[last].y = 1.0;
     }
 
     pub(crate) fn get_y_at(&self, x: f64) -> f64 {
@@ -289,4 +369,145 @@ impl Pattern {
         ])
         .unwrap()
     }
+
+    /// A release that drops quickly, for a tight duck
+    pub(crate) fn pluck() -> Self {
+        Self::new(vec![
+            Point::new(0.0, 0.0, -0.1, CurveType::Curve).unwrap(),
+            Point::new(1.0, 1.0, 0.0, CurveType::Curve).unwrap(),
+        ])
+        .unwrap()
+    }
+
+    /// A release that stays low for a while before rising, for a long swell
+    pub(crate) fn swell() -> Self {
+        Self::new(vec![
+            Point::new(0.0, 0.0, 0.1, CurveType::Curve).unwrap(),
+            Point::new(1.0, 1.0, 0.0, CurveType::Curve).unwrap(),
+        ])
+        .unwrap()
+    }
+}
+
+/// The A and B patterns of each band, as `[low, mid, high]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct MorphPatterns {
+    #[serde(deserialize_with = "deserialize_envelope_curves")]
+    pub(crate) bands: [[Pattern; 2]; 3],
+}
+
+/// The state may have been edited by hand or be malformed, so the endpoints are moved back to
+/// 0.0 and 1.0 before the patterns reach the audio thread.
+fn deserialize_envelope_curves<'de, D>(deserializer: D) -> Result<[[Pattern; 2]; 3], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut bands = <[[Pattern; 2]; 3]>::deserialize(deserializer)?;
+    for pattern in bands.iter_mut().flatten() {
+        pattern.pin_endpoints();
+    }
+    Ok(bands)
+}
+
+impl Default for MorphPatterns {
+    fn default() -> Self {
+        Self {
+            bands: std::array::from_fn(|_| [Pattern::pluck(), Pattern::swell()]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod move_point {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // the endpoints can't be moved
+            let mut pattern = Pattern::default();
+            assert!(pattern.move_point(0, 0.0, 0.5).is_err());
+            assert!(pattern.move_point(1, 1.0, 0.5).is_err());
+            assert_eq!(pattern.points()[0].y(), 0.0);
+            assert_eq!(pattern.points()[1].y(), 1.0);
+        }
+
+        #[test]
+        fn test_02() {
+            // points in between can't pass their neighbours
+            let mut pattern = Pattern::default();
+            let point = Point::new(0.5, 0.5, 0.0, CurveType::Curve).unwrap();
+            let i = pattern.insert_point(point).unwrap();
+            pattern.move_point(i, 1.5, -1.0).unwrap();
+            assert_eq!(pattern.points()[i].x(), 1.0);
+            assert_eq!(pattern.points()[i].y(), 0.0);
+        }
+    }
+
+    mod clear {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            let mut pattern = Pattern::sine();
+            pattern.clear();
+            assert_eq!(pattern.len(), 2);
+            assert_eq!(pattern.get_y_at(0.0), 0.5);
+            assert_eq!(pattern.get_y_at(1.0), 0.5);
+        }
+    }
+
+    mod deserialize {
+        use super::*;
+
+        fn point(x: f64, y: f64) -> String {
+            format!(r#"{{ "x": {x}, "y": {y}, "tension": 0.0, "kind": "Curve" }}"#)
+        }
+
+        fn morph_patterns(points: &str) -> String {
+            let pattern = format!(r#"{{ "points": [{points}] }}"#);
+            let band = format!("[{pattern}, {pattern}]");
+            format!(r#"{{ "bands": [{band}, {band}, {band}] }}"#)
+        }
+
+        #[test]
+        fn test_01() {
+            // endpoints that were moved are put back at 0.0 and 1.0
+            let json = morph_patterns(&[point(0.0, 0.5), point(1.0, 0.5)].join(", "));
+            let patterns: MorphPatterns = serde_json::from_str(&json).unwrap();
+            for pattern in patterns.bands.iter().flatten() {
+                assert_eq!(pattern.get_y_at(0.0), 0.0);
+                assert_eq!(pattern.get_y_at(1.0), 1.0);
+            }
+        }
+
+        #[test]
+        fn test_02() {
+            // patterns that can't be repaired are rejected
+            for points in [
+                // unsorted
+                [
+                    point(0.0, 0.0),
+                    point(0.7, 0.2),
+                    point(0.3, 0.5),
+                    point(1.0, 1.0),
+                ]
+                .join(", "),
+                // doesn't reach the end
+                [point(0.0, 0.0), point(0.5, 1.0)].join(", "),
+                // out of range
+                [point(0.0, 0.0), point(0.5, 2.0), point(1.0, 1.0)].join(", "),
+                // a single point
+                point(0.0, 0.0),
+            ] {
+                let json = morph_patterns(&points);
+                assert!(
+                    serde_json::from_str::<MorphPatterns>(&json).is_err(),
+                    "{points}"
+                );
+            }
+        }
+    }
 }
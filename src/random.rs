@@ -0,0 +1,103 @@
+//! Small deterministic random number generator, so that offline renders are reproducible.
+
+/// A xorshift32 generator, this doesn't allocate so it can be used on the audio thread.
+pub(crate) struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u32) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.reseed(seed);
+        rng
+    }
+
+    /// Restart the sequence of random numbers from the given seed
+    pub(crate) fn reseed(&mut self, seed: u32) {
+        // scramble the seed, so that nearby seeds give unrelated sequences
+        // https://github.com/skeeto/hash-prospector
+        let mut x = seed;
+        x ^= x >> 16;
+        x = x.wrapping_mul(0x7feb352d);
+        x ^= x >> 15;
+        x = x.wrapping_mul(0x846ca68b);
+        x ^= x >> 16;
+
+        // xorshift gets stuck on zero
+        self.state = if x == 0 { 0x9e3779b9 } else { x };
+    }
+
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Random value in range 0.0 -- 1.0 (exclusive)
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        // use the top 24 bits, which is all the precision an f32 has
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Random value in range -1.0 -- 1.0
+    pub(crate) fn next_bipolar(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod rng {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // the same seed always gives the same sequence, also after reseeding
+            let mut a = Rng::new(1234);
+            let mut b = Rng::new(1234);
+            let first: Vec<u32> = (0..64).map(|_| a.next_u32()).collect();
+            let second: Vec<u32> = (0..64).map(|_| b.next_u32()).collect();
+            assert_eq!(first, second);
+
+            a.reseed(1234);
+            let third: Vec<u32> = (0..64).map(|_| a.next_u32()).collect();
+            assert_eq!(first, third);
+
+            // renders made with older versions must stay the same
+            assert_eq!(first[..4], [108756110, 1421575963, 2940621783, 3266095209]);
+        }
+
+        #[test]
+        fn test_02() {
+            // nearby seeds give different sequences, including the seed 0
+            let sequences: Vec<Vec<u32>> = (0..4)
+                .map(|seed| {
+                    let mut rng = Rng::new(seed);
+                    (0..8).map(|_| rng.next_u32()).collect()
+                })
+                .collect();
+            for (i, a) in sequences.iter().enumerate() {
+                assert!(a.iter().all(|x| *x != 0));
+                for b in &sequences[i + 1..] {
+                    assert_ne!(a, b);
+                }
+            }
+        }
+
+        #[test]
+        fn test_03() {
+            let mut rng = Rng::new(42);
+            for _ in 0..10000 {
+                let x = rng.next_f32();
+                assert!((0.0..1.0).contains(&x), "{x}");
+                let x = rng.next_bipolar();
+                assert!((-1.0..1.0).contains(&x), "{x}");
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Built-in step sequencer, triggers the envelope channels in time with the host's transport.
+
+use nih_plug::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Maximum number of steps in a pattern
+pub(crate) const MAX_STEPS: usize = 32;
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum StepRate {
+    #[id = "1/8"]
+    #[name = "1/8"]
+    Eighth,
+    #[id = "1/16"]
+    #[name = "1/16"]
+    Sixteenth,
+    #[id = "1/32"]
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl StepRate {
+    /// Length of a single step in quarter notes
+    pub(crate) fn beats(&self) -> f64 {
+        match self {
+            StepRate::Eighth => 0.5,
+            StepRate::Sixteenth => 0.25,
+            StepRate::ThirtySecond => 0.125,
+        }
+    }
+}
+
+/// The steps of every envelope channel, each step stores a velocity where 0.0 means the step is
+/// off
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SequencerPattern {
+    #[serde(deserialize_with = "deserialize_steps")]
+    steps: [[f32; MAX_STEPS]; 16],
+}
+
+/// The state may have been edited by hand, so the velocities are clamped the same way as in
+/// [`SequencerPattern::set_velocity()`] before they reach the audio thread
+fn deserialize_steps<'de, D>(deserializer: D) -> Result<[[f32; MAX_STEPS]; 16], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut steps = <[[f32; MAX_STEPS]; 16]>::deserialize(deserializer)?;
+    for velocity in steps.iter_mut().flatten() {
+        *velocity = clamp_velocity(*velocity);
+    }
+    Ok(steps)
+}
+
+/// Keep a velocity within 0.0 -- 1.0, NaN turns the step off
+fn clamp_velocity(velocity: f32) -> f32 {
+    if velocity.is_nan() {
+        0.0
+    } else {
+        velocity.clamp(0.0, 1.0)
+    }
+}
+
+impl Default for SequencerPattern {
+    fn default() -> Self {
+        Self {
+            steps: [[0.0; MAX_STEPS]; 16],
+        }
+    }
+}
+
+impl SequencerPattern {
+    /// Velocity of the given step, 0.0 if the step is off
+    pub(crate) fn velocity(&self, channel: usize, step: usize) -> f32 {
+        self.steps[channel][step]
+    }
+
+    pub(crate) fn set_velocity(&mut self, channel: usize, step: usize, velocity: f32) {
+        self.steps[channel][step] = clamp_velocity(velocity);
+    }
+
+    pub(crate) fn channel_steps(&self, channel: usize) -> &[f32; MAX_STEPS] {
+        &self.steps[channel]
+    }
+
+    /// Turn off all steps of the given channel
+    pub(crate) fn clear_channel(&mut self, channel: usize) {
+        self.steps[channel] = [0.0; MAX_STEPS];
+    }
+}
+
+/// Find the step that is playing at the given position, counting from the start of the song.
+///
+/// Swing (0.0 -- 1.0) delays every second step, up to half a step.
+fn step_at(beat: f64, step_beats: f64, swing: f64) -> i64 {
+    // steps are swung in pairs
+    let pair_beats = step_beats * 2.0;
+    let pair = (beat / pair_beats).floor();
+    let beat_in_pair = beat - pair * pair_beats;
+
+    let second_step_start = step_beats * (1.0 + swing * 0.5);
+    if beat_in_pair >= second_step_start {
+        pair as i64 * 2 + 1
+    } else {
+        pair as i64 * 2
+    }
+}
+
+/// If a step starts within the sample at the given position, return the index of the step in
+/// the pattern.
+pub(crate) fn step_starting_at(
+    beat: f64,
+    beats_per_sample: f64,
+    step_beats: f64,
+    swing: f64,
+    step_count: usize,
+) -> Option<usize> {
+    let step = step_at(beat, step_beats, swing);
+    let previous_step = step_at(beat - beats_per_sample, step_beats, swing);
+
+    if step != previous_step {
+        Some(step.rem_euclid(step_count as i64) as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod deserialize {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // velocities are clamped to 0.0 -- 1.0
+            let mut steps = vec![vec![0.5; MAX_STEPS]; 16];
+            steps[0][0] = -1.0;
+            steps[0][1] = 2.0;
+            steps[15][31] = 1.0;
+            let json = serde_json::json!({ "steps": steps }).to_string();
+
+            let pattern: SequencerPattern = serde_json::from_str(&json).unwrap();
+            assert_eq!(pattern.velocity(0, 0), 0.0);
+            assert_eq!(pattern.velocity(0, 1), 1.0);
+            assert_eq!(pattern.velocity(0, 2), 0.5);
+            assert_eq!(pattern.velocity(15, 31), 1.0);
+        }
+
+        #[test]
+        fn test_02() {
+            // saved patterns load unchanged
+            let mut pattern = SequencerPattern::default();
+            pattern.set_velocity(3, 7, 0.25);
+            let json = serde_json::to_string(&pattern).unwrap();
+            assert_eq!(
+                serde_json::from_str::<SequencerPattern>(&json).unwrap(),
+                pattern
+            );
+        }
+
+        #[test]
+        fn test_03() {
+            // patterns with the wrong number of steps are rejected
+            let json = serde_json::json!({ "steps": vec![vec![0.5; MAX_STEPS]; 15] }).to_string();
+            assert!(serde_json::from_str::<SequencerPattern>(&json).is_err());
+        }
+    }
+
+    mod set_velocity {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            let mut pattern = SequencerPattern::default();
+            pattern.set_velocity(0, 0, f32::NAN);
+            pattern.set_velocity(0, 1, -0.5);
+            pattern.set_velocity(0, 2, 1.5);
+            assert_eq!(pattern.velocity(0, 0), 0.0);
+            assert_eq!(pattern.velocity(0, 1), 0.0);
+            assert_eq!(pattern.velocity(0, 2), 1.0);
+        }
+    }
+
+    mod step_at {
+        use super::*;
+
+        /// Without swing, steps start every `step_beats`
+        #[test]
+        fn test_01() {
+            assert_eq!(step_at(0.0, 0.25, 0.0), 0);
+            assert_eq!(step_at(0.2, 0.25, 0.0), 0);
+            assert_eq!(step_at(0.25, 0.25, 0.0), 1);
+            assert_eq!(step_at(0.5, 0.25, 0.0), 2);
+            assert_eq!(step_at(1.0, 0.25, 0.0), 4);
+        }
+
+        /// Swing delays the second step of every pair, the first step stays in place
+        #[test]
+        fn test_02() {
+            // full swing moves the second step by half a step
+            assert_eq!(step_at(0.25, 0.25, 1.0), 0);
+            assert_eq!(step_at(0.37, 0.25, 1.0), 0);
+            assert_eq!(step_at(0.375, 0.25, 1.0), 1);
+            assert_eq!(step_at(0.5, 0.25, 1.0), 2);
+            assert_eq!(step_at(0.875, 0.25, 1.0), 3);
+        }
+
+        /// Positions before the start of the song give negative steps
+        #[test]
+        fn test_03() {
+            assert_eq!(step_at(-0.01, 0.25, 0.0), -1);
+            assert_eq!(step_at(-0.25, 0.25, 0.0), -1);
+            assert_eq!(step_at(-0.26, 0.25, 0.0), -2);
+            assert_eq!(step_at(-0.5, 0.25, 0.0), -2);
+        }
+    }
+
+    mod step_starting_at {
+        use super::*;
+
+        /// A step that starts exactly on a sample is triggered on that sample only
+        #[test]
+        fn test_01() {
+            assert_eq!(step_starting_at(0.0, 0.0625, 0.25, 0.0, 16), Some(0));
+            assert_eq!(step_starting_at(0.0625, 0.0625, 0.25, 0.0, 16), None);
+            assert_eq!(step_starting_at(0.1875, 0.0625, 0.25, 0.0, 16), None);
+            assert_eq!(step_starting_at(0.25, 0.0625, 0.25, 0.0, 16), Some(1));
+            assert_eq!(step_starting_at(0.3125, 0.0625, 0.25, 0.0, 16), None);
+        }
+
+        /// A step that starts between two samples is triggered on the later sample
+        #[test]
+        fn test_02() {
+            assert_eq!(step_starting_at(0.2, 0.1, 0.25, 0.0, 16), None);
+            assert_eq!(step_starting_at(0.3, 0.1, 0.25, 0.0, 16), Some(1));
+            assert_eq!(step_starting_at(0.4, 0.1, 0.25, 0.0, 16), None);
+            assert_eq!(step_starting_at(0.5, 0.1, 0.25, 0.0, 16), Some(2));
+        }
+
+        /// Swung steps are triggered at their delayed position
+        #[test]
+        fn test_03() {
+            // half swing moves the second step by a quarter step, to 0.3125
+            assert_eq!(step_starting_at(0.25, 0.0625, 0.25, 0.5, 16), None);
+            assert_eq!(step_starting_at(0.3125, 0.0625, 0.25, 0.5, 16), Some(1));
+            assert_eq!(step_starting_at(0.375, 0.0625, 0.25, 0.5, 16), None);
+            assert_eq!(step_starting_at(0.5, 0.0625, 0.25, 0.5, 16), Some(2));
+
+            // between samples, the swung step is triggered on the next sample
+            assert_eq!(step_starting_at(0.3, 0.1, 0.25, 0.5, 16), None);
+            assert_eq!(step_starting_at(0.4, 0.1, 0.25, 0.5, 16), Some(1));
+        }
+
+        /// Steps wrap around the length of the pattern, also before the start of the song
+        #[test]
+        fn test_04() {
+            assert_eq!(step_starting_at(1.0, 0.0625, 0.25, 0.0, 4), Some(0));
+            assert_eq!(step_starting_at(1.25, 0.0625, 0.25, 0.0, 4), Some(1));
+            assert_eq!(step_starting_at(-0.25, 0.0625, 0.25, 0.0, 4), Some(3));
+            assert_eq!(step_starting_at(-0.5, 0.0625, 0.25, 0.0, 4), Some(2));
+        }
+    }
+}
@@ -2,7 +2,7 @@ use nih_plug::util::{db_to_gain, gain_to_db};
 
 use crate::{
     biquad::{
-        CookbookAP, CookbookHP, CookbookLP, FirstOrderAP, FixedQFilter, GainlessFilter,
+        Complex, CookbookAP, CookbookHP, CookbookLP, FirstOrderAP, FixedQFilter, GainlessFilter,
         LinkwitzRileyHP, LinkwitzRileyLP,
     },
     svf::{GainFilter, HighShelf, LowShelf},
@@ -39,6 +39,13 @@ impl MinimumTwoBand24Slope {
         let high = self.hpf2.process_sample(self.hpf1.process_sample(sample));
         [low, high]
     }
+
+    /// The complex gain of each band at frequency `f`, matching [`Self::split_bands()`]
+    pub(crate) fn frequency_response(&self, f: Precision) -> [Complex; 2] {
+        let low = self.lpf1.frequency_response(f) * self.lpf2.frequency_response(f);
+        let high = self.hpf1.frequency_response(f) * self.hpf2.frequency_response(f);
+        [low, high]
+    }
 }
 
 pub(crate) struct MinimumTwoBand12Slope {
@@ -64,6 +71,13 @@ impl MinimumTwoBand12Slope {
         let high = self.hpf.process_sample(sample);
         [low, -high]
     }
+
+    /// The complex gain of each band at frequency `f`, matching [`Self::split_bands()`]
+    pub(crate) fn frequency_response(&self, f: Precision) -> [Complex; 2] {
+        let low = self.lpf.frequency_response(f);
+        let high = self.hpf.frequency_response(f);
+        [low, -high]
+    }
 }
 
 pub(crate) struct MinimumThreeBand12Slope {
@@ -111,6 +125,15 @@ impl MinimumThreeBand12Slope {
         let high = -self.hpf2.process_sample(midhigh);
         [low, mid, high]
     }
+
+    /// The complex gain of each band at frequency `f`, matching [`Self::split_bands()`]
+    pub(crate) fn frequency_response(&self, f: Precision) -> [Complex; 3] {
+        let low = self.lpf1.frequency_response(f) * self.apf.frequency_response(f);
+        let midhigh = -self.hpf1.frequency_response(f);
+        let mid = midhigh * self.lpf2.frequency_response(f);
+        let high = -(midhigh * self.hpf2.frequency_response(f));
+        [low, mid, high]
+    }
 }
 
 pub(crate) struct MinimumThreeBand24Slope {
@@ -172,6 +195,17 @@ impl MinimumThreeBand24Slope {
         let high = self.hpf4.process_sample(self.hpf3.process_sample(midhigh));
         [low, mid, high]
     }
+
+    /// The complex gain of each band at frequency `f`, matching [`Self::split_bands()`]
+    pub(crate) fn frequency_response(&self, f: Precision) -> [Complex; 3] {
+        let low = self.lpf1.frequency_response(f)
+            * self.lpf2.frequency_response(f)
+            * self.apf.frequency_response(f);
+        let midhigh = self.hpf1.frequency_response(f) * self.hpf2.frequency_response(f);
+        let mid = midhigh * self.lpf3.frequency_response(f) * self.lpf4.frequency_response(f);
+        let high = midhigh * self.hpf3.frequency_response(f) * self.hpf4.frequency_response(f);
+        [low, mid, high]
+    }
 }
 
 pub(crate) struct DynamicThreeBand24Slope {
@@ -229,3 +263,63 @@ impl SingleBand {
         sample * gain
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frequencies to check the responses at, in Hz
+    const FREQUENCIES: [Precision; 6] = [20.0, 100.0, 500.0, 1000.0, 5000.0, 18000.0];
+
+    mod frequency_response {
+        use super::*;
+
+        #[test]
+        fn test_01() {
+            // the 24dB bands sum to a flat magnitude
+            let splitter = MinimumThreeBand24Slope::new(200.0, 2000.0, 48000.0);
+            for f in FREQUENCIES {
+                let [low, mid, high] = splitter.frequency_response(f);
+                let sum = (low + mid + high).norm();
+                assert!((sum - 1.0).abs() < 1e-6, "{} Hz: {}", f, sum);
+            }
+        }
+
+        #[test]
+        fn test_02() {
+            // the 12dB bands sum to a flat magnitude
+            let splitter = MinimumThreeBand12Slope::new(200.0, 2000.0, 48000.0);
+            for f in FREQUENCIES {
+                let [low, mid, high] = splitter.frequency_response(f);
+                let sum = (low + mid + high).norm();
+                assert!((sum - 1.0).abs() < 1e-6, "{} Hz: {}", f, sum);
+            }
+        }
+
+        #[test]
+        fn test_03() {
+            // the response matches the output of the filters, for a sine well past the transient
+            let sr = 48000.0;
+            let f = 1000.0;
+            let mut splitter = MinimumThreeBand24Slope::new(200.0, 2000.0, sr);
+            let expected = splitter.frequency_response(f);
+
+            let mut outputs = [0.0; 3];
+            let n = 48000;
+            for i in 0..=n {
+                let x = (std::f64::consts::TAU * f * i as Precision / sr).sin();
+                outputs = splitter.split_bands(x);
+            }
+            for (output, expected) in outputs.iter().zip(expected) {
+                // the sine at the last sample has a phase of 0
+                let predicted = expected.norm() * expected.arg().sin();
+                assert!(
+                    (output - predicted).abs() < 1e-4,
+                    "{} {}",
+                    output,
+                    predicted
+                );
+            }
+        }
+    }
+}